@@ -0,0 +1,250 @@
+use crate::{
+    params::EvalParams,
+    piece::{Piece, Position, WHITE},
+    search::is_mate_value,
+};
+
+// What the computer does along with its move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Decision {
+    // Play the move.
+    Play,
+    // Play the move and offer a draw, which the other player can accept
+    // instead of moving.
+    OfferDraw,
+    // Resign instead of moving, by playing `Move::Resign`.
+    Resign,
+}
+
+// Decides when the computer resigns a lost game, and when it offers or
+// accepts a draw in a dead drawn one, from the values its searches give
+// its position move after move.
+//
+// It resigns once its position has been worse than the resign threshold,
+// in pawns, for a number of moves in a row, so that a single bad search
+// isn't enough. It offers a draw late in the game, once the value has
+// stayed within the draw threshold of zero for a number of moves in a row,
+// and accepts one late in the game when it isn't better than that.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Adjudicator {
+    resign_threshold: Option<f64>,
+    resign_moves: u32,
+    draw_threshold: Option<f64>,
+    draw_moves: u32,
+    // The number of the computer's own moves before it talks about draws.
+    draw_after: u32,
+
+    moves: u32,
+    losing_moves: u32,
+    drawn_moves: u32,
+    // The last value of the computer's position, in pawns.
+    value: Option<f64>,
+}
+
+impl Adjudicator {
+    pub const DEFAULT_RESIGN_THRESHOLD: f64 = 6.0;
+    pub const DEFAULT_RESIGN_MOVES: u32 = 4;
+    pub const DEFAULT_DRAW_THRESHOLD: f64 = 0.2;
+    pub const DEFAULT_DRAW_MOVES: u32 = 8;
+    pub const DEFAULT_DRAW_AFTER: u32 = 40;
+
+    pub fn new() -> Self {
+        Self {
+            resign_threshold: Some(Self::DEFAULT_RESIGN_THRESHOLD),
+            resign_moves: Self::DEFAULT_RESIGN_MOVES,
+            draw_threshold: Some(Self::DEFAULT_DRAW_THRESHOLD),
+            draw_moves: Self::DEFAULT_DRAW_MOVES,
+            draw_after: Self::DEFAULT_DRAW_AFTER,
+            moves: 0,
+            losing_moves: 0,
+            drawn_moves: 0,
+            value: None,
+        }
+    }
+
+    // An adjudicator that always plays on, like the computer did before.
+    pub fn never() -> Self {
+        Self::new().without_resigning().without_draws()
+    }
+
+    // Resign after `moves` moves in a row with the position worse than
+    // `threshold` pawns.
+    pub fn with_resigning(mut self, threshold: f64, moves: u32) -> Self {
+        self.resign_threshold = Some(threshold.abs());
+        self.resign_moves = moves.max(1);
+        self
+    }
+
+    pub fn without_resigning(mut self) -> Self {
+        self.resign_threshold = None;
+        self
+    }
+
+    // Offer a draw after `moves` moves in a row with the position within
+    // `threshold` pawns of even, once the computer has played `after` moves.
+    pub fn with_draws(mut self, threshold: f64, moves: u32, after: u32) -> Self {
+        self.draw_threshold = Some(threshold.abs());
+        self.draw_moves = moves.max(1);
+        self.draw_after = after;
+        self
+    }
+
+    pub fn without_draws(mut self) -> Self {
+        self.draw_threshold = None;
+        self
+    }
+
+    #[inline]
+    pub fn get_resign_threshold(&self) -> Option<f64> {
+        self.resign_threshold
+    }
+
+    #[inline]
+    pub fn get_resign_moves(&self) -> u32 {
+        self.resign_moves
+    }
+
+    #[inline]
+    pub fn get_draw_threshold(&self) -> Option<f64> {
+        self.draw_threshold
+    }
+
+    #[inline]
+    pub fn get_draw_moves(&self) -> u32 {
+        self.draw_moves
+    }
+
+    #[inline]
+    pub fn get_draw_after(&self) -> u32 {
+        self.draw_after
+    }
+
+    // Forget the moves of the last game.
+    pub fn reset(&mut self) {
+        self.moves = 0;
+        self.losing_moves = 0;
+        self.drawn_moves = 0;
+        self.value = None;
+    }
+
+    // Record the value the computer's search gave its position before it
+    // plays, and decide what it does with its move.
+    pub fn update(&mut self, value: f64, params: &EvalParams) -> Decision {
+        // Forced mates are further from even than any other value.
+        let pawns = if is_mate_value(value) {
+            value.signum() * f64::INFINITY
+        } else {
+            value / params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)))
+        };
+        self.moves += 1;
+        self.value = Some(pawns);
+
+        match self.resign_threshold {
+            Some(threshold) if pawns <= -threshold => self.losing_moves += 1,
+            _ => self.losing_moves = 0,
+        }
+        match self.draw_threshold {
+            Some(threshold) if pawns.abs() <= threshold => self.drawn_moves += 1,
+            _ => self.drawn_moves = 0,
+        }
+
+        if self.resign_threshold.is_some() && self.losing_moves >= self.resign_moves {
+            Decision::Resign
+        } else if self.is_late() && self.drawn_moves >= self.draw_moves {
+            // Wait as long again before offering another draw.
+            self.drawn_moves = 0;
+            Decision::OfferDraw
+        } else {
+            Decision::Play
+        }
+    }
+
+    // Does the computer accept the draw the other player offers, from the
+    // last value it gave its position?
+    pub fn accepts_draw(&self) -> bool {
+        match (self.draw_threshold, self.value) {
+            (Some(threshold), Some(pawns)) => self.is_late() && pawns <= threshold,
+            _ => false,
+        }
+    }
+
+    #[inline]
+    fn is_late(&self) -> bool {
+        self.draw_threshold.is_some() && self.moves >= self.draw_after
+    }
+}
+
+impl Default for Adjudicator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE_VALUE;
+
+    // Feed the adjudicator values in pawns and collect its decisions.
+    fn run(adjudicator: &mut Adjudicator, values: &[f64]) -> Vec<Decision> {
+        let params = EvalParams::default();
+        let pawn = params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)));
+        values
+            .iter()
+            .map(|value| adjudicator.update(value * pawn, &params))
+            .collect()
+    }
+
+    #[test]
+    fn resigns_after_losing_moves_in_a_row() {
+        let mut adjudicator = Adjudicator::new().with_resigning(5.0, 3);
+        let decisions = run(&mut adjudicator, &[-6.0, -6.0, -1.0, -6.0, -7.0, -8.0]);
+        assert_eq!(decisions[..5], [Decision::Play; 5]);
+        assert_eq!(decisions[5], Decision::Resign);
+
+        // A mate against the computer is as lost as it gets.
+        adjudicator.reset();
+        let params = EvalParams::default();
+        let decisions: Vec<Decision> = (0..3)
+            .map(|_| adjudicator.update(-MATE_VALUE + 10.0, &params))
+            .collect();
+        assert_eq!(
+            decisions,
+            [Decision::Play, Decision::Play, Decision::Resign]
+        );
+
+        let mut adjudicator = Adjudicator::never();
+        assert!(run(&mut adjudicator, &[-20.0; 10])
+            .iter()
+            .all(|&decision| decision == Decision::Play));
+    }
+
+    #[test]
+    fn offers_and_accepts_draws_late_in_dead_drawn_games() {
+        let mut adjudicator = Adjudicator::new().without_resigning().with_draws(0.2, 2, 4);
+        // Too early: the value is even but the computer hasn't played enough.
+        assert_eq!(run(&mut adjudicator, &[0.0, 0.1]), [Decision::Play; 2]);
+        assert!(!adjudicator.accepts_draw());
+        assert_eq!(
+            run(&mut adjudicator, &[0.0, 0.1, 0.0, 0.0, 0.0]),
+            [
+                Decision::Play,
+                Decision::OfferDraw,
+                Decision::Play,
+                Decision::OfferDraw,
+                Decision::Play,
+            ]
+        );
+        assert!(adjudicator.accepts_draw());
+
+        // It accepts when worse, but not when better.
+        run(&mut adjudicator, &[-3.0]);
+        assert!(adjudicator.accepts_draw());
+        run(&mut adjudicator, &[1.0]);
+        assert!(!adjudicator.accepts_draw());
+
+        adjudicator.reset();
+        assert!(!adjudicator.accepts_draw());
+        assert!(!Adjudicator::never().accepts_draw());
+    }
+}
@@ -0,0 +1,215 @@
+// Solve puzzles in the terminal, from a CSV file in the format of the
+// Lichess puzzle database. Puzzles are picked close to the player's puzzle
+// rating, which is kept in a file between runs.
+//
+// Moves are typed in SAN or UCI notation. `hint` shows the piece to move,
+// `skip` gives up on the puzzle and `quit` stops.
+//
+// usage: puzzle <puzzles.csv> [--id <id>] [--rating-file <file>]
+
+use chess::{
+    board::Board,
+    game::{Evaluate, Move},
+    piece::{Color, Position},
+    puzzle::{self, Puzzle, PuzzleAttempt, Step},
+};
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    process,
+};
+
+// Where the puzzle rating is kept when no other file is given.
+const DEFAULT_RATING_FILE: &str = ".puzzle-rating";
+
+struct Options {
+    path: String,
+    id: Option<String>,
+    rating_file: String,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        path: String::new(),
+        id: None,
+        rating_file: String::from(DEFAULT_RATING_FILE),
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for `{}`", name));
+        match arg.as_str() {
+            "--id" => options.id = Some(value(&arg)?),
+            "--rating-file" => options.rating_file = value(&arg)?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ if options.path.is_empty() => options.path = arg,
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    if options.path.is_empty() {
+        return Err(String::from(
+            "usage: puzzle <puzzles.csv> [--id <id>] [--rating-file <file>]",
+        ));
+    }
+    Ok(options)
+}
+
+fn read_rating(path: &str) -> f64 {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(puzzle::DEFAULT_RATING)
+}
+
+// Draw the board from the side of the player solving the puzzle.
+fn print_board(board: &Board, color: Color) {
+    let rows = match color {
+        Color::White => (0..8).rev().collect::<Vec<i32>>(),
+        Color::Black => (0..8).collect(),
+    };
+    let cols = match color {
+        Color::White => (0..8).collect::<Vec<i32>>(),
+        Color::Black => (0..8).rev().collect(),
+    };
+    for row in &rows {
+        let squares = cols
+            .iter()
+            .map(|col| match board.get_piece(Position::new(*row, *col)) {
+                Some(piece) if piece.get_color() == Color::White => piece.get_type().to_string(),
+                Some(piece) => piece.get_type().to_lowercase(),
+                None => String::from("."),
+            })
+            .collect::<Vec<String>>();
+        println!("{} {}", row + 1, squares.join(" "));
+    }
+    let files = cols
+        .iter()
+        .map(|col| ((b'a' + *col as u8) as char).to_string())
+        .collect::<Vec<String>>();
+    println!("  {}", files.join(" "));
+}
+
+fn parse_move(board: &Board, text: &str) -> Result<Move, String> {
+    board.parse_san(text).or_else(|_| board.parse_uci(text))
+}
+
+// Let the player solve a puzzle. Returns whether they solved it, or `None`
+// if they quit.
+fn solve(puzzle: &Puzzle, input: &mut impl BufRead) -> Option<bool> {
+    let mut attempt = PuzzleAttempt::new(puzzle.clone());
+    let setup = puzzle.get_board().get_san(puzzle.get_moves()[0]);
+    println!(
+        "\nPuzzle {}, rated {}. {} to move after {}.",
+        puzzle.get_id(),
+        puzzle.get_rating(),
+        puzzle.get_color(),
+        setup
+    );
+
+    loop {
+        let board = attempt.get_board();
+        print_board(&board, puzzle.get_color());
+        print!("> ");
+        let _ = io::stdout().flush();
+
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let expected = attempt.get_expected_move()?;
+        match line.trim() {
+            "quit" => return None,
+            "skip" => {
+                println!("The solution was {}.", board.get_san(expected));
+                return Some(false);
+            }
+            "hint" => {
+                let from = board.get_uci(expected)[..2].to_string();
+                println!("Move the piece on {}.", from);
+                continue;
+            }
+            _ => {}
+        }
+
+        let m = match parse_move(&board, line.trim()) {
+            Ok(m) => m,
+            Err(e) => {
+                println!("{}", e);
+                continue;
+            }
+        };
+        match attempt.play(m) {
+            Ok(Step::Correct(reply)) => {
+                println!(
+                    "{} is right. The opponent plays {}.",
+                    board.get_san(m),
+                    board.apply_eval_move(m).get_san(reply)
+                );
+            }
+            Ok(Step::Solved) => {
+                println!("{} solves the puzzle.", board.get_san(m));
+                return Some(true);
+            }
+            Ok(Step::Wrong(expected)) => {
+                println!(
+                    "{} is wrong. The solution was {}.",
+                    board.get_san(m),
+                    board.get_san(expected)
+                );
+                return Some(false);
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let (puzzles, errors) = puzzle::read_file(&options.path)?;
+    for e in &errors {
+        eprintln!("skipped {}", e);
+    }
+    let mut rating = read_rating(&options.rating_file);
+    println!(
+        "Loaded {} puzzles. Your puzzle rating is {:.0}.",
+        puzzles.len(),
+        rating
+    );
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    loop {
+        let puzzle = match &options.id {
+            Some(id) => puzzles
+                .iter()
+                .find(|puzzle| puzzle.get_id() == id)
+                .ok_or(format!("no puzzle `{}`", id))?,
+            None => puzzle::pick_puzzle(&puzzles, rating).ok_or("no puzzles to solve")?,
+        };
+
+        let solved = match solve(puzzle, &mut input) {
+            Some(solved) => solved,
+            None => return Ok(()),
+        };
+        let new_rating = puzzle::update_rating(rating, puzzle.get_rating(), solved);
+        println!(
+            "Your puzzle rating is {:.0} ({:+.0}).",
+            new_rating,
+            new_rating - rating
+        );
+        rating = new_rating;
+        fs::write(&options.rating_file, format!("{}\n", rating))
+            .map_err(|e| format!("could not write `{}`: {}", options.rating_file, e))?;
+
+        if options.id.is_some() {
+            return Ok(());
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = parse_options().and_then(run) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,252 @@
+// Review a game with the engine: every move is classified as best, good,
+// an inaccuracy, a mistake or a blunder by how much it lost, and the game
+// is written back in PGN with the review in it, followed by the accuracy
+// and average centipawn loss of both players.
+//
+// The game is read from the first one in a PGN file, which can start from
+// a position given by a `FEN` tag.
+//
+// usage: review <game.pgn> [--depth <n>] [--time <ms>] [--threads <n>]
+//               [--hash <mb>] [--params <file>] [--output <file>]
+
+use chess::{
+    board::Board,
+    game::{Evaluate, Move},
+    params::EvalParams,
+    piece::Color,
+    review::{Classification, Review},
+    search::{Search, SearchLimits},
+};
+use std::{env, fs, process, time::Duration};
+
+struct Options {
+    path: String,
+    depth: i32,
+    time: Option<Duration>,
+    threads: usize,
+    hash: usize,
+    params: Option<String>,
+    output: Option<String>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        path: String::new(),
+        depth: 6,
+        time: None,
+        threads: 1,
+        hash: Search::DEFAULT_HASH,
+        params: None,
+        output: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for `{}`", name));
+        match arg.as_str() {
+            "--depth" => {
+                options.depth = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid depth"))?
+            }
+            "--time" => {
+                let ms = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid time"))?;
+                options.time = Some(Duration::from_millis(ms))
+            }
+            "--threads" => {
+                options.threads = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid number of threads"))?
+            }
+            "--hash" => {
+                options.hash = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid hash size"))?
+            }
+            "--params" => options.params = Some(value(&arg)?),
+            "--output" => options.output = Some(value(&arg)?),
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ if options.path.is_empty() => options.path = arg,
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    if options.path.is_empty() {
+        return Err(String::from(
+            "usage: review <game.pgn> [--depth <n>] [--time <ms>] [--threads <n>] [--hash <mb>] \
+             [--params <file>] [--output <file>]",
+        ));
+    }
+    Ok(options)
+}
+
+// A game read from a PGN file.
+struct Game {
+    tags: Vec<(String, String)>,
+    start: Board,
+    moves: Vec<Move>,
+    result: String,
+}
+
+// Read the first game of a PGN file. Comments, variations and annotation
+// glyphs are skipped, since the review replaces them.
+fn parse_game(text: &str) -> Result<Game, String> {
+    let mut tags = vec![];
+    let mut movetext = String::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(tag) = line.strip_prefix('[').and_then(|tag| tag.strip_suffix(']')) {
+            if !movetext.trim().is_empty() {
+                break;
+            }
+            let (name, value) = tag
+                .split_once(' ')
+                .ok_or(format!("invalid tag `{}`", line))?;
+            let value = value.trim().trim_matches('"').replace("\\\"", "\"");
+            tags.push((name.to_string(), value));
+        } else if !line.starts_with('%') {
+            movetext += line;
+            movetext += "\n";
+        }
+    }
+
+    let mut board = match tags.iter().find(|(name, _)| name == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen)?,
+        None => Board::new(),
+    };
+    let start = board;
+    let mut moves = vec![];
+    let mut result = String::from("*");
+    let mut depth = 0;
+    let mut chars = movetext.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => {
+                for ch in chars.by_ref() {
+                    if ch == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                for ch in chars.by_ref() {
+                    if ch == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ if ch.is_whitespace() || depth > 0 => {}
+            _ => {
+                let mut token = ch.to_string();
+                while let Some(ch) = chars.peek() {
+                    if ch.is_whitespace() || "{;()".contains(*ch) {
+                        break;
+                    }
+                    token.push(*ch);
+                    chars.next();
+                }
+
+                // Skip move numbers like `12.` or `12...`, before a move
+                // written right after them too.
+                let token = token.trim_start_matches(|ch: char| ch.is_ascii_digit() || ch == '.');
+                if token.is_empty() || token.starts_with('$') {
+                    continue;
+                }
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token) {
+                    result = token.to_string();
+                    break;
+                }
+                // The digits of a result were taken for a move number.
+                if ["-0", "-1", "/2-1/2"].contains(&token) {
+                    result = format!("{}{}", ch, token);
+                    break;
+                }
+                let m = board.parse_san(token)?;
+                moves.push(m);
+                board = board.apply_eval_move(m);
+            }
+        }
+    }
+
+    Ok(Game {
+        tags,
+        start,
+        moves,
+        result,
+    })
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let params = match &options.params {
+        Some(path) => EvalParams::open(path)?,
+        None => EvalParams::default(),
+    };
+    let text = fs::read_to_string(&options.path)
+        .map_err(|e| format!("could not read `{}`: {}", options.path, e))?;
+    let game = parse_game(&text)?;
+    let search = Search::new(options.threads, options.hash);
+    let limits = match options.time {
+        Some(time) => SearchLimits::new().with_time(time),
+        None => SearchLimits::new().with_depth(options.depth),
+    };
+
+    let review = Review::run(
+        game.start,
+        &game.moves,
+        &search,
+        &limits,
+        &params,
+        |done, total| eprint!("\rReviewing position {}/{}", done + 1, total),
+    )?;
+    eprintln!();
+
+    // The tags that the review writes itself are left out.
+    let tags = game
+        .tags
+        .iter()
+        .filter(|(name, _)| !["Result", "SetUp", "FEN"].contains(&name.as_str()))
+        .filter(|(name, _)| !name.ends_with("Accuracy") && !name.ends_with("ACPL"))
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect::<Vec<(&str, &str)>>();
+    let pgn = review.to_pgn(&tags, &game.result);
+    match &options.output {
+        Some(path) => {
+            fs::write(path, &pgn).map_err(|e| format!("could not write `{}`: {}", path, e))?
+        }
+        None => println!("{}", pgn),
+    }
+
+    for color in [Color::White, Color::Black] {
+        let counts = Classification::ALL
+            .iter()
+            .filter(|classification| classification.is_error())
+            .map(|classification| {
+                format!(
+                    "{} {}",
+                    review.count(color, *classification),
+                    classification.get_name().to_lowercase()
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        println!(
+            "{:?}: accuracy {:.1}%, average centipawn loss {:.0}, {}",
+            color,
+            review.get_accuracy(color),
+            review.get_average_loss(color),
+            counts
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = parse_options().and_then(run) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,516 @@
+use chess::{
+    adjudication::{Adjudicator, Decision},
+    board::Board,
+    book::Book,
+    difficulty::Difficulty,
+    game::Move,
+    params::EvalParams,
+    piece::WHITE,
+    search::{Search, SearchFeatures, SearchLimits},
+    tablebase::{self, Tablebase},
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+// How long an external engine may take to start up or get ready.
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How long past its deadline an external engine is waited for, before it
+// is considered to be hanging.
+const GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+// The time left on the clocks of both players, and the time added after
+// every move.
+#[derive(Clone, Copy, Debug)]
+pub struct Clock {
+    pub white: Duration,
+    pub black: Duration,
+    pub increment: Duration,
+}
+
+impl Clock {
+    // Parse a time control like `60+0.6`, in seconds for the game plus
+    // seconds added per move.
+    pub fn parse(tc: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid time control `{}`, expected `<seconds>+<increment>`",
+                tc
+            )
+        };
+        let (base, increment) = tc.split_once('+').unwrap_or((tc, "0"));
+        let base = base.parse::<f64>().map_err(|_| invalid())?;
+        let increment = increment.parse::<f64>().map_err(|_| invalid())?;
+        if base <= 0.0 || increment < 0.0 {
+            return Err(invalid());
+        }
+        Ok(Self {
+            white: Duration::from_secs_f64(base),
+            black: Duration::from_secs_f64(base),
+            increment: Duration::from_secs_f64(increment),
+        })
+    }
+
+    #[inline]
+    pub fn get_remaining(&self, board: &Board) -> Duration {
+        if board.get_turn_color() == WHITE {
+            self.white
+        } else {
+            self.black
+        }
+    }
+}
+
+// One side of a match, configured from a list of `key=value` options like
+// `name=tuned,depth=3,params=tuned.toml` or `cmd=stockfish,option.Hash=16`.
+//
+// - `name`: the name shown in the results and the PGN.
+// - `depth`: the search depth, when there is no time control.
+// - `movetime`: the milliseconds to think per move, when there is no time
+//   control.
+// - `cmd`: an external UCI engine to run instead of the built-in one.
+// - `option.<name>`: a UCI option to set on the external engine, like
+//   `option.UCI_LimitStrength=true,option.UCI_Elo=1500`.
+//
+// These options are only used by the built-in engine:
+//
+// - `level`: a difficulty level from 1 to 20 to play at, instead of the
+//   depth and the time given.
+// - `threads`: the number of threads searching, 1 by default.
+// - `hash`: the size of the transposition table in megabytes.
+// - `params`: a TOML or JSON file of evaluation parameters.
+// - `book`: a Polyglot opening book, played from at random by weight.
+// - `tablebase`: a directory of Syzygy endgame tables.
+// - `null-move`, `lmr`, `futility`, `reverse-futility`, `check-extensions`:
+//   `on` or `off` to turn a selective part of the search on or off, all on
+//   by default.
+// - `resign`: `<pawns>:<moves>` to resign after being worse than that many
+//   pawns for that many moves in a row. The engine plays on by default.
+// - `draw`: `<pawns>:<moves>:<after>` to offer and accept draws once it has
+//   played `after` moves, after the game stays within that many pawns of
+//   even for that many moves in a row. It never does by default.
+pub struct Engine {
+    name: String,
+    depth: i32,
+    movetime: Option<Duration>,
+    player: Player,
+}
+
+enum Player {
+    Native {
+        params: Box<EvalParams>,
+        difficulty: Option<Difficulty>,
+        search: Search,
+        book: Option<Book>,
+        tablebase: Option<Arc<Tablebase>>,
+        adjudicator: Adjudicator,
+    },
+    Uci(UciEngine),
+}
+
+impl Engine {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut depth = 2;
+        let mut movetime = None;
+        let mut command = None;
+        let mut uci_options = vec![];
+        let mut params = None;
+        let mut difficulty = None;
+        let mut threads = None;
+        let mut hash = None;
+        let mut book = None;
+        let mut tablebase = None;
+        let mut features = SearchFeatures::default();
+        let mut adjudicator = Adjudicator::never();
+
+        for option in spec.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = option.split_once('=').ok_or(format!(
+                "invalid engine option `{}`, expected `key=value`",
+                option
+            ))?;
+            match key {
+                "name" => name = Some(value.to_string()),
+                "depth" => {
+                    depth = value
+                        .parse()
+                        .map_err(|_| format!("invalid depth `{}`", value))?
+                }
+                "movetime" => {
+                    let ms = value
+                        .parse()
+                        .map_err(|_| format!("invalid move time `{}`", value))?;
+                    movetime = Some(Duration::from_millis(ms))
+                }
+                "cmd" => command = Some(value.to_string()),
+                "level" => {
+                    let level = value
+                        .parse()
+                        .map_err(|_| format!("invalid level `{}`", value))?;
+                    difficulty = Some(Difficulty::new(level)?)
+                }
+                "threads" => {
+                    threads = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid number of threads `{}`", value))?,
+                    )
+                }
+                "hash" => {
+                    hash = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid hash size `{}`", value))?,
+                    )
+                }
+                "params" => params = Some(EvalParams::open(value)?),
+                "book" => book = Some(Book::open(value)?),
+                "tablebase" => tablebase = Some(Arc::new(Tablebase::open(value)?)),
+                "resign" => {
+                    let (threshold, moves) = value
+                        .split_once(':')
+                        .and_then(|(threshold, moves)| {
+                            Some((threshold.parse().ok()?, moves.parse().ok()?))
+                        })
+                        .ok_or(format!(
+                            "invalid resign rule `{}`, expected `<pawns>:<moves>`",
+                            value
+                        ))?;
+                    adjudicator = adjudicator.with_resigning(threshold, moves)
+                }
+                "draw" => {
+                    let parts = value.split(':').collect::<Vec<&str>>();
+                    let (threshold, moves, after) = match parts[..] {
+                        [threshold, moves, after] => threshold
+                            .parse()
+                            .ok()
+                            .zip(moves.parse().ok())
+                            .zip(after.parse().ok())
+                            .map(|((threshold, moves), after)| (threshold, moves, after)),
+                        _ => None,
+                    }
+                    .ok_or(format!(
+                        "invalid draw rule `{}`, expected `<pawns>:<moves>:<after>`",
+                        value
+                    ))?;
+                    adjudicator = adjudicator.with_draws(threshold, moves, after)
+                }
+                _ if SearchFeatures::NAMES.contains(&key) => {
+                    let enabled = match value {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("invalid switch `{}`, expected on or off", value)),
+                    };
+                    features.set(key, enabled)?
+                }
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => uci_options.push((option.to_string(), value.to_string())),
+                    None => return Err(format!("unknown engine option `{}`", key)),
+                },
+            }
+        }
+
+        let player = match command {
+            Some(command) => {
+                if params.is_some()
+                    || difficulty.is_some()
+                    || threads.is_some()
+                    || hash.is_some()
+                    || book.is_some()
+                    || tablebase.is_some()
+                    || features != SearchFeatures::default()
+                    || adjudicator != Adjudicator::never()
+                {
+                    return Err(String::from(
+                        "`level`, `threads`, `hash`, `params`, `book`, `tablebase`, `resign`, \
+                         `draw` and the search features only apply to the built-in engine, set \
+                         UCI options on external ones",
+                    ));
+                }
+                Player::Uci(UciEngine::start(&command, &uci_options)?)
+            }
+            None => {
+                if !uci_options.is_empty() {
+                    return Err(String::from("UCI options only apply to external engines"));
+                }
+                let mut search =
+                    Search::new(threads.unwrap_or(1), hash.unwrap_or(Search::DEFAULT_HASH));
+                search.set_features(features);
+                Player::Native {
+                    params: Box::new(params.unwrap_or_default()),
+                    difficulty,
+                    search,
+                    book,
+                    tablebase,
+                    adjudicator,
+                }
+            }
+        };
+
+        let name = name.unwrap_or_else(|| match &player {
+            Player::Uci(engine) => engine.name.clone(),
+            Player::Native {
+                difficulty: Some(difficulty),
+                ..
+            } => format!("level{}", difficulty.get_level()),
+            Player::Native { .. } => format!("depth{}", depth),
+        });
+        Ok(Self {
+            name,
+            depth,
+            movetime,
+            player,
+        })
+    }
+
+    #[inline]
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // Get ready to play a new game.
+    pub fn new_game(&mut self) -> Result<(), String> {
+        match &mut self.player {
+            Player::Native {
+                search,
+                adjudicator,
+                ..
+            } => {
+                search.clear();
+                adjudicator.reset();
+                Ok(())
+            }
+            Player::Uci(engine) => {
+                engine.send("ucinewgame")?;
+                engine.send("isready")?;
+                engine.wait_for("readyok", Some(STARTUP_TIMEOUT))?;
+                Ok(())
+            }
+        }
+    }
+
+    // Does the engine accept the draw its opponent offers? External engines
+    // never do, since UCI has no way to ask them.
+    pub fn accepts_draw(&self) -> bool {
+        match &self.player {
+            Player::Native { adjudicator, .. } => adjudicator.accepts_draw(),
+            Player::Uci(_) => false,
+        }
+    }
+
+    // Pick a move on a board, reached by playing the moves of the history
+    // from the start of the game, each with the board it was played on.
+    // The move is made within the time left on the clock if there is one.
+    // The engine resigns by playing `Move::Resign`, and can offer a draw
+    // along with its move.
+    pub fn get_move(
+        &mut self,
+        board: &Board,
+        start: &Board,
+        history: &[(Board, Move)],
+        clock: Option<&Clock>,
+    ) -> Result<(Move, Decision), String> {
+        match &mut self.player {
+            Player::Native {
+                params,
+                difficulty,
+                search,
+                book,
+                tablebase,
+                adjudicator,
+            } => {
+                if let Some(m) = book.as_ref().and_then(|book| book.get_move(board, true)) {
+                    return Ok((m, Decision::Play));
+                }
+
+                // The search reads the tablebase from a global, so every move
+                // sets the one of the engine playing it.
+                tablebase::set_tablebase(tablebase.clone());
+                let (m, value) = match difficulty {
+                    Some(difficulty) => difficulty.get_move_and_value(board, search, params),
+                    None => {
+                        let budget = match clock {
+                            Some(clock) => Some(get_budget(board, clock)),
+                            None => self.movetime,
+                        };
+                        let limits = match budget {
+                            Some(budget) => SearchLimits::new().with_time(budget),
+                            None => SearchLimits::new().with_depth(self.depth),
+                        };
+                        let result = search.run_with_limits(board, &limits, params);
+                        (result.get_best_move(), result.get_value())
+                    }
+                };
+                match adjudicator.update(value, params) {
+                    Decision::Resign => Ok((Move::Resign, Decision::Resign)),
+                    decision => Ok((m, decision)),
+                }
+            }
+            Player::Uci(engine) => {
+                let mut position = format!("position fen {}", start.to_fen());
+                if !history.is_empty() {
+                    position += " moves";
+                    for (board, m) in history {
+                        position += " ";
+                        position += &board.get_uci(*m);
+                    }
+                }
+                engine.send(&position)?;
+
+                let (go, timeout) = match (clock, self.movetime) {
+                    (Some(clock), _) => (
+                        format!(
+                            "go wtime {} btime {} winc {} binc {}",
+                            clock.white.as_millis(),
+                            clock.black.as_millis(),
+                            clock.increment.as_millis(),
+                            clock.increment.as_millis()
+                        ),
+                        Some(clock.get_remaining(board) + GRACE_PERIOD),
+                    ),
+                    (None, Some(movetime)) => (
+                        format!("go movetime {}", movetime.as_millis()),
+                        Some(movetime + GRACE_PERIOD),
+                    ),
+                    (None, None) => (format!("go depth {}", self.depth), None),
+                };
+                engine.send(&go)?;
+
+                let line = engine.wait_for("bestmove", timeout)?;
+                let uci = line.split_whitespace().nth(1).unwrap_or("");
+                // Engines that resign send the null move.
+                if uci == "0000" {
+                    return Ok((Move::Resign, Decision::Resign));
+                }
+                board
+                    .parse_uci(uci)
+                    .map(|m| (m, Decision::Play))
+                    .map_err(|e| format!("{} played an {}", self.name, e))
+            }
+        }
+    }
+}
+
+// Get the time the built-in engine spends on a move: a small share of the
+// time left, plus the increment.
+fn get_budget(board: &Board, clock: &Clock) -> Duration {
+    let remaining = clock.get_remaining(board);
+    (remaining / 30 + clock.increment).min(remaining / 2)
+}
+
+// An external engine spoken to over the UCI protocol through its standard
+// input and output.
+struct UciEngine {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl UciEngine {
+    fn start(command: &str, options: &[(String, String)]) -> Result<Self, String> {
+        let mut child = Command::new(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("could not start `{}`: {}", command, e))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        // Read the output on another thread, so that an engine which stops
+        // answering can be given up on.
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let name = Path::new(command)
+            .file_name()
+            .map_or(command.to_string(), |name| {
+                name.to_string_lossy().to_string()
+            });
+        let mut engine = Self {
+            name,
+            child,
+            stdin,
+            lines,
+        };
+
+        engine.send("uci")?;
+        loop {
+            let line = engine.wait_for("", Some(STARTUP_TIMEOUT))?;
+            if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if line.trim() == "uciok" {
+                break;
+            }
+        }
+        for (name, value) in options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.send("isready")?;
+        engine.wait_for("readyok", Some(STARTUP_TIMEOUT))?;
+        Ok(engine)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|e| format!("could not write to {}: {}", self.name, e))
+    }
+
+    // Wait for the next line starting with a command, skipping the others,
+    // like the `info` lines sent while searching. An empty command matches
+    // any line.
+    fn wait_for(&self, command: &str, timeout: Option<Duration>) -> Result<String, String> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let line = match deadline {
+                Some(deadline) => self
+                    .lines
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .map_err(|e| match e {
+                        RecvTimeoutError::Timeout => format!("{} stopped answering", self.name),
+                        RecvTimeoutError::Disconnected => format!("{} exited", self.name),
+                    })?,
+                None => self
+                    .lines
+                    .recv()
+                    .map_err(|_| format!("{} exited", self.name))?,
+            };
+            if command.is_empty() || line.split_whitespace().next() == Some(command) {
+                return Ok(line);
+            }
+        }
+    }
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        // Give the engine a moment to exit on its own before killing it.
+        let deadline = Instant::now() + GRACE_PERIOD;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,376 @@
+// Play a match between two engines and report the Elo difference between
+// them, to check that a change makes the engine stronger before merging
+// it, or to measure it against an external UCI engine such as a strength
+// limited Stockfish.
+//
+// Every opening is played twice, once with each engine as white. Games are
+// adjudicated by `Board::play_move`, drawn by threefold repetition, by a
+// draw offer the opponent accepts or once they reach the maximum number of
+// plies, and lost by an engine that resigns, runs out of time, plays an
+// illegal move or stops answering.
+//
+// usage: selfplay <engine1> <engine2> [--openings <file>] [--games <n>]
+//                 [--tc <seconds>+<increment>] [--max-plies <n>]
+//                 [--pgn <file>] [--sprt <elo0>,<elo1>] [--alpha <a>]
+//                 [--beta <b>]
+//
+// See `Engine::parse` for the engine options.
+
+mod engine;
+mod pgn;
+mod stats;
+
+use chess::{
+    adjudication::Decision,
+    board::Board,
+    epd,
+    game::{GameResult, Move},
+    piece::{Color, BLACK, WHITE},
+};
+use engine::{Clock, Engine};
+use stats::{Score, Sprt, Verdict};
+use std::{
+    collections::HashMap,
+    env,
+    fs::{File, OpenOptions},
+    io::Write,
+    process,
+    time::Instant,
+};
+
+const USAGE: &str = "usage: selfplay <engine1> <engine2> [--openings <file>] [--games <n>] \
+                     [--tc <seconds>+<increment>] [--max-plies <n>] [--pgn <file>] \
+                     [--sprt <elo0>,<elo1>] [--alpha <a>] [--beta <b>]";
+
+struct Options {
+    engines: Vec<String>,
+    openings: Option<String>,
+    games: u32,
+    time_control: Option<String>,
+    max_plies: usize,
+    pgn: Option<String>,
+    sprt: Option<(f64, f64)>,
+    alpha: f64,
+    beta: f64,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        engines: vec![],
+        openings: None,
+        games: 100,
+        time_control: None,
+        max_plies: 300,
+        pgn: None,
+        sprt: None,
+        alpha: 0.05,
+        beta: 0.05,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for `{}`", name));
+        match arg.as_str() {
+            "--openings" => options.openings = Some(value(&arg)?),
+            "--games" => {
+                options.games = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid number of games"))?
+            }
+            "--tc" => options.time_control = Some(value(&arg)?),
+            "--max-plies" => {
+                options.max_plies = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid number of plies"))?
+            }
+            "--pgn" => options.pgn = Some(value(&arg)?),
+            "--sprt" => {
+                let bounds = value(&arg)?;
+                let (elo0, elo1) = bounds
+                    .split_once(',')
+                    .and_then(|(elo0, elo1)| Some((elo0.parse().ok()?, elo1.parse().ok()?)))
+                    .ok_or(format!(
+                        "invalid sprt bounds `{}`, expected `elo0,elo1`",
+                        bounds
+                    ))?;
+                options.sprt = Some((elo0, elo1))
+            }
+            "--alpha" => {
+                options.alpha = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid alpha"))?
+            }
+            "--beta" => {
+                options.beta = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid beta"))?
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ => options.engines.push(arg),
+        }
+    }
+
+    if options.engines.len() != 2 {
+        return Err(String::from(USAGE));
+    }
+    Ok(options)
+}
+
+// How a game ended, with the winner if there is one.
+struct Outcome {
+    winner: Option<Color>,
+    reason: &'static str,
+}
+
+impl Outcome {
+    fn draw(reason: &'static str) -> Self {
+        Self {
+            winner: None,
+            reason,
+        }
+    }
+
+    fn win(winner: Color, reason: &'static str) -> Self {
+        Self {
+            winner: Some(winner),
+            reason,
+        }
+    }
+
+    fn get_result(&self) -> &'static str {
+        match self.winner {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
+// Play a game from a starting position, and get how it ended along with
+// the moves played, each with the board it was played on.
+fn play_game(
+    start: Board,
+    white: &mut Engine,
+    black: &mut Engine,
+    mut clock: Option<Clock>,
+    max_plies: usize,
+) -> (Outcome, Vec<(Board, Move)>) {
+    let mut board = start;
+    let mut history = vec![];
+    let mut seen = HashMap::new();
+
+    for (engine, color) in [(&mut *white, WHITE), (&mut *black, BLACK)] {
+        if let Err(e) = engine.new_game() {
+            eprintln!("warning: {}", e);
+            return (Outcome::win(!color, "engine failure"), history);
+        }
+    }
+
+    loop {
+        let count = seen.entry(board.get_zobrist_key()).or_insert(0);
+        *count += 1;
+        if *count >= 3 {
+            return (Outcome::draw("threefold repetition"), history);
+        }
+        if history.len() >= max_plies {
+            return (Outcome::draw("move limit"), history);
+        }
+
+        let color = board.get_turn_color();
+        let engine = if color == WHITE {
+            &mut *white
+        } else {
+            &mut *black
+        };
+        let started = Instant::now();
+        let m = engine.get_move(&board, &start, &history, clock.as_ref());
+        let elapsed = started.elapsed();
+
+        if let Some(clock) = &mut clock {
+            let remaining = if color == WHITE {
+                &mut clock.white
+            } else {
+                &mut clock.black
+            };
+            if elapsed > *remaining {
+                return (Outcome::win(!color, "time forfeit"), history);
+            }
+            *remaining = *remaining - elapsed + clock.increment;
+        }
+        let (m, decision) = match m {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("warning: {}", e);
+                return (Outcome::win(!color, "engine failure"), history);
+            }
+        };
+
+        // Resigning and illegal moves are left out of the history, since
+        // they can't be written down as moves of the game.
+        let result = board.play_move(m);
+        if matches!(result, GameResult::Continuing(_) | GameResult::Stalemate)
+            || matches!(result, GameResult::Victory(_, _)) && m != Move::Resign
+        {
+            history.push((board, m));
+        }
+        board = match result {
+            GameResult::Continuing(next_board) => {
+                let opponent = if color == WHITE {
+                    &mut *black
+                } else {
+                    &mut *white
+                };
+                if decision == Decision::OfferDraw && opponent.accepts_draw() {
+                    return (Outcome::draw("draw agreement"), history);
+                }
+                next_board
+            }
+            GameResult::Victory(_, winner) => {
+                let reason = if m == Move::Resign {
+                    "resignation"
+                } else {
+                    "checkmate"
+                };
+                return (Outcome::win(winner, reason), history);
+            }
+            GameResult::Stalemate => return (Outcome::draw("stalemate"), history),
+            GameResult::IllegalMove(_) => {
+                eprintln!(
+                    "warning: {} played the illegal move {}",
+                    engine.get_name(),
+                    m
+                );
+                return (Outcome::win(!color, "illegal move"), history);
+            }
+        };
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let mut engines = [
+        Engine::parse(&options.engines[0])?,
+        Engine::parse(&options.engines[1])?,
+    ];
+    let openings = match &options.openings {
+        Some(path) => epd::read_file(path)?
+            .iter()
+            .map(|epd| epd.get_board())
+            .collect(),
+        None => vec![Board::new()],
+    };
+    if openings.is_empty() {
+        return Err(String::from("no openings to play"));
+    }
+    let clock = options
+        .time_control
+        .as_deref()
+        .map(Clock::parse)
+        .transpose()?;
+    let mut pgn = match &options.pgn {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("could not open `{}`: {}", path, e))?,
+        ),
+        None => None::<File>,
+    };
+    let sprt = options
+        .sprt
+        .map(|(elo0, elo1)| Sprt::new(elo0, elo1, options.alpha, options.beta));
+
+    let mut score = Score::default();
+    for game in 0..options.games {
+        // Play every opening twice in a row, with the colors swapped.
+        let opening = openings[(game as usize / 2) % openings.len()];
+        let first_is_white = game % 2 == 0;
+        let [first, second] = &mut engines;
+        let (white, black) = if first_is_white {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let (outcome, history) = play_game(opening, white, black, clock, options.max_plies);
+        let first_color = if first_is_white {
+            Color::White
+        } else {
+            Color::Black
+        };
+        match outcome.winner {
+            Some(winner) if winner == first_color => score.wins += 1,
+            Some(_) => score.losses += 1,
+            None => score.draws += 1,
+        }
+
+        if let Some(out) = &mut pgn {
+            let game = pgn::Game {
+                round: game + 1,
+                white: white.get_name(),
+                black: black.get_name(),
+                start: opening,
+                history: &history,
+                result: outcome.get_result(),
+                reason: outcome.reason,
+                time_control: options.time_control.as_deref(),
+            };
+            game.write(out)
+                .and_then(|_| out.flush())
+                .map_err(|e| format!("could not write the game: {}", e))?;
+        }
+
+        println!(
+            "Game {} ({} vs {}): {} {{{}}} in {} plies",
+            game + 1,
+            white.get_name(),
+            black.get_name(),
+            outcome.get_result(),
+            outcome.reason,
+            history.len()
+        );
+        println!(
+            "Score of {} vs {}: {} - {} - {} [{:.3}] {}",
+            engines[0].get_name(),
+            engines[1].get_name(),
+            score.wins,
+            score.losses,
+            score.draws,
+            score.get_ratio(),
+            score.get_games()
+        );
+
+        if let Some(sprt) = sprt {
+            if sprt.get_verdict(&score) != Verdict::Continue {
+                break;
+            }
+        }
+    }
+
+    let (elo, margin) = score.get_elo();
+    println!();
+    println!(
+        "Elo difference: {:.1} +/- {:.1}, LOS: {:.1} %",
+        elo,
+        margin,
+        score.get_los() * 100.0
+    );
+    if let Some(sprt) = sprt {
+        let (lower, upper) = sprt.get_bounds();
+        println!(
+            "SPRT: llr {:.2} ({:.2}, {:.2}), {}",
+            sprt.get_llr(&score),
+            lower,
+            upper,
+            sprt.get_verdict(&score)
+        );
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = parse_options().and_then(run) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
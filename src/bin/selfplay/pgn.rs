@@ -0,0 +1,101 @@
+use chess::{board::Board, game::Move, piece::WHITE};
+use std::{
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// The longest line of moves written, as recommended by the PGN standard.
+const MAX_LINE_LENGTH: usize = 79;
+
+// A finished game, ready to be written in Portable Game Notation.
+pub struct Game<'a> {
+    pub round: u32,
+    pub white: &'a str,
+    pub black: &'a str,
+    pub start: Board,
+    pub history: &'a [(Board, Move)],
+    pub result: &'a str,
+    // Why the game ended, like `checkmate` or `time forfeit`.
+    pub reason: &'a str,
+    pub time_control: Option<&'a str>,
+}
+
+impl Game<'_> {
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "[Event \"Engine match\"]")?;
+        writeln!(out, "[Site \"?\"]")?;
+        writeln!(out, "[Date \"{}\"]", get_date())?;
+        writeln!(out, "[Round \"{}\"]", self.round)?;
+        writeln!(out, "[White \"{}\"]", escape(self.white))?;
+        writeln!(out, "[Black \"{}\"]", escape(self.black))?;
+        writeln!(out, "[Result \"{}\"]", self.result)?;
+        if self.start != Board::new() {
+            writeln!(out, "[SetUp \"1\"]")?;
+            writeln!(out, "[FEN \"{}\"]", self.start.to_fen())?;
+        }
+        if let Some(time_control) = self.time_control {
+            writeln!(out, "[TimeControl \"{}\"]", time_control)?;
+        }
+        writeln!(out, "[PlyCount \"{}\"]", self.history.len())?;
+        writeln!(out)?;
+
+        let mut tokens = vec![];
+        for (i, (board, m)) in self.history.iter().enumerate() {
+            // Count the moves from the first one in the game, which is black's
+            // when the game starts from a position with black to move.
+            let ply = i + (self.start.get_turn_color() != WHITE) as usize;
+            if board.get_turn_color() == WHITE {
+                tokens.push(format!("{}.", ply / 2 + 1));
+            } else if i == 0 {
+                tokens.push(format!("{}...", ply / 2 + 1));
+            }
+            tokens.push(board.get_san(*m));
+        }
+        tokens.push(format!("{{{}}}", self.reason));
+        tokens.push(self.result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                writeln!(out, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        writeln!(out, "{}", line)?;
+        writeln!(out)
+    }
+}
+
+fn escape(tag: &str) -> String {
+    tag.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// Get today's date in UTC, formatted like `2024.01.31`.
+fn get_date() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() / 86400) as i64;
+
+    // Convert the days since 1970 to a date of the Gregorian calendar, in
+    // eras of 400 years starting on the first of March.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
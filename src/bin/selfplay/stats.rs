@@ -0,0 +1,214 @@
+// The statistics of a match: the Elo difference with its error bars, the
+// likelihood of superiority and the sequential probability ratio test.
+
+// The results of a match, from the point of view of the first engine.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Score {
+    #[inline]
+    pub fn get_games(&self) -> u32 {
+        self.wins + self.losses + self.draws
+    }
+
+    // Get the average points per game, where a win is 1 and a draw 0.5.
+    pub fn get_ratio(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.get_games().max(1) as f64
+    }
+
+    // Get the variance of the points of a single game.
+    fn get_variance(&self) -> f64 {
+        let games = self.get_games().max(1) as f64;
+        let ratio = self.get_ratio();
+        (self.wins as f64 * (1.0 - ratio).powi(2)
+            + self.draws as f64 * (0.5 - ratio).powi(2)
+            + self.losses as f64 * ratio.powi(2))
+            / games
+    }
+
+    // Get the Elo difference and the margin of its 95% confidence interval.
+    pub fn get_elo(&self) -> (f64, f64) {
+        let games = self.get_games().max(1) as f64;
+        let ratio = self.get_ratio();
+        let margin = 1.959964 * (self.get_variance() / games).sqrt();
+
+        let elo = ratio_to_elo(ratio);
+        let low = ratio_to_elo(ratio - margin);
+        let high = ratio_to_elo(ratio + margin);
+        (elo, (high - low) / 2.0)
+    }
+
+    // Get the likelihood of superiority: how likely the first engine is to
+    // be the stronger one, draws left aside.
+    pub fn get_los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        0.5 * (1.0 + erf((self.wins as f64 - self.losses as f64) / (2.0 * decisive).sqrt()))
+    }
+}
+
+// The outcome of the sequential probability ratio test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    // The first engine is `elo1` stronger than the second.
+    AcceptH1,
+    // The first engine is no more than `elo0` stronger than the second.
+    AcceptH0,
+    // More games are needed.
+    Continue,
+}
+
+impl core::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::AcceptH1 => "H1 accepted",
+                Self::AcceptH0 => "H0 accepted",
+                Self::Continue => "inconclusive",
+            }
+        )
+    }
+}
+
+// A sequential probability ratio test between the hypotheses that the
+// first engine is `elo0` (H0) or `elo1` (H1) stronger than the second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Self {
+        Self {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        }
+    }
+
+    // Get the lower and upper bounds of the log-likelihood ratio.
+    pub fn get_bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    // Get the log-likelihood ratio of the score, using the normal
+    // approximation of the generalized SPRT.
+    pub fn get_llr(&self, score: &Score) -> f64 {
+        let variance = score.get_variance();
+        if score.wins == 0 || score.losses == 0 || variance == 0.0 {
+            return 0.0;
+        }
+
+        let ratio0 = elo_to_ratio(self.elo0);
+        let ratio1 = elo_to_ratio(self.elo1);
+        let ratio = score.get_ratio();
+        (ratio1 - ratio0) * (2.0 * ratio - ratio0 - ratio1) * score.get_games() as f64
+            / (2.0 * variance)
+    }
+
+    pub fn get_verdict(&self, score: &Score) -> Verdict {
+        let llr = self.get_llr(score);
+        let (lower, upper) = self.get_bounds();
+        if llr >= upper {
+            Verdict::AcceptH1
+        } else if llr <= lower {
+            Verdict::AcceptH0
+        } else {
+            Verdict::Continue
+        }
+    }
+}
+
+fn ratio_to_elo(ratio: f64) -> f64 {
+    let ratio = ratio.clamp(1e-6, 1.0 - 1e-6);
+    -400.0 * (1.0 / ratio - 1.0).log10()
+}
+
+fn elo_to_ratio(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+// The error function, from Abramowitz and Stegun 7.1.26.
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let y = 1.0
+        - (((((1.061405429 * t - 1.453152027) * t) + 1.421413741) * t - 0.284496736) * t
+            + 0.254829592)
+            * t
+            * (-x * x).exp();
+    if x >= 0.0 {
+        y
+    } else {
+        -y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: u32, losses: u32, draws: u32) -> Score {
+        Score {
+            wins,
+            losses,
+            draws,
+        }
+    }
+
+    #[test]
+    fn elo_and_los_on_known_scores() {
+        let (elo, margin) = score(60, 40, 0).get_elo();
+        assert!((elo - 70.44).abs() < 0.01, "elo {}", elo);
+        assert!((margin - 70.57).abs() < 0.01, "margin {}", margin);
+        assert!((score(60, 40, 0).get_los() - 0.9772).abs() < 1e-4);
+
+        let (elo, margin) = score(1200, 800, 2000).get_elo();
+        assert!((elo - 34.86).abs() < 0.01, "elo {}", elo);
+        assert!((margin - 7.61).abs() < 0.01, "margin {}", margin);
+
+        let even = score(30, 30, 40);
+        assert!(even.get_elo().0.abs() < 1e-9);
+        assert!((even.get_los() - 0.5).abs() < 1e-6);
+        assert_eq!(score(0, 0, 0).get_los(), 0.5);
+    }
+
+    #[test]
+    fn sprt_on_known_scores() {
+        let sprt = Sprt::new(0.0, 5.0, 0.05, 0.05);
+        let (lower, upper) = sprt.get_bounds();
+        assert!((lower + 2.944).abs() < 1e-3 && (upper - 2.944).abs() < 1e-3);
+
+        let llr = sprt.get_llr(&score(1200, 800, 2000));
+        assert!((llr - 10.90).abs() < 0.01, "llr {}", llr);
+        assert_eq!(sprt.get_verdict(&score(1200, 800, 2000)), Verdict::AcceptH1);
+
+        let llr = sprt.get_llr(&score(1000, 1000, 2000));
+        assert!((llr + 0.828).abs() < 0.001, "llr {}", llr);
+        assert_eq!(
+            sprt.get_verdict(&score(1000, 1000, 2000)),
+            Verdict::Continue
+        );
+
+        let llr = sprt.get_llr(&score(900, 1100, 2000));
+        assert!((llr + 6.617).abs() < 0.001, "llr {}", llr);
+        assert_eq!(sprt.get_verdict(&score(900, 1100, 2000)), Verdict::AcceptH0);
+
+        // Without both a win and a loss there is nothing to decide on yet.
+        assert_eq!(sprt.get_llr(&score(10, 0, 5)), 0.0);
+    }
+}
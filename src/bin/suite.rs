@@ -0,0 +1,277 @@
+// Run the engine on an EPD test suite, such as WAC, ECM or STS, and report
+// how many positions it solves.
+//
+// A position is solved when the engine plays one of its `bm` (best move)
+// moves and none of its `am` (avoid move) moves. Suites that score moves
+// with a `c0` operation like `c0 "Nf5=10, Qd2=5";`, like STS, are scored
+// with those points, otherwise every solved position is worth one point.
+//
+// usage: suite <positions.epd> [--depth <n>] [--time <ms>] [--threads <n>]
+//              [--hash <mb>] [--params <file>] [--disable <features>]
+//
+// `--disable` turns off selective parts of the search, given as a list like
+// `lmr,futility`, to measure what they bring.
+
+use chess::{
+    board::Board,
+    epd::{self, Epd},
+    game::Move,
+    params::EvalParams,
+    search::{Search, SearchFeatures, SearchLimits},
+};
+use std::{
+    env, process,
+    time::{Duration, Instant},
+};
+
+struct Options {
+    path: String,
+    depth: i32,
+    time: Option<Duration>,
+    threads: usize,
+    hash: usize,
+    params: Option<String>,
+    features: SearchFeatures,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut options = Options {
+        path: String::new(),
+        depth: 2,
+        time: None,
+        threads: 1,
+        hash: Search::DEFAULT_HASH,
+        params: None,
+        features: SearchFeatures::default(),
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for `{}`", name));
+        match arg.as_str() {
+            "--depth" => {
+                options.depth = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid depth"))?
+            }
+            "--time" => {
+                let ms = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid time"))?;
+                options.time = Some(Duration::from_millis(ms))
+            }
+            "--threads" => {
+                options.threads = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid number of threads"))?
+            }
+            "--hash" => {
+                options.hash = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid hash size"))?
+            }
+            "--params" => options.params = Some(value(&arg)?),
+            "--disable" => options.features.disable(&value(&arg)?)?,
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ if options.path.is_empty() => options.path = arg,
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    if options.path.is_empty() {
+        return Err(String::from(
+            "usage: suite <positions.epd> [--depth <n>] [--time <ms>] [--threads <n>] \
+             [--hash <mb>] [--params <file>] [--disable <features>]",
+        ));
+    }
+    Ok(options)
+}
+
+// Search a position to a fixed depth, or deeper and deeper until the time
+// runs out.
+fn search(
+    search: &Search,
+    board: &Board,
+    options: &Options,
+    params: &EvalParams,
+) -> (Move, i32, u64) {
+    let limits = match options.time {
+        Some(time) => SearchLimits::new().with_time(time),
+        None => SearchLimits::new().with_depth(options.depth),
+    };
+    let result = search.run_with_limits(board, &limits, params);
+    (
+        result.get_best_move(),
+        result.get_depth(),
+        result.get_nodes(),
+    )
+}
+
+fn parse_moves(board: &Board, epd: &Epd, opcode: &str) -> Result<Vec<Move>, String> {
+    epd.get_operation(opcode)
+        .unwrap_or(&[])
+        .iter()
+        .map(|san| board.parse_san(san))
+        .collect()
+}
+
+// Is a position solved by a move: one of its best moves, if it has any,
+// and none of the moves to avoid?
+fn is_solved(board: &Board, m: Move, best_moves: &[Move], avoid_moves: &[Move]) -> bool {
+    let is_any = |moves: &[Move]| moves.iter().any(|other| board.is_same_move(*other, m));
+    (best_moves.is_empty() || is_any(best_moves))
+        && !is_any(avoid_moves)
+        && !(best_moves.is_empty() && avoid_moves.is_empty())
+}
+
+// Get the points of every scored move from a `c0` operation like
+// `"Nf5=10, Qd2=5"`, if the position has one. Promotions like `e8=Q=10`
+// have their points after the last `=`.
+fn parse_points(board: &Board, epd: &Epd) -> Option<Vec<(Move, u32)>> {
+    let comment = epd.get_operation("c0")?.join(" ");
+    comment
+        .split(',')
+        .map(|part| {
+            let (san, points) = part.trim().rsplit_once('=')?;
+            Some((board.parse_san(san).ok()?, points.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let params = match &options.params {
+        Some(path) => EvalParams::open(path)?,
+        None => EvalParams::default(),
+    };
+    let positions = epd::read_file(&options.path)?;
+    let mut searcher = Search::new(options.threads, options.hash);
+    searcher.set_features(options.features);
+
+    let mut solved = 0;
+    let mut score = 0;
+    let mut max_score = 0;
+    let mut total_nodes = 0;
+    let mut total_time = Duration::ZERO;
+
+    for (i, epd) in positions.iter().enumerate() {
+        let board = epd.get_board();
+        let id = epd
+            .get_id()
+            .map(String::from)
+            .unwrap_or(format!("#{}", i + 1));
+        let best_moves = parse_moves(&board, epd, "bm").map_err(|e| format!("{}: {}", id, e))?;
+        let avoid_moves = parse_moves(&board, epd, "am").map_err(|e| format!("{}: {}", id, e))?;
+
+        let start = Instant::now();
+        // Every position is searched from scratch.
+        searcher.clear();
+        let (m, depth, nodes) = search(&searcher, &board, &options, &params);
+        let elapsed = start.elapsed();
+        total_nodes += nodes;
+        total_time += elapsed;
+
+        let is_solved = is_solved(&board, m, &best_moves, &avoid_moves);
+        if is_solved {
+            solved += 1;
+        }
+        match parse_points(&board, epd) {
+            Some(points) => {
+                score += points
+                    .iter()
+                    .find(|(scored, _)| board.is_same_move(*scored, m))
+                    .map_or(0, |(_, points)| *points);
+                max_score += points.iter().map(|(_, points)| *points).max().unwrap_or(0);
+            }
+            None => {
+                score += is_solved as u32;
+                max_score += 1;
+            }
+        }
+
+        let expected = ["bm", "am"]
+            .iter()
+            .filter_map(|opcode| {
+                epd.get_operation(opcode)
+                    .map(|moves| format!("{} {}", opcode, moves.join(" ")))
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+        println!(
+            "{:<12} {:<7} {:<8} ({})  depth {}  {} nodes  {:.2}s",
+            id,
+            if is_solved { "solved" } else { "failed" },
+            board.get_san(m),
+            expected,
+            depth,
+            nodes,
+            elapsed.as_secs_f64()
+        );
+    }
+
+    println!();
+    println!("Solved {}/{}", solved, positions.len());
+    println!("Score {}/{}", score, max_score);
+    println!(
+        "{} nodes in {:.2}s, {:.0} nodes per second",
+        total_nodes,
+        total_time.as_secs_f64(),
+        total_nodes as f64 / total_time.as_secs_f64().max(1e-9)
+    );
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = parse_options().and_then(run) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess::piece::Position;
+
+    const PROMOTION: &str = "8/4P3/8/8/8/8/k7/6K1 w - - bm e8=Q; am e8=R; c0 \"e8=Q=10, e8=N=2\";";
+
+    #[test]
+    fn promotion_solves_best_move() {
+        let epd = Epd::parse(PROMOTION).unwrap();
+        let board = epd.get_board();
+        let best_moves = parse_moves(&board, &epd, "bm").unwrap();
+        let avoid_moves = parse_moves(&board, &epd, "am").unwrap();
+
+        // However the engine writes the promotion to a queen.
+        let pushed = Move::Piece(Position::new(6, 4), Position::new(7, 4));
+        assert!(is_solved(&board, pushed, &best_moves, &avoid_moves));
+        assert!(is_solved(&board, best_moves[0], &best_moves, &avoid_moves));
+        assert!(!is_solved(
+            &board,
+            avoid_moves[0],
+            &best_moves,
+            &avoid_moves
+        ));
+
+        let result = Search::new(1, 1).run_with_limits(
+            &board,
+            &SearchLimits::new().with_depth(3),
+            &EvalParams::default(),
+        );
+        assert!(is_solved(
+            &board,
+            result.get_best_move(),
+            &best_moves,
+            &avoid_moves
+        ));
+    }
+
+    #[test]
+    fn promotion_points() {
+        let epd = Epd::parse(PROMOTION).unwrap();
+        let board = epd.get_board();
+        let points = parse_points(&board, &epd).unwrap();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0], (board.parse_san("e8=Q").unwrap(), 10));
+        assert_eq!(points[1], (board.parse_san("e8=N").unwrap(), 2));
+    }
+}
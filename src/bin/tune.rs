@@ -0,0 +1,232 @@
+// Tune the evaluation parameters with the Texel method.
+//
+// The input is an EPD or FEN file of quiet positions, each labelled with the
+// result of the game it was taken from, either as an operation like
+// `c9 "1-0";` or anywhere after the position as `1-0`, `0-1`, `1/2-1/2`,
+// `[1.0]`, `[0.5]` or `[0.0]`. The tuner looks for the parameters whose
+// evaluations, mapped through a sigmoid, best predict those results.
+//
+// usage: tune <positions> <output.toml|json> [--params <file>]
+//             [--iterations <n>] [--step <size>] [--limit <n>]
+
+use chess::{board::Board, epd::Epd, game::Evaluate, params::EvalParams, piece::WHITE};
+use std::{env, process, thread};
+
+struct Options {
+    positions: String,
+    output: String,
+    params: Option<String>,
+    iterations: usize,
+    step: f64,
+    limit: Option<usize>,
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut args = env::args().skip(1);
+    let mut paths = vec![];
+    let mut options = Options {
+        positions: String::new(),
+        output: String::new(),
+        params: None,
+        iterations: 100,
+        step: 0.5,
+        limit: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("missing value for `{}`", name));
+        match arg.as_str() {
+            "--params" => options.params = Some(value(&arg)?),
+            "--iterations" => {
+                options.iterations = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid number of iterations"))?
+            }
+            "--step" => {
+                options.step = value(&arg)?
+                    .parse()
+                    .map_err(|_| String::from("invalid step size"))?
+            }
+            "--limit" => {
+                options.limit = Some(
+                    value(&arg)?
+                        .parse()
+                        .map_err(|_| String::from("invalid position limit"))?,
+                )
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option `{}`", arg)),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() != 2 {
+        return Err(String::from(
+            "usage: tune <positions> <output.toml|json> [--params <file>] \
+             [--iterations <n>] [--step <size>] [--limit <n>]",
+        ));
+    }
+    options.output = paths.pop().unwrap();
+    options.positions = paths.pop().unwrap();
+    Ok(options)
+}
+
+// Get the result of the game a position comes from, from white's point of
+// view: 1.0 for a win, 0.5 for a draw and 0.0 for a loss.
+fn parse_result(line: &str, epd: &Epd) -> Option<f64> {
+    let labels = [
+        ("1/2-1/2", 0.5),
+        ("1-0", 1.0),
+        ("0-1", 0.0),
+        ("[0.5]", 0.5),
+        ("[1.0]", 1.0),
+        ("[0.0]", 0.0),
+    ];
+
+    if let Some(result) = epd.get_operation("c9").and_then(|operands| operands.first()) {
+        return labels
+            .iter()
+            .find(|(label, _)| label == result)
+            .map(|(_, value)| *value);
+    }
+
+    // Skip the position itself, `1-0` can't appear in it but the rest of
+    // the line is free form.
+    let rest = line.split_whitespace().skip(4).collect::<Vec<&str>>().join(" ");
+    labels
+        .iter()
+        .find(|(label, _)| rest.contains(label))
+        .map(|(_, value)| *value)
+}
+
+fn read_positions(path: &str, limit: Option<usize>) -> Result<Vec<(Board, f64)>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|e| format!("could not read `{}`: {}", path, e))?;
+
+    let mut result = vec![];
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let epd = Epd::parse(line).map_err(|e| format!("{}:{}: {}", path, i + 1, e))?;
+        let value = parse_result(line, &epd)
+            .ok_or(format!("{}:{}: missing game result", path, i + 1))?;
+        result.push((epd.get_board(), value));
+
+        if Some(result.len()) == limit {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+#[inline]
+fn sigmoid(k: f64, value: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * value / 400.0))
+}
+
+// Get the mean squared error between the game results and the predictions
+// made from the evaluations, spread over every available core.
+fn get_error(positions: &[(Board, f64)], params: &EvalParams, k: f64) -> f64 {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = positions.len().div_ceil(threads).max(1);
+
+    let total: f64 = thread::scope(|scope| {
+        positions
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(board, result)| {
+                            let prediction = sigmoid(k, board.value_for(WHITE, params));
+                            (result - prediction).powi(2)
+                        })
+                        .sum::<f64>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    });
+    total / positions.len() as f64
+}
+
+// Find the scaling constant of the sigmoid that best fits the current
+// evaluation, narrowing the search around the best value on every pass.
+fn find_k(positions: &[(Board, f64)], params: &EvalParams) -> f64 {
+    let (mut start, mut end, mut step) = (0.0, 30.0, 1.0);
+    let mut best = (f64::MAX, start);
+
+    for _ in 0..5 {
+        let mut k = start;
+        while k <= end {
+            let error = get_error(positions, params, k);
+            if error < best.0 {
+                best = (error, k);
+            }
+            k += step;
+        }
+        start = (best.1 - step).max(0.0);
+        end = best.1 + step;
+        step /= 10.0;
+    }
+    best.1
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let mut params = match &options.params {
+        Some(path) => EvalParams::open(path)?,
+        None => EvalParams::default(),
+    };
+    let positions = read_positions(&options.positions, options.limit)?;
+    if positions.is_empty() {
+        return Err(format!("no positions in `{}`", options.positions));
+    }
+    println!("Loaded {} positions", positions.len());
+
+    let k = find_k(&positions, &params);
+    let mut best_error = get_error(&positions, &params, k);
+    println!("K = {:.4}, error = {:.6}", k, best_error);
+
+    let mut values = params.get_values();
+    for iteration in 1..=options.iterations {
+        let mut improved = false;
+
+        for i in 0..values.len() {
+            for delta in [options.step, -options.step] {
+                let old_value = values[i];
+                values[i] += delta;
+                params.set_values(&values);
+
+                let error = get_error(&positions, &params, k);
+                if error < best_error {
+                    best_error = error;
+                    improved = true;
+                    break;
+                }
+                values[i] = old_value;
+                params.set_values(&values);
+            }
+        }
+
+        println!("Iteration {}: error = {:.6}", iteration, best_error);
+        // Save after every iteration, so a long run can be stopped at any
+        // time without losing the progress.
+        params.save(&options.output)?;
+
+        if !improved {
+            break;
+        }
+    }
+
+    println!("Wrote tuned parameters to `{}`", options.output);
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = parse_options().and_then(run) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,421 @@
+// Play through the UCI protocol on the standard input and output, so that
+// the engine can be used by chess GUIs and by match runners like `selfplay`.
+//
+// Searches run on a thread of their own while commands are read, so that
+// `stop` plays the best move found so far at once. The engine ponders when
+// told to with `go ponder`: it searches the position after the reply it
+// expects, and once the GUI sends `ponderhit` because that reply was played,
+// it keeps searching with the time it has for the move, and the work done
+// while pondering is used right away.
+//
+// With the `OwnBook` option on, positions in the Polyglot book set with
+// `BookFile` are played from the book without searching, picking the moves
+// at random in proportion to their weights.
+//
+// usage: uci [--params <file>]
+
+use chess::{
+    board::Board,
+    book::Book,
+    game::{Evaluate, Move},
+    params::EvalParams,
+    piece::{Piece, Position, WHITE},
+    search::{get_mate_plies, Search, SearchFeatures, SearchLimits, SearchResult},
+};
+use std::{
+    env,
+    io::{self, BufRead},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const MAX_HASH: usize = 4096;
+const MAX_THREADS: usize = 256;
+
+// How long to wait between two checks of whether a search that finished
+// while pondering can send its best move.
+const HOLD_INTERVAL: Duration = Duration::from_millis(1);
+
+// A search running in the background.
+struct Running {
+    limits: SearchLimits,
+    // Set while the engine ponders or searches with `go infinite`, when its
+    // best move must wait for a `stop` or a `ponderhit`.
+    hold: Arc<AtomicBool>,
+    // The time to search for after a `ponderhit`.
+    budget: Option<Duration>,
+    handle: JoinHandle<()>,
+}
+
+struct Engine {
+    board: Board,
+    params: Arc<EvalParams>,
+    search: Arc<Search>,
+    threads: usize,
+    hash: usize,
+    features: SearchFeatures,
+    book: Option<Book>,
+    own_book: bool,
+    running: Option<Running>,
+}
+
+impl Engine {
+    fn new(params: EvalParams) -> Self {
+        Self {
+            board: Board::new(),
+            params: Arc::new(params),
+            search: Arc::new(Search::default()),
+            threads: 1,
+            hash: Search::DEFAULT_HASH,
+            features: SearchFeatures::default(),
+            book: None,
+            own_book: false,
+            running: None,
+        }
+    }
+
+    // Handle a command, and get whether the engine should keep reading
+    // them.
+    fn handle(&mut self, line: &str) -> Result<bool, String> {
+        let args = line.split_whitespace().collect::<Vec<&str>>();
+        match args.first().copied().unwrap_or("") {
+            "uci" => {
+                println!(
+                    "id name {} {}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                );
+                println!("id author {}", env!("CARGO_PKG_AUTHORS"));
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    Search::DEFAULT_HASH,
+                    MAX_HASH
+                );
+                println!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                );
+                println!("option name Ponder type check default false");
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                // The selective parts of the search can be turned off for
+                // testing.
+                for name in SearchFeatures::NAMES {
+                    println!("option name {} type check default true", name);
+                }
+                println!("option name Clear Hash type button");
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop();
+                self.search.clear();
+                self.board = Board::new();
+            }
+            "setoption" => {
+                self.stop();
+                self.set_option(&args[1..])?;
+            }
+            "position" => {
+                self.stop();
+                self.board = parse_position(&args[1..])?;
+            }
+            "go" => {
+                self.stop();
+                self.go(&args[1..])?;
+            }
+            "stop" => self.stop(),
+            "ponderhit" => self.ponderhit(),
+            "quit" => {
+                self.stop();
+                return Ok(false);
+            }
+            "" => {}
+            command => return Err(format!("unknown command `{}`", command)),
+        }
+        Ok(true)
+    }
+
+    fn set_option(&mut self, args: &[&str]) -> Result<(), String> {
+        // Option names can have spaces, like `Clear Hash`.
+        let value_index = args
+            .iter()
+            .position(|arg| *arg == "value")
+            .unwrap_or(args.len());
+        let name = args[..value_index]
+            .iter()
+            .skip_while(|arg| **arg == "name")
+            .copied()
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let value = args.get(value_index + 1..).unwrap_or(&[]).join(" ");
+
+        match name.to_lowercase().as_str() {
+            "hash" => {
+                self.hash = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid hash size `{}`", value))?
+                    .clamp(1, MAX_HASH);
+                self.reset_search();
+            }
+            "threads" => {
+                self.threads = value
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid number of threads `{}`", value))?
+                    .clamp(1, MAX_THREADS);
+                self.reset_search();
+            }
+            // The GUI decides when to ponder, so the engine only needs to
+            // know that it may.
+            "ponder" => {}
+            "ownbook" => {
+                self.own_book = value
+                    .parse()
+                    .map_err(|_| format!("invalid value `{}` for `OwnBook`", value))?;
+            }
+            "bookfile" => {
+                self.book = match value.as_str() {
+                    "" | "<empty>" => None,
+                    path => Some(Book::open(path)?),
+                };
+            }
+            "clear hash" => self.search.clear(),
+            name if SearchFeatures::NAMES.contains(&name) => {
+                let enabled = value
+                    .parse()
+                    .map_err(|_| format!("invalid value `{}` for `{}`", value, name))?;
+                self.features.set(name, enabled)?;
+                self.reset_search();
+            }
+            _ => return Err(format!("unknown option `{}`", name)),
+        }
+        Ok(())
+    }
+
+    // Start over with a new search and transposition table, with the
+    // current options.
+    fn reset_search(&mut self) {
+        let mut search = Search::new(self.threads, self.hash);
+        search.set_features(self.features);
+        self.search = Arc::new(search);
+    }
+
+    fn go(&mut self, args: &[&str]) -> Result<(), String> {
+        let mut limits = SearchLimits::new();
+        let mut ponder = false;
+        let mut infinite = false;
+        let mut movetime = None;
+        let mut clock = [None, None];
+        let mut increment = [Duration::ZERO, Duration::ZERO];
+        let mut moves_to_go = None;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .ok_or(format!("missing or invalid value for `{}`", arg))
+            };
+            match *arg {
+                "ponder" => ponder = true,
+                "infinite" => infinite = true,
+                "depth" => limits = limits.with_depth(value()?.saturating_sub(1) as i32),
+                "nodes" => limits = limits.with_nodes(value()?),
+                "movetime" => movetime = Some(Duration::from_millis(value()?)),
+                "wtime" => clock[0] = Some(Duration::from_millis(value()?)),
+                "btime" => clock[1] = Some(Duration::from_millis(value()?)),
+                "winc" => increment[0] = Duration::from_millis(value()?),
+                "binc" => increment[1] = Duration::from_millis(value()?),
+                "movestogo" => moves_to_go = Some(value()?.max(1) as u32),
+                // The engine always searches every move.
+                "searchmoves" => {
+                    while args
+                        .as_slice()
+                        .first()
+                        .is_some_and(|arg| self.board.parse_uci(arg).is_ok())
+                    {
+                        args.next();
+                    }
+                }
+                _ => return Err(format!("unknown go option `{}`", arg)),
+            }
+        }
+
+        let color = (self.board.get_turn_color() != WHITE) as usize;
+        let budget = movetime.or_else(|| {
+            clock[color].map(|remaining| get_budget(remaining, increment[color], moves_to_go))
+        });
+        // A ponder search only starts counting its time after a `ponderhit`.
+        if let Some(budget) = budget.filter(|_| !ponder) {
+            limits = limits.with_time(budget);
+        }
+        // Book moves are played at once, unless the GUI waits for a `stop`
+        // or a `ponderhit`.
+        if self.own_book && !ponder && !infinite {
+            let book_move = self
+                .book
+                .as_ref()
+                .and_then(|book| book.get_move(&self.board, true));
+            if let Some(m) = book_move {
+                println!("bestmove {}", self.board.get_uci(m));
+                return Ok(());
+            }
+        }
+        let hold = Arc::new(AtomicBool::new(ponder || infinite));
+
+        let board = self.board;
+        let search = self.search.clone();
+        let params = self.params.clone();
+        let (search_limits, search_hold) = (limits.clone(), hold.clone());
+        let handle = thread::spawn(move || {
+            let pawn = params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)));
+            let start = Instant::now();
+            let result = search.run_with_progress(&board, &search_limits, &params, |result| {
+                print_info(&board, result, pawn, start.elapsed(), &search)
+            });
+
+            while search_hold.load(Ordering::Relaxed) && !search_limits.is_stopped() {
+                thread::sleep(HOLD_INTERVAL);
+            }
+            print_best_move(&board, &result);
+        });
+
+        self.running = Some(Running {
+            limits,
+            hold,
+            budget,
+            handle,
+        });
+        Ok(())
+    }
+
+    // Stop the search, and wait for it to send its best move.
+    fn stop(&mut self) {
+        if let Some(running) = self.running.take() {
+            running.limits.stop();
+            let _ = running.handle.join();
+        }
+    }
+
+    // The expected reply was played, so the ponder search becomes a regular
+    // one, with the time it would have had for the move.
+    fn ponderhit(&mut self) {
+        if let Some(running) = &self.running {
+            running.hold.store(false, Ordering::Relaxed);
+            if let Some(budget) = running.budget {
+                let limits = running.limits.clone();
+                thread::spawn(move || {
+                    thread::sleep(budget);
+                    limits.stop();
+                });
+            }
+        }
+    }
+}
+
+// Get the time to spend on a move: a share of the time left, plus the
+// increment.
+fn get_budget(remaining: Duration, increment: Duration, moves_to_go: Option<u32>) -> Duration {
+    (remaining / moves_to_go.unwrap_or(30) + increment).min(remaining / 2)
+}
+
+// Parse the arguments of a `position` command, like `startpos moves e2e4`.
+fn parse_position(args: &[&str]) -> Result<Board, String> {
+    let moves_index = args
+        .iter()
+        .position(|arg| *arg == "moves")
+        .unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"startpos") => Board::new(),
+        Some(&"fen") => Board::from_fen(&args[1..moves_index].join(" "))?,
+        _ => return Err(String::from("expected `startpos` or `fen`")),
+    };
+    for uci in args.get(moves_index + 1..).unwrap_or(&[]) {
+        let m = board.parse_uci(uci)?;
+        board = board.apply_eval_move(m);
+    }
+    Ok(board)
+}
+
+fn print_info(board: &Board, result: &SearchResult, pawn: f64, elapsed: Duration, search: &Search) {
+    let score = match get_mate_plies(result.get_value()) {
+        // Mates are counted in moves rather than plies.
+        Some(plies) if plies > 0 => format!("mate {}", (plies + 1) / 2),
+        Some(plies) => format!("mate {}", plies / 2),
+        None => format!("cp {}", (result.get_value() / pawn * 100.0).round()),
+    };
+    let ms = elapsed.as_millis().max(1);
+    println!(
+        "info depth {} score {} nodes {} nps {} time {} hashfull {} pv {}",
+        result.get_depth() + 1,
+        score,
+        result.get_nodes(),
+        result.get_nodes() as u128 * 1000 / ms,
+        ms,
+        search.get_table().get_hashfull(),
+        get_line(board, result.get_pv())
+    );
+}
+
+fn print_best_move(board: &Board, result: &SearchResult) {
+    let best_move = result.get_best_move();
+    match result.get_ponder_move() {
+        Some(ponder) => println!(
+            "bestmove {} ponder {}",
+            board.get_uci(best_move),
+            board.apply_eval_move(best_move).get_uci(ponder)
+        ),
+        None => println!("bestmove {}", board.get_uci(best_move)),
+    }
+}
+
+// Write a line of moves played one after the other in UCI notation.
+fn get_line(board: &Board, moves: &[Move]) -> String {
+    let mut board = *board;
+    let mut line = vec![];
+    for m in moves {
+        line.push(board.get_uci(*m));
+        board = board.apply_eval_move(*m);
+    }
+    line.join(" ")
+}
+
+fn parse_options() -> Result<EvalParams, String> {
+    let mut args = env::args().skip(1);
+    match (args.next().as_deref(), args.next()) {
+        (None, _) => Ok(EvalParams::default()),
+        (Some("--params"), Some(path)) => EvalParams::open(path),
+        _ => Err(String::from("usage: uci [--params <file>]")),
+    }
+}
+
+fn main() {
+    let params = match parse_options() {
+        Ok(params) => params,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut engine = Engine::new(params);
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        match engine.handle(&line) {
+            Ok(true) => {}
+            Ok(false) => return,
+            // GUIs ignore what they don't understand, so errors are only
+            // reported as information.
+            Err(e) => println!("info string error: {}", e),
+        }
+    }
+    engine.stop();
+}
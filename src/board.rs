@@ -1,5 +1,6 @@
 use crate::{piece::{Color, Position, Piece, BLACK, WHITE}, 
-            game::{ Move, GameResult, Evaluate }};
+            game::{ Move, GameResult, Evaluate }, eval, params::EvalParams, search::get_mate_plies,
+            tablebase, zobrist};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Square {
@@ -8,6 +9,7 @@ pub struct Square {
 
 pub const EMPTY_SQUARE: Square = Square { piece: None };
 
+#[allow(clippy::derivable_impls)]
 impl Default for Square {
     fn default() -> Self {
         Self {
@@ -16,6 +18,35 @@ impl Default for Square {
     }
 }
 
+// The game phase of the starting position: four minor pieces, two rooks
+// and a queen on each side.
+const MAX_PHASE: i32 = 24;
+
+const KNIGHT_OFFSETS: [(i32, i32); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+
+const KING_OFFSETS: [(i32, i32); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+
+const DIAGONAL_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const ORTHOGONAL_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
 impl From<Piece> for Square {
     fn from(piece: Piece) -> Self {
         Self { piece: Some(piece) }
@@ -87,19 +118,19 @@ impl BoardBuilder {
         self
     }
 
-    // pub fn disable_castling(mut self) -> Self {
-    //     self.board.black_castling_rights.disable_all();
-    //     self.board.white_castling_rights.disable_all();
-    //     self
-    // }
+    pub fn disable_castling(mut self) -> Self {
+        self.board.black_castling_rights.disable_all();
+        self.board.white_castling_rights.disable_all();
+        self
+    }
 
-    // pub fn enable_queenside_castle(mut self, color: Color) -> Self {
-    //     match color {
-    //         WHITE => self.board.white_castling_rights.enable_queenside(),
-    //         BLACK => self.board.black_castling_rights.enable_queenside(),
-    //     }
-    //     self
-    // }
+    pub fn enable_queenside_castle(mut self, color: Color) -> Self {
+        match color {
+            WHITE => self.board.white_castling_rights.enable_queenside(),
+            BLACK => self.board.black_castling_rights.enable_queenside(),
+        }
+        self
+    }
 
     // pub fn disable_queenside_castle(mut self, color: Color) -> Self {
     //     match color {
@@ -109,13 +140,13 @@ impl BoardBuilder {
     //     self
     // }
 
-    // pub fn enable_kingside_castle(mut self, color: Color) -> Self {
-    //     match color {
-    //         WHITE => self.board.white_castling_rights.enable_kingside(),
-    //         BLACK => self.board.black_castling_rights.enable_kingside(),
-    //     }
-    //     self
-    // }
+    pub fn enable_kingside_castle(mut self, color: Color) -> Self {
+        match color {
+            WHITE => self.board.white_castling_rights.enable_kingside(),
+            BLACK => self.board.black_castling_rights.enable_kingside(),
+        }
+        self
+    }
 
     // pub fn disable_kingside_castle(mut self, color: Color) -> Self {
     //     match color {
@@ -125,15 +156,15 @@ impl BoardBuilder {
     //     self
     // }
 
-    // pub fn set_en_passant(mut self, position: Option<Position>) -> Self {
-    //     self.board.en_passant = position;
-    //     self
-    // }
+    pub fn set_en_passant(mut self, position: Option<Position>) -> Self {
+        self.board.en_passant = position;
+        self
+    }
 
-    // pub fn set_turn(mut self, color: Color) -> Self {
-    //     self.board = self.board.set_turn(color);
-    //     self
-    // }
+    pub fn set_turn(mut self, color: Color) -> Self {
+        self.board = self.board.set_turn(color);
+        self
+    }
 
     pub fn build(self) -> Board {
         self.board
@@ -190,6 +221,88 @@ impl CastlingRights {
     }
 }
 
+// The number of pieces of each color attacking every square of a board,
+// counting the pieces that defend a square held by one of their own. The
+// squares are in the same order as `Board::squares`, from a8 to h1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttackMap {
+    white: [u8; 64],
+    black: [u8; 64],
+}
+
+impl AttackMap {
+    #[inline]
+    fn index(pos: Position) -> usize {
+        ((7 - pos.get_row()) * 8 + pos.get_col()) as usize
+    }
+
+    // Get the number of pieces of a color attacking or defending a square.
+    #[inline]
+    pub fn get_count(&self, pos: Position, color: Color) -> u8 {
+        if pos.is_off_board() {
+            return 0;
+        }
+        self.get_counts(color)[Self::index(pos)]
+    }
+
+    // Get the counts of every square for a color.
+    #[inline]
+    pub fn get_counts(&self, color: Color) -> &[u8; 64] {
+        match color {
+            WHITE => &self.white,
+            BLACK => &self.black,
+        }
+    }
+
+    // Get how much white controls a square: the number of white pieces
+    // attacking it minus the number of black ones.
+    #[inline]
+    pub fn get_control(&self, pos: Position) -> i32 {
+        self.get_count(pos, WHITE) as i32 - self.get_count(pos, BLACK) as i32
+    }
+
+    // Get the number of squares a color attacks at least once.
+    pub fn get_mobility(&self, color: Color) -> usize {
+        self.get_counts(color).iter().filter(|count| **count > 0).count()
+    }
+}
+
+// A piece standing alone between a king and an enemy rook, bishop or queen
+// lined up with it. When the piece belongs to the king's side it is pinned,
+// and when it belongs to the attacker's side, moving it off the line gives
+// a discovered check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pin {
+    piece: Position,
+    attacker: Position,
+    // The squares from the king to the attacker, leaving out the king.
+    ray: Vec<Position>,
+}
+
+impl Pin {
+    #[inline]
+    pub fn get_piece(&self) -> Position {
+        self.piece
+    }
+
+    #[inline]
+    pub fn get_attacker(&self) -> Position {
+        self.attacker
+    }
+
+    #[inline]
+    pub fn get_ray(&self) -> &[Position] {
+        &self.ray
+    }
+
+    // Does moving the piece to a square keep it on the line, so that the
+    // king stays covered?
+    #[inline]
+    pub fn allows(&self, to: Position) -> bool {
+        self.ray.contains(&to)
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Board {
     squares: [Square; 64],
@@ -202,22 +315,30 @@ pub struct Board {
     turn: Color,
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Evaluate for Board {
     #[inline]
-    fn value_for(&self, ally_color: Color) -> f64 {
+    fn value_for(&self, ally_color: Color, params: &EvalParams) -> f64 {
+        let phase = self.get_game_phase();
         self.squares
             .iter()
             .map(|square| match square.get_piece() {
                 Some(piece) => {
                     if piece.get_color() == ally_color {
-                        piece.get_weighted_value()
+                        piece.get_weighted_value(phase, params)
                     } else {
-                        -piece.get_weighted_value()
+                        -piece.get_weighted_value(phase, params)
                     }
                 }
                 None => 0.0,
             })
-            .sum()
+            .sum::<f64>()
+            + eval::get_positional_value(self, ally_color, params)
     }
 
     #[inline]
@@ -230,14 +351,56 @@ impl Evaluate for Board {
         self.apply_move(m).change_turn()
     }
 
+    fn get_tablebase_value(&self, ally_color: Color) -> Option<f64> {
+        if self.get_piece_count() > tablebase::get_tablebase_pieces() {
+            return None;
+        }
+        let value = tablebase::get_tablebase()?.probe_wdl(self)?.get_score();
+        Some(if self.turn == ally_color { value } else { -value })
+    }
+
+    fn get_tablebase_move(&self) -> Option<(Move, f64)> {
+        if self.get_piece_count() > tablebase::get_tablebase_pieces() {
+            return None;
+        }
+        let (m, wdl) = tablebase::get_tablebase()?.get_best_move(self)?;
+        Some((m, wdl.get_score()))
+    }
+
     #[inline]
     fn get_legal_moves(&self) -> Vec<Move> {
         let mut result = vec![];
         let color = self.get_current_player_color();
+        // Out of check, moving a piece other than the king can only leave
+        // the king in check when the piece is pinned and leaves the line
+        // of its pin, so only king moves, en passant and moves out of
+        // check are played out to see if they are legal.
+        let in_check = self.is_in_check(color);
+        let pins = if in_check { vec![] } else { self.get_pins(color) };
         for square in &self.squares {
             if let Some(piece) = square.get_piece() {
-                if piece.get_color() == color {
-                    result.extend(piece.get_legal_moves(self))
+                if piece.get_color() != color {
+                    continue;
+                }
+                for m in piece.get_candidate_moves(self) {
+                    let is_legal = match m {
+                        Move::Piece(from, to) if from.is_off_board() || to.is_off_board() => false,
+                        Move::Piece(from, to) | Move::Promotion(from, to, _)
+                            if !(in_check
+                                || piece.is_king()
+                                || piece.is_pawn() && self.en_passant == Some(to)) =>
+                        {
+                            piece.is_legal_move(to, self)
+                                && pins
+                                    .iter()
+                                    .find(|pin| pin.get_piece() == from)
+                                    .is_none_or(|pin| pin.allows(to))
+                        }
+                        _ => self.is_legal_move(m, color),
+                    };
+                    if is_legal {
+                        result.push(m);
+                    }
                 }
             }
         }
@@ -276,6 +439,312 @@ impl Board {
         self.squares.len()
     }
 
+    // Get the number of pieces of both colors on the board, kings included.
+    pub fn get_piece_count(&self) -> usize {
+        self.squares
+            .iter()
+            .filter(|square| square.get_piece().is_some())
+            .count()
+    }
+
+    // Parse a board from a FEN string, such as
+    // `rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1`.
+    //
+    // The halfmove clock and fullmove number are optional and ignored, so
+    // the first four fields of an EPD line are also accepted.
+    pub fn from_fen(fen: &str) -> Result<Self, String> {
+        let fields = fen.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(format!("invalid fen `{}`", fen));
+        }
+
+        let rows = fields[0].split('/').collect::<Vec<&str>>();
+        if rows.len() != 8 {
+            return Err(format!("invalid fen placement `{}`", fields[0]));
+        }
+
+        let mut builder = BoardBuilder::default();
+        for (i, pieces) in rows.iter().enumerate() {
+            let row = 7 - i as i32;
+            let mut col = 0;
+            for ch in pieces.chars() {
+                if let Some(skip) = ch.to_digit(10) {
+                    col += skip as i32;
+                    continue;
+                }
+
+                let color = if ch.is_ascii_uppercase() { WHITE } else { BLACK };
+                let pos = Position::new(row, col);
+                let piece = match ch.to_ascii_lowercase() {
+                    'k' => Piece::King(color, pos),
+                    'q' => Piece::Queen(color, pos),
+                    'r' => Piece::Rook(color, pos),
+                    'b' => Piece::Bishop(color, pos),
+                    'n' => Piece::Knight(color, pos),
+                    'p' => Piece::Pawn(color, pos),
+                    _ => return Err(format!("invalid fen piece `{}`", ch)),
+                };
+                if pos.is_off_board() {
+                    return Err(format!("invalid fen row `{}`", pieces));
+                }
+                builder = builder.piece(piece);
+                col += 1;
+            }
+            if col != 8 {
+                return Err(format!("invalid fen row `{}`", pieces));
+            }
+        }
+
+        builder = match fields[1] {
+            "w" => builder.set_turn(WHITE),
+            "b" => builder.set_turn(BLACK),
+            other => return Err(format!("invalid fen turn `{}`", other)),
+        };
+
+        builder = builder.disable_castling();
+        if fields[2] != "-" {
+            for ch in fields[2].chars() {
+                builder = match ch {
+                    'K' => builder.enable_kingside_castle(WHITE),
+                    'Q' => builder.enable_queenside_castle(WHITE),
+                    'k' => builder.enable_kingside_castle(BLACK),
+                    'q' => builder.enable_queenside_castle(BLACK),
+                    _ => return Err(format!("invalid fen castling rights `{}`", fields[2])),
+                };
+            }
+        }
+
+        if fields[3] != "-" {
+            let pos = Position::pgn(fields[3])?;
+            if pos.is_off_board() {
+                return Err(format!("invalid fen en passant square `{}`", fields[3]));
+            }
+            builder = builder.set_en_passant(Some(pos));
+        }
+
+        Ok(builder.build())
+    }
+
+    // Get the FEN string of the board. The board does not keep track of the
+    // halfmove clock or the move number, so those are always `0 1`.
+    pub fn to_fen(&self) -> String {
+        let mut result = String::new();
+        for row in (0..8).rev() {
+            let mut empty = 0;
+            for col in 0..8 {
+                match self.get_piece(Position::new(row, col)) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            result += &empty.to_string();
+                            empty = 0;
+                        }
+                        let name = piece.get_type();
+                        result += &match piece.get_color() {
+                            WHITE => name.to_string(),
+                            BLACK => name.to_lowercase(),
+                        };
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                result += &empty.to_string();
+            }
+            if row > 0 {
+                result.push('/');
+            }
+        }
+
+        result += match self.turn {
+            WHITE => " w ",
+            BLACK => " b ",
+        };
+
+        let mut castling = String::new();
+        for (color, kingside, queenside) in [(WHITE, 'K', 'Q'), (BLACK, 'k', 'q')] {
+            if self.has_kingside_castling_right(color) {
+                castling.push(kingside);
+            }
+            if self.has_queenside_castling_right(color) {
+                castling.push(queenside);
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        result += &castling;
+
+        match self.en_passant {
+            Some(pos) => result += &format!(" {} 0 1", pos),
+            None => result += " - 0 1",
+        }
+        result
+    }
+
+    // Get a move in standard algebraic notation, like `Nbd7`, `exd5`,
+    // `O-O` or `e8=Q+`. The move is assumed to be legal.
+    pub fn get_san(&self, m: Move) -> String {
+        let (from, to, promotion) = match m {
+            Move::KingSideCastle | Move::QueenSideCastle => {
+                let name = if m == Move::KingSideCastle { "O-O" } else { "O-O-O" };
+                return format!("{}{}", name, self.get_check_suffix(m));
+            }
+            Move::Piece(from, to) => (from, to, None),
+            Move::Promotion(from, to, piece) => (from, to, Some(piece)),
+            Move::Resign => return String::from("resign"),
+        };
+
+        let piece = match self.get_piece(from) {
+            Some(piece) => piece,
+            None => return format!("{}{}", from, to),
+        };
+        let is_capture = self.has_enemy_piece(to, self.turn)
+            || (piece.is_pawn() && from.get_col() != to.get_col());
+
+        let mut result = String::new();
+        if piece.is_pawn() {
+            if is_capture {
+                result += &from.to_string()[..1];
+            }
+        } else {
+            result += piece.get_type();
+
+            // Name the file, the row, or both of the moving piece if another
+            // piece of the same kind could also move to the same square.
+            let others = self
+                .get_legal_moves()
+                .into_iter()
+                .filter_map(|other| match other {
+                    Move::Piece(other_from, other_to) if other_from != from && other_to == to => {
+                        self.get_piece(other_from)
+                    }
+                    _ => None,
+                })
+                .filter(|other| other.get_type() == piece.get_type())
+                .map(|other| other.get_pos())
+                .collect::<Vec<Position>>();
+            if !others.is_empty() {
+                let square = from.to_string();
+                if others.iter().all(|other| other.get_col() != from.get_col()) {
+                    result += &square[..1];
+                } else if others.iter().all(|other| other.get_row() != from.get_row()) {
+                    result += &square[1..];
+                } else {
+                    result += &square;
+                }
+            }
+        }
+
+        if is_capture {
+            result.push('x');
+        }
+        result += &to.to_string();
+
+        if piece.is_pawn() && (to.get_row() == 0 || to.get_row() == 7) {
+            result.push('=');
+            result += promotion.map_or("Q", |promotion| promotion.get_type());
+        }
+
+        result + self.get_check_suffix(m)
+    }
+
+    fn get_check_suffix(&self, m: Move) -> &'static str {
+        let next = self.apply_move(m).change_turn();
+        if next.is_checkmate() {
+            "#"
+        } else if next.is_in_check(next.turn) {
+            "+"
+        } else {
+            ""
+        }
+    }
+
+    // Parse a move of the current player in standard algebraic notation.
+    // Captures, checks and annotations like `!?` may be left out, and moves
+    // in coordinates like `e2e4` are also accepted.
+    pub fn parse_san(&self, san: &str) -> Result<Move, String> {
+        let normalize = |san: &str| {
+            san.trim()
+                .replace('0', "O")
+                .chars()
+                .filter(|ch| !"x+#=!?".contains(*ch))
+                .collect::<String>()
+        };
+        let wanted = normalize(san);
+        if wanted.is_empty() {
+            return Err(format!("invalid move `{}`", san));
+        }
+
+        let moves = self.get_legal_moves();
+        if let Some(m) = moves.iter().find(|m| normalize(&self.get_san(**m)) == wanted) {
+            return Ok(*m);
+        }
+
+        // Try coordinates, which may end with a promotion like `e7e8q`.
+        self.parse_uci(san)
+            .map_err(|_| format!("illegal or invalid move `{}`", san))
+    }
+
+    // Are two moves the same move on this board, however they are written?
+    // A pawn moved to the last row without a promotion piece is promoted to
+    // a queen, and a castle is the king moving two squares.
+    pub fn is_same_move(&self, a: Move, b: Move) -> bool {
+        a == b || self.get_uci(a) == self.get_uci(b)
+    }
+
+    // Get a move in the coordinate notation of the UCI protocol, like
+    // `e2e4`, `e1g1` for castling or `e7e8q`. Resigning has no notation and
+    // is written as the null move `0000`.
+    pub fn get_uci(&self, m: Move) -> String {
+        let row = if self.turn == WHITE { 0 } else { 7 };
+        match m {
+            Move::KingSideCastle => format!("{}{}", Position::new(row, 4), Position::new(row, 6)),
+            Move::QueenSideCastle => format!("{}{}", Position::new(row, 4), Position::new(row, 2)),
+            Move::Piece(from, to) => {
+                // Pawns reaching the last row are promoted to queens.
+                let is_promotion = (to.get_row() == 0 || to.get_row() == 7)
+                    && self.get_piece(from).is_some_and(|piece| piece.is_pawn());
+                format!("{}{}{}", from, to, if is_promotion { "q" } else { "" })
+            }
+            Move::Promotion(from, to, piece) => {
+                format!("{}{}{}", from, to, piece.get_type().to_lowercase())
+            }
+            Move::Resign => String::from("0000"),
+        }
+    }
+
+    // Parse a legal move of the current player in the coordinate notation
+    // of the UCI protocol. A missing promotion piece promotes to a queen.
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, String> {
+        let coordinates = uci.trim().to_lowercase();
+        let invalid = || format!("invalid move `{}`", uci);
+        if !coordinates.is_ascii() || (coordinates.len() != 4 && coordinates.len() != 5) {
+            return Err(invalid());
+        }
+        let from = Position::pgn(&coordinates[..2]).map_err(|_| invalid())?;
+        let to = Position::pgn(&coordinates[2..4]).map_err(|_| invalid())?;
+        let promotion = coordinates[4..].to_uppercase();
+
+        let is_castle = self.get_piece(from).is_some_and(|piece| piece.is_king())
+            && from.get_col() == 4
+            && from.get_row() == to.get_row();
+        self.get_legal_moves()
+            .into_iter()
+            .find(|m| match m {
+                Move::Piece(f, t) => *f == from && *t == to && promotion.is_empty(),
+                Move::Promotion(f, t, piece) => {
+                    *f == from
+                        && *t == to
+                        && (promotion.is_empty() && piece.is_queen()
+                            || piece.get_type() == promotion)
+                }
+                Move::KingSideCastle => is_castle && to.get_col() == 6,
+                Move::QueenSideCastle => is_castle && to.get_col() == 2,
+                Move::Resign => false,
+            })
+            .ok_or(format!("illegal move `{}`", uci))
+    }
+
 
 }
 impl Board {
@@ -305,8 +774,26 @@ impl Board {
         }
     }
 
-    pub fn rating_bar() {
+    // Get how much of an evaluation bar is filled for white, from 0 to 1,
+    // and the score written on it from white's side, from the value a
+    // search gave the current player's position. Forced mates fill the bar
+    // and are written as the number of moves to mate, like `M3`, or `-M3`
+    // when black mates.
+    pub fn rating_bar(&self, value: f64, params: &EvalParams) -> (f64, String) {
+        let value = if self.turn == WHITE { value } else { -value };
+        if let Some(plies) = get_mate_plies(value) {
+            let moves = (plies.abs() + 1) / 2;
+            return if value > 0.0 {
+                (1.0, format!("M{}", moves))
+            } else {
+                (0.0, format!("-M{}", moves))
+            };
+        }
 
+        // The bar shows the chances of winning rather than the value, so
+        // that it moves less once the game is decided.
+        let pawns = value / params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)));
+        (1.0 / (1.0 + (-0.368 * pawns).exp()), format!("{:+.1}", pawns))
     }
 
     #[inline]
@@ -353,6 +840,26 @@ impl Board {
             .sum()
     }
 
+    // Explain the score `value_for` gives a player, broken down into the
+    // material and piece-square value of every piece and every positional
+    // term of the evaluation.
+    pub fn explain_eval(&self, color: Color, params: &EvalParams) -> eval::Explanation {
+        eval::Explanation::new(self, color, params)
+    }
+
+    // Get how far the game is from the endgame, judging by the pieces left
+    // on the board. This is 1.0 with all the minor and major pieces still on
+    // the board and drops to 0.0 as they get traded, ignoring pawns and kings.
+    pub fn get_game_phase(&self) -> f64 {
+        let phase: i32 = self
+            .squares
+            .iter()
+            .filter_map(|square| square.get_piece())
+            .map(|piece| piece.get_phase_value())
+            .sum();
+        phase.min(MAX_PHASE) as f64 / MAX_PHASE as f64
+    }
+
     
     #[inline]
     fn get_square(&mut self, pos: Position) -> &mut Square {
@@ -404,11 +911,13 @@ impl Board {
 
     // If a square at a given position has any piece, return true
     #[inline]
+    #[allow(clippy::partialeq_to_none)]
     pub fn has_piece(&self, pos: Position) -> bool {
         self.get_piece(pos) != None
     }
 
     #[inline]
+    #[allow(clippy::partialeq_to_none)]
     pub fn has_no_piece(&self, pos: Position) -> bool {
         self.get_piece(pos) == None
     }
@@ -450,6 +959,254 @@ impl Board {
         false
     }
 
+    // Get every square a piece attacks, whether it is empty or not. Sliding
+    // pieces stop at the first piece in their way, so a square held by one
+    // of their own pieces is defended by them.
+    pub fn get_attacked_squares(&self, piece: Piece) -> Vec<Position> {
+        let pos = piece.get_pos();
+        let offset =
+            |(drow, dcol): (i32, i32)| Position::new(pos.get_row() + drow, pos.get_col() + dcol);
+
+        match piece {
+            Piece::Pawn(color, _) => {
+                let up = pos.pawn_up(color);
+                vec![up.next_left(), up.next_right()]
+                    .into_iter()
+                    .filter(Position::is_on_board)
+                    .collect()
+            }
+            Piece::Knight(_, _) => KNIGHT_OFFSETS
+                .iter()
+                .map(|d| offset(*d))
+                .filter(Position::is_on_board)
+                .collect(),
+            Piece::King(_, _) => KING_OFFSETS
+                .iter()
+                .map(|d| offset(*d))
+                .filter(Position::is_on_board)
+                .collect(),
+            Piece::Bishop(_, _) => self.get_sliding_squares(pos, &DIAGONAL_DIRECTIONS),
+            Piece::Rook(_, _) => self.get_sliding_squares(pos, &ORTHOGONAL_DIRECTIONS),
+            Piece::Queen(_, _) => {
+                let mut result = self.get_sliding_squares(pos, &DIAGONAL_DIRECTIONS);
+                result.extend(self.get_sliding_squares(pos, &ORTHOGONAL_DIRECTIONS));
+                result
+            }
+        }
+    }
+
+    fn get_sliding_squares(&self, from: Position, directions: &[(i32, i32)]) -> Vec<Position> {
+        let mut result = vec![];
+        for (drow, dcol) in directions {
+            let mut pos = Position::new(from.get_row() + drow, from.get_col() + dcol);
+            while pos.is_on_board() {
+                result.push(pos);
+                if self.has_piece(pos) {
+                    break;
+                }
+                pos = Position::new(pos.get_row() + drow, pos.get_col() + dcol);
+            }
+        }
+        result
+    }
+
+    // Get the positions of the pieces of a color that attack a square. A
+    // square held by one of their own pieces is defended by them.
+    pub fn attackers_of(&self, pos: Position, color: Color) -> Vec<Position> {
+        self.get_attackers(pos)
+            .into_iter()
+            .filter(|piece| piece.get_color() == color)
+            .map(|piece| piece.get_pos())
+            .collect()
+    }
+
+    // Count the pieces of both colors attacking and defending every square.
+    pub fn get_attack_map(&self) -> AttackMap {
+        let mut map = AttackMap {
+            white: [0; 64],
+            black: [0; 64],
+        };
+        for piece in self.squares.iter().filter_map(|square| square.get_piece()) {
+            let counts = match piece.get_color() {
+                WHITE => &mut map.white,
+                BLACK => &mut map.black,
+            };
+            for pos in self.get_attacked_squares(piece) {
+                counts[AttackMap::index(pos)] += 1;
+            }
+        }
+        map
+    }
+
+    // Get the pieces of both colors attacking or defending a square.
+    fn get_attackers(&self, pos: Position) -> Vec<Piece> {
+        if pos.is_off_board() {
+            return vec![];
+        }
+        self.squares
+            .iter()
+            .filter_map(|square| square.get_piece())
+            .filter(|piece| self.get_attacked_squares(*piece).contains(&pos))
+            .collect()
+    }
+
+    // Get the material in pawns won by the current player with a move, once
+    // every capture on the square it moves to has been played out. Both
+    // players capture with their least valuable piece first, and stop when
+    // going on would lose material. Pieces lined up behind the ones that
+    // capture, like a rook behind a queen, join in once those have moved.
+    // A move to a square where the piece is simply lost is negative.
+    pub fn see(&self, m: Move) -> i32 {
+        let (from, to, promotion) = match m {
+            Move::Piece(from, to) => (from, to, None),
+            Move::Promotion(from, to, piece) => (from, to, Some(piece)),
+            _ => return 0,
+        };
+        let mut piece = match self.get_piece(from) {
+            Some(piece) => piece,
+            None => return 0,
+        };
+        let color = piece.get_color();
+        let mut board = *self;
+        let mut gains = vec![self.get_piece(to).map_or(0, |piece| piece.get_material_value())];
+
+        if piece.is_pawn() {
+            // En passant
+            if from.get_col() != to.get_col() && self.has_no_piece(to) {
+                *board.get_square(Position::new(from.get_row(), to.get_col())) = EMPTY_SQUARE;
+                gains[0] = 1;
+            }
+            // Pawns reaching the last row are promoted to queens, unless
+            // the move says otherwise.
+            if to.get_row() == 0 || to.get_row() == 7 {
+                piece = match promotion {
+                    Some(promotion) if !promotion.is_king() && !promotion.is_pawn() => {
+                        promotion.with_color(color)
+                    }
+                    _ => Piece::Queen(color, from),
+                };
+                gains[0] += piece.get_material_value() - 1;
+            }
+        }
+        board.en_passant = None;
+        *board.get_square(from) = EMPTY_SQUARE;
+        board.add_piece(piece.move_to(to));
+
+        // Each gain is the material won by the player capturing, if the
+        // piece they capture with isn't captured back. Pawns taking back
+        // on the last row are promoted to queens too.
+        let mut value = piece.get_material_value();
+        let mut turn = !color;
+        while let Some(mut attacker) = board.get_least_valuable_attacker(to, turn) {
+            let mut gain = value;
+            if attacker.is_pawn() && (to.get_row() == 0 || to.get_row() == 7) {
+                attacker = Piece::Queen(turn, attacker.get_pos());
+                gain += attacker.get_material_value() - 1;
+            }
+            gains.push(gain - gains[gains.len() - 1]);
+            *board.get_square(attacker.get_pos()) = EMPTY_SQUARE;
+            board.add_piece(attacker.move_to(to));
+            value = attacker.get_material_value();
+            turn = !turn;
+        }
+
+        // Every player only captures if it gains more than stopping.
+        for i in (1..gains.len()).rev() {
+            gains[i - 1] = -(-gains[i - 1]).max(gains[i]);
+        }
+        gains[0]
+    }
+
+    // Get the least valuable piece of a color that attacks a square.
+    fn get_least_valuable_attacker(&self, pos: Position, color: Color) -> Option<Piece> {
+        self.squares
+            .iter()
+            .filter_map(|square| square.get_piece())
+            .filter(|piece| piece.get_color() == color && piece.is_legal_attack(pos, self))
+            .min_by_key(|piece| piece.get_material_value())
+    }
+
+    // Get the pieces of a color pinned against their king, each with the
+    // enemy piece pinning it and the line it can still move along.
+    pub fn get_pins(&self, color: Color) -> Vec<Pin> {
+        self.get_lined_up(color, color)
+    }
+
+    // Get the positions of the enemy pieces giving check to the king of a
+    // color, which is empty when it isn't in check.
+    pub fn get_checkers(&self, color: Color) -> Vec<Position> {
+        match self.get_king_pos(color) {
+            Some(king_pos) => self.attackers_of(king_pos, !color),
+            None => vec![],
+        }
+    }
+
+    // Get the pieces of a color that give a discovered check to the enemy
+    // king when they move off the line between it and one of their rooks,
+    // bishops or queens.
+    pub fn get_discovered_checks(&self, color: Color) -> Vec<Pin> {
+        self.get_lined_up(!color, color)
+    }
+
+    // Find the pieces of a color standing alone between the king of another
+    // color and an enemy rook, bishop or queen, by looking along every line
+    // going out of the king.
+    fn get_lined_up(&self, king_color: Color, color: Color) -> Vec<Pin> {
+        let king_pos = match self.get_king_pos(king_color) {
+            Some(king_pos) => king_pos,
+            None => return vec![],
+        };
+
+        let mut result = vec![];
+        for (row_step, col_step) in [
+            (1, 0),
+            (-1, 0),
+            (0, 1),
+            (0, -1),
+            (1, 1),
+            (1, -1),
+            (-1, 1),
+            (-1, -1),
+        ] {
+            let is_diagonal = row_step != 0 && col_step != 0;
+            let mut ray = vec![];
+            let mut piece = None;
+            let mut pos = king_pos;
+            loop {
+                pos = Position::new(pos.get_row() + row_step, pos.get_col() + col_step);
+                if pos.is_off_board() {
+                    break;
+                }
+                ray.push(pos);
+                let found = match self.get_piece(pos) {
+                    Some(found) => found,
+                    None => continue,
+                };
+
+                match piece {
+                    None if found.get_color() == color => piece = Some(pos),
+                    None => break,
+                    Some(piece) => {
+                        let slides_here = if is_diagonal {
+                            found.is_bishop() || found.is_queen()
+                        } else {
+                            found.is_rook() || found.is_queen()
+                        };
+                        if found.get_color() != king_color && slides_here {
+                            result.push(Pin {
+                                piece,
+                                attacker: pos,
+                                ray,
+                            });
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        result
+    }
+
     // Is the king of a given color in check
     #[inline]
     pub fn is_in_check(&self, color: Color) -> bool {
@@ -554,6 +1311,7 @@ impl Board {
                     && self.white_castling_rights.can_queenside_castle()
                     && !self.is_in_check(color)
                     && !self.is_threatened(Position::queen_pos(color), color)
+                    && !self.is_threatened(Position::queen_pos(color).next_left(), color)
             }
             BLACK => {
                 self.has_no_piece(Position::new(7, 1))
@@ -564,6 +1322,7 @@ impl Board {
                     && self.black_castling_rights.can_queenside_castle()
                     && !self.is_in_check(color)
                     && !self.is_threatened(Position::queen_pos(color), color)
+                    && !self.is_threatened(Position::queen_pos(color).next_left(), color)
             }
         }
     }
@@ -576,6 +1335,69 @@ impl Board {
         }
     }
 
+    // Does a player still have the right to castle kingside later in the game?
+    //
+    // Unlike `can_kingside_castle`, this does not care whether castling is
+    // possible right now, only that neither the king nor the kingside rook
+    // have left their starting squares.
+    pub fn has_kingside_castling_right(&self, color: Color) -> bool {
+        let king_pos = Position::king_pos(color);
+        let rook_pos = Position::new(king_pos.get_row(), 7);
+        self.get_castling_rights(color).can_kingside_castle()
+            && self.get_piece(king_pos) == Some(Piece::King(color, king_pos))
+            && self.get_piece(rook_pos) == Some(Piece::Rook(color, rook_pos))
+    }
+
+    // Does a player still have the right to castle queenside later in the game?
+    pub fn has_queenside_castling_right(&self, color: Color) -> bool {
+        let king_pos = Position::king_pos(color);
+        let rook_pos = Position::new(king_pos.get_row(), 0);
+        self.get_castling_rights(color).can_queenside_castle()
+            && self.get_piece(king_pos) == Some(Piece::King(color, king_pos))
+            && self.get_piece(rook_pos) == Some(Piece::Rook(color, rook_pos))
+    }
+
+    // Get the Polyglot compatible Zobrist key of this position.
+    //
+    // A castling right only counts while the king and the respective rook
+    // are still on their starting squares, and the en-passant square only
+    // counts when a pawn of the current player could actually capture on it,
+    // just like the Polyglot book format expects.
+    pub fn get_zobrist_key(&self) -> u64 {
+        let mut key = 0;
+        for square in &self.squares {
+            if let Some(piece) = square.get_piece() {
+                key ^= zobrist::piece_key(piece);
+            }
+        }
+
+        for (i, color) in [WHITE, BLACK].iter().enumerate() {
+            if self.has_kingside_castling_right(*color) {
+                key ^= zobrist::POLYGLOT_RANDOM[zobrist::CASTLE_OFFSET + 2 * i];
+            }
+            if self.has_queenside_castling_right(*color) {
+                key ^= zobrist::POLYGLOT_RANDOM[zobrist::CASTLE_OFFSET + 2 * i + 1];
+            }
+        }
+
+        if let Some(en_passant) = self.en_passant {
+            let pawn_pos = en_passant.pawn_back(self.turn);
+            let can_capture = [pawn_pos.next_left(), pawn_pos.next_right()]
+                .iter()
+                .any(|pos| self.get_piece(*pos) == Some(Piece::Pawn(self.turn, *pos)));
+            if can_capture {
+                key ^= zobrist::POLYGLOT_RANDOM
+                    [zobrist::EN_PASSANT_OFFSET + en_passant.get_col() as usize];
+            }
+        }
+
+        if self.turn == WHITE {
+            key ^= zobrist::POLYGLOT_RANDOM[zobrist::TURN_OFFSET];
+        }
+
+        key
+    }
+
     pub(crate) fn is_legal_move(&self, m: Move, player_color: Color) -> bool {
         match m {
             Move::KingSideCastle => self.can_kingside_castle(player_color),
@@ -621,6 +1443,7 @@ impl Board {
     
     // Does the respective player have sufficient material?
     #[inline]
+    #[allow(clippy::if_same_then_else, clippy::len_zero, clippy::needless_bool)]
     pub fn has_sufficient_material(&self, color: Color) -> bool {
         let mut pieces = vec![];
         for square in &self.squares {
@@ -831,3 +1654,267 @@ pub const H1: Position = Position::new(0, 7);
 // pub const H6: Position = Position::new(5, 7);
 // pub const H7: Position = Position::new(6, 7);
 pub const H8: Position = Position::new(7, 7);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Count the leaf nodes of the tree of legal moves, to check the move
+    // generator against published counts.
+    fn perft(board: &Board, depth: u32) -> u64 {
+        let moves = board.get_legal_moves();
+        if depth <= 1 {
+            return moves.len() as u64;
+        }
+        moves
+            .into_iter()
+            .map(|m| perft(&board.apply_eval_move(m), depth - 1))
+            .sum()
+    }
+
+    fn assert_perft(fen: &str, counts: &[u64]) {
+        let board = Board::from_fen(fen).unwrap();
+        for (depth, count) in counts.iter().enumerate() {
+            assert_eq!(perft(&board, depth as u32 + 1), *count, "{} at depth {}", fen, depth + 1);
+        }
+    }
+
+    #[test]
+    fn perft_start_position() {
+        assert_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902],
+        );
+    }
+
+    // Castling both ways, en passant and pins.
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+    }
+
+    // En passant that would leave the king in check along a row.
+    #[test]
+    fn perft_position_3() {
+        assert_perft("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812]);
+    }
+
+    // Promotions to every piece, with captures.
+    #[test]
+    fn perft_position_4() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    // Out of check, pinned pieces can only move along their pin.
+    #[test]
+    fn pinned_piece_moves_along_pin() {
+        let board = Board::from_fen("4k3/8/8/8/4r3/8/4R3/4K3 w - - 0 1").unwrap();
+        let pins = board.get_pins(WHITE);
+        assert_eq!(pins.len(), 1);
+        assert_eq!(pins[0].get_piece(), Position::new(1, 4));
+        assert_eq!(pins[0].get_attacker(), Position::new(3, 4));
+        let rook_moves = board
+            .get_legal_moves()
+            .into_iter()
+            .filter(|m| matches!(m, Move::Piece(from, _) if *from == Position::new(1, 4)))
+            .count();
+        // Up to the rook and its capture, and down is blocked by the king.
+        assert_eq!(rook_moves, 2);
+    }
+
+    #[test]
+    fn san_disambiguation() {
+        // Knights on b1 and f3 can both reach d2, rooks on a1 and a5 can
+        // both reach a3.
+        let board = Board::from_fen("4k3/8/8/R7/8/8/8/RN2KN2 w - - 0 1").unwrap();
+        assert_eq!(board.get_san(Move::Piece(B1, Position::new(1, 3))), "Nbd2");
+        assert_eq!(board.get_san(Move::Piece(A1, Position::new(2, 0))), "R1a3");
+        assert_eq!(board.parse_san("Nfd2"), Ok(Move::Piece(F1, Position::new(1, 3))));
+        assert_eq!(board.parse_san("R5a3"), Ok(Move::Piece(Position::new(4, 0), Position::new(2, 0))));
+    }
+
+    #[test]
+    fn san_promotions_round_trip() {
+        let board = Board::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotions = board
+            .get_legal_moves()
+            .into_iter()
+            .filter(|m| matches!(m, Move::Promotion(_, _, _)))
+            .collect::<Vec<Move>>();
+        // Pushing to a8 or taking on b8, to each of four pieces.
+        assert_eq!(promotions.len(), 8);
+        for m in promotions {
+            let san = board.get_san(m);
+            assert_eq!(board.parse_san(&san), Ok(m), "{}", san);
+            assert_eq!(board.parse_uci(&board.get_uci(m)), Ok(m));
+        }
+        assert_eq!(board.get_san(board.parse_san("axb8=N").unwrap()), "axb8=N");
+        assert_eq!(board.get_san(board.parse_uci("a7a8").unwrap()), "a8=Q");
+    }
+
+    #[test]
+    fn same_move_however_written() {
+        let board = Board::from_fen("8/4P3/8/8/8/8/k7/6K1 w - - 0 1").unwrap();
+        let queen = board.parse_san("e8=Q").unwrap();
+        let pushed = Move::Piece(Position::new(6, 4), E8);
+        assert!(board.is_same_move(queen, pushed));
+        assert!(!board.is_same_move(board.parse_san("e8=N").unwrap(), pushed));
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert!(board.is_same_move(Move::KingSideCastle, board.parse_uci("e1g1").unwrap()));
+    }
+
+    #[test]
+    fn checkers_and_discovered_checks() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/4N3/4R1K1 w - - 0 1").unwrap();
+        assert!(board.get_checkers(BLACK).is_empty());
+        assert_eq!(board.get_discovered_checks(WHITE).len(), 1);
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert_eq!(board.get_checkers(BLACK), vec![Position::new(0, 4)]);
+    }
+
+    // The keys of positions reached by playing moves from the start, from
+    // the Polyglot book format specification.
+    #[test]
+    fn polyglot_keys() {
+        let positions = [
+            ("", 0x463b96181691fc9c),
+            ("e2e4", 0x823c9b50fd114196),
+            ("e2e4 d7d5", 0x0756b94461c50fb0),
+            ("e2e4 d7d5 e4e5", 0x662fafb965db29d4),
+            // The pawn on e5 can take on f6 en passant.
+            ("e2e4 d7d5 e4e5 f7f5", 0x22a48b5a8e47ff78),
+            // Both kings lose their castling rights.
+            ("e2e4 d7d5 e4e5 f7f5 e1e2", 0x652a607ca3f242c1),
+            ("e2e4 d7d5 e4e5 f7f5 e1e2 e8f7", 0x00fdd303c946bdd9),
+            // The pawn on b4 can take on c3 en passant.
+            ("a2a4 b7b5 h2h4 b5b4 c2c4", 0x3c8123ea7b067637),
+            // White loses the queenside castling right.
+            ("a2a4 b7b5 h2h4 b5b4 c2c4 b4c3 a1a3", 0x5c3f9b829b279560),
+        ];
+
+        for (moves, key) in positions {
+            let mut board = Board::new();
+            for uci in moves.split_whitespace() {
+                board = board.apply_eval_move(board.parse_uci(uci).unwrap());
+            }
+            assert_eq!(board.get_zobrist_key(), key, "after `{}`", moves);
+        }
+    }
+
+    #[test]
+    fn see_on_defended_captures() {
+        let see = |fen: &str, san: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            board.see(board.parse_san(san).unwrap())
+        };
+        // An undefended pawn is won.
+        assert_eq!(see("4k3/8/8/4p3/8/8/8/4R1K1 w - - 0 1", "Rxe5"), 1);
+        // The rook that takes a defended pawn is lost for it.
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4R1K1 w - - 0 1", "Rxe5"), -4);
+        // A pawn can take a defended knight.
+        assert_eq!(see("4k3/8/5p2/4n3/3P4/8/8/4K3 w - - 0 1", "dxe5"), 2);
+        // The queen behind the rook takes back, which still loses the
+        // exchange for a knight and a pawn.
+        assert_eq!(see("4k3/8/3p4/4n3/8/8/4R3/4Q1K1 w - - 0 1", "Rxe5"), -1);
+        // A knight moved where a pawn takes it is simply lost.
+        assert_eq!(see("4k3/8/3p4/8/8/5N2/8/4K3 w - - 0 1", "Ne5"), -3);
+    }
+
+    #[test]
+    fn see_promotes_pawns_taking_back_on_the_last_row() {
+        let see = |fen: &str, san: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            board.see(board.parse_san(san).unwrap())
+        };
+        // A pawn promoting with a capture wins the rook and a queen for it.
+        assert_eq!(see("1r2k3/P7/8/8/8/8/8/4K3 w - - 0 1", "axb8=Q"), 13);
+        // The knight can't take back the rook, or the pawn takes it and
+        // promotes.
+        assert_eq!(see("1r2k3/P7/2n5/8/8/8/8/1R2K3 w - - 0 1", "Rxb8"), 5);
+        // The rook taking the knight is lost to a pawn that promotes.
+        assert_eq!(see("2R5/8/8/7k/8/8/1p6/2n1K3 w - - 0 1", "Rxc1"), -10);
+    }
+
+    #[test]
+    fn attackers_of_lined_up_and_pinned_pieces() {
+        let sq = |s: &str| Position::pgn(s).unwrap();
+        let attackers = |board: &Board, s: &str, color: Color| {
+            let mut result = board.attackers_of(sq(s), color);
+            result.sort();
+            result
+        };
+        // The rook on d1 is behind the one on d2 and the queen on g8 behind
+        // the bishop on f7, so neither attacks d5. The knight on e3 is pinned
+        // by the rook on e8 but still attacks it.
+        let board = Board::from_fen("4r1qk/5b2/2p5/3p4/5N2/1B2N3/3R4/3RK3 w - - 0 1").unwrap();
+        assert_eq!(board.get_pins(WHITE)[0].get_piece(), sq("e3"));
+        assert_eq!(
+            attackers(&board, "d5", WHITE),
+            vec![sq("d2"), sq("b3"), sq("e3"), sq("f4")]
+        );
+        assert_eq!(attackers(&board, "d5", BLACK), vec![sq("c6"), sq("f7")]);
+
+        // A piece is defended by its own pieces that could take back on its
+        // square, and not by itself.
+        assert_eq!(attackers(&board, "d2", WHITE), vec![sq("d1"), sq("e1")]);
+        assert_eq!(attackers(&board, "f7", BLACK), vec![sq("g8")]);
+        assert_eq!(attackers(&board, "f4", WHITE), vec![]);
+        assert_eq!(attackers(&board, "e3", BLACK), vec![sq("e8")]);
+
+        let map = board.get_attack_map();
+        for (s, white, black) in [("d5", 4, 2), ("d2", 2, 0), ("f7", 0, 1), ("e3", 0, 1)] {
+            assert_eq!(map.get_count(sq(s), WHITE), white, "{}", s);
+            assert_eq!(map.get_count(sq(s), BLACK), black, "{}", s);
+            assert_eq!(map.get_control(sq(s)), white as i32 - black as i32, "{}", s);
+        }
+    }
+
+    #[test]
+    fn attack_map_of_the_start_position() {
+        let map = Board::default().get_attack_map();
+        let sq = |s: &str| Position::pgn(s).unwrap();
+
+        // Every square of the third rank is attacked by two pawns, or by a
+        // pawn and a knight, and the knights add one to c3 and f3.
+        let third_rank = [2, 2, 3, 2, 2, 3, 2, 2];
+        for col in 0..8 {
+            let white = Position::new(2, col);
+            let black = Position::new(5, col);
+            assert_eq!(map.get_count(white, WHITE), third_rank[col as usize], "{}", white);
+            assert_eq!(map.get_count(white, BLACK), 0, "{}", white);
+            assert_eq!(map.get_count(black, BLACK), third_rank[col as usize], "{}", black);
+            assert_eq!(map.get_count(black, WHITE), 0, "{}", black);
+        }
+
+        // The pawns on d2 and e2 are defended by the king, the queen, a
+        // bishop and a knight.
+        assert_eq!(map.get_count(sq("d2"), WHITE), 4);
+        assert_eq!(map.get_count(sq("e7"), BLACK), 4);
+        assert_eq!(map.get_count(sq("a1"), WHITE), 0);
+        assert_eq!(map.get_control(sq("c3")), 3);
+        assert_eq!(map.get_control(sq("f6")), -3);
+        assert_eq!(map.get_control(sq("e4")), 0);
+        assert_eq!(map.get_count(Position::new(8, 0), WHITE), 0);
+
+        // The third and second ranks, and every square of the first rank
+        // but the corners.
+        assert_eq!(map.get_mobility(WHITE), 22);
+        assert_eq!(map.get_mobility(BLACK), 22);
+    }
+}
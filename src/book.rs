@@ -0,0 +1,186 @@
+use crate::{
+    board::Board,
+    game::Move,
+    piece::{Piece, Position},
+    utils::Rng,
+};
+
+// The size of a single entry in a Polyglot `.bin` file.
+const ENTRY_SIZE: usize = 16;
+
+// A single entry of a Polyglot opening book.
+//
+// All numbers are stored big-endian in the file. The move is packed as
+// `to file | to row << 3 | from file << 6 | from row << 9 | promotion << 12`,
+// and castling is written as the king capturing its own rook.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BookEntry {
+    key: u64,
+    raw_move: u16,
+    weight: u16,
+    learn: u32,
+}
+
+impl BookEntry {
+    #[inline]
+    pub fn get_key(&self) -> u64 {
+        self.key
+    }
+
+    #[inline]
+    pub fn get_weight(&self) -> u16 {
+        self.weight
+    }
+
+    #[inline]
+    pub fn get_learn(&self) -> u32 {
+        self.learn
+    }
+
+    // Decode the move of this entry for the given board.
+    //
+    // This does not check the legality of the move.
+    pub fn get_move(&self, board: &Board) -> Move {
+        let to = Position::new(
+            ((self.raw_move >> 3) & 7) as i32,
+            (self.raw_move & 7) as i32,
+        );
+        let from = Position::new(
+            ((self.raw_move >> 9) & 7) as i32,
+            ((self.raw_move >> 6) & 7) as i32,
+        );
+        let color = board.get_turn_color();
+
+        if let Some(Piece::King(_, _)) = board.get_piece(from) {
+            if from == Position::king_pos(color) && to.get_row() == from.get_row() {
+                if to.get_col() == 7 {
+                    return Move::KingSideCastle;
+                } else if to.get_col() == 0 {
+                    return Move::QueenSideCastle;
+                }
+            }
+        }
+
+        match (self.raw_move >> 12) & 7 {
+            1 => Move::Promotion(from, to, Piece::Knight(color, to)),
+            2 => Move::Promotion(from, to, Piece::Bishop(color, to)),
+            3 => Move::Promotion(from, to, Piece::Rook(color, to)),
+            4 => Move::Promotion(from, to, Piece::Queen(color, to)),
+            _ => Move::Piece(from, to),
+        }
+    }
+}
+
+// A Polyglot opening book.
+//
+// Books can be read from the raw bytes of a `.bin` file, for example when
+// the web page fetches one, or opened straight from a local file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Book {
+    // Sorted by key, as in the file, so positions can be binary searched.
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    // Parse a book from the contents of a Polyglot `.bin` file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(format!(
+                "invalid polyglot book size `{}`, expected a multiple of {}",
+                bytes.len(),
+                ENTRY_SIZE
+            ));
+        }
+
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| {
+                let mut key = [0; 8];
+                key.copy_from_slice(&chunk[0..8]);
+                let mut learn = [0; 4];
+                learn.copy_from_slice(&chunk[12..16]);
+                BookEntry {
+                    key: u64::from_be_bytes(key),
+                    raw_move: u16::from_be_bytes([chunk[8], chunk[9]]),
+                    weight: u16::from_be_bytes([chunk[10], chunk[11]]),
+                    learn: u32::from_be_bytes(learn),
+                }
+            })
+            .collect::<Vec<BookEntry>>();
+
+        // Books are supposed to be sorted already, but a stable sort of a
+        // sorted list is cheap and makes the binary search safe regardless.
+        entries.sort_by_key(|entry| entry.key);
+
+        Ok(Self { entries })
+    }
+
+    // Read a book from a local Polyglot `.bin` file.
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| format!("could not read book `{}`: {}", path.display(), e))?;
+        Self::from_bytes(&bytes)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Get the legal book moves for the current player, with their weights.
+    pub fn get_moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        let key = board.get_zobrist_key();
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let color = board.get_turn_color();
+
+        self.entries[start..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .map(|entry| (entry.get_move(board), entry.weight))
+            .filter(|(m, _)| board.is_legal_move(*m, color))
+            .collect()
+    }
+
+    // Pick a book move for the current player.
+    //
+    // If `random` is true, the move is picked at random in proportion to the
+    // weights of the moves. Otherwise the move with the highest weight is
+    // always played. Moves with a weight of zero are never played.
+    pub fn get_move(&self, board: &Board, random: bool) -> Option<Move> {
+        let moves = self
+            .get_moves(board)
+            .into_iter()
+            .filter(|(_, weight)| *weight > 0)
+            .collect::<Vec<(Move, u16)>>();
+
+        if !random {
+            // `max_by_key` returns the last maximum, prefer the first one
+            // like other Polyglot tools do.
+            return moves
+                .iter()
+                .rev()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(m, _)| *m);
+        }
+
+        let total: u64 = moves.iter().map(|(_, weight)| *weight as u64).sum();
+        if total == 0 {
+            return None;
+        }
+
+        let mut choice = Rng::from_time().below(total);
+        for (m, weight) in moves {
+            if choice < weight as u64 {
+                return Some(m);
+            }
+            choice -= weight as u64;
+        }
+        None
+    }
+}
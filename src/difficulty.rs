@@ -0,0 +1,273 @@
+use crate::{
+    board::Board,
+    game::Move,
+    params::EvalParams,
+    piece::{Piece, Position, WHITE},
+    search::{Search, SearchLimits, SearchResult, MAX_DEPTH},
+    utils::Rng,
+};
+use std::time::Duration;
+
+// How a difficulty level plays: how deep it searches, how much random noise
+// in pawns is added to the rating of every move, and how often it plays a
+// random move other than the best one on purpose. Every level also stops
+// searching after `Difficulty::MOVE_TIME`, so the strongest one searches as
+// deep as it can in that time.
+struct Level {
+    depth: i32,
+    noise: f64,
+    blunder_chance: f64,
+    elo: u32,
+}
+
+// The Elo ratings are rough estimates of the strength of each level against
+// club players. They can be measured more precisely by playing levels
+// against a strength limited UCI engine with the `selfplay` tool.
+const LEVELS: [Level; 20] = [
+    Level { depth: 0, noise: 4.0, blunder_chance: 0.30, elo: 400 },
+    Level { depth: 0, noise: 3.0, blunder_chance: 0.25, elo: 500 },
+    Level { depth: 0, noise: 2.5, blunder_chance: 0.20, elo: 600 },
+    Level { depth: 0, noise: 2.0, blunder_chance: 0.15, elo: 700 },
+    Level { depth: 1, noise: 2.0, blunder_chance: 0.15, elo: 800 },
+    Level { depth: 1, noise: 1.5, blunder_chance: 0.12, elo: 900 },
+    Level { depth: 1, noise: 1.2, blunder_chance: 0.10, elo: 1000 },
+    Level { depth: 1, noise: 1.0, blunder_chance: 0.08, elo: 1100 },
+    Level { depth: 1, noise: 0.8, blunder_chance: 0.06, elo: 1200 },
+    Level { depth: 2, noise: 0.8, blunder_chance: 0.05, elo: 1300 },
+    Level { depth: 2, noise: 0.6, blunder_chance: 0.04, elo: 1400 },
+    Level { depth: 2, noise: 0.5, blunder_chance: 0.03, elo: 1500 },
+    Level { depth: 2, noise: 0.4, blunder_chance: 0.02, elo: 1550 },
+    Level { depth: 3, noise: 0.3, blunder_chance: 0.01, elo: 1600 },
+    Level { depth: 3, noise: 0.2, blunder_chance: 0.0, elo: 1650 },
+    Level { depth: 3, noise: 0.1, blunder_chance: 0.0, elo: 1700 },
+    Level { depth: 4, noise: 0.0, blunder_chance: 0.0, elo: 1800 },
+    Level { depth: 6, noise: 0.0, blunder_chance: 0.0, elo: 1950 },
+    Level { depth: 8, noise: 0.0, blunder_chance: 0.0, elo: 2100 },
+    Level { depth: MAX_DEPTH, noise: 0.0, blunder_chance: 0.0, elo: 2250 },
+];
+
+// The skill level the computer plays at, from 1 for complete beginners to
+// 20 for its full strength.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Difficulty {
+    level: u8,
+}
+
+impl Difficulty {
+    pub const MIN_LEVEL: u8 = 1;
+    pub const MAX_LEVEL: u8 = LEVELS.len() as u8;
+    // The longest time a level thinks about a move.
+    pub const MOVE_TIME: Duration = Duration::from_secs(10);
+
+    pub fn new(level: u8) -> Result<Self, String> {
+        if (Self::MIN_LEVEL..=Self::MAX_LEVEL).contains(&level) {
+            Ok(Self { level })
+        } else {
+            Err(format!(
+                "invalid difficulty level {}, expected {} to {}",
+                level,
+                Self::MIN_LEVEL,
+                Self::MAX_LEVEL
+            ))
+        }
+    }
+
+    // Get the level whose strength is closest to an Elo rating.
+    pub fn from_elo(elo: u32) -> Self {
+        let index = (0..LEVELS.len())
+            .min_by_key(|i| LEVELS[*i].elo.abs_diff(elo))
+            .unwrap();
+        Self {
+            level: index as u8 + 1,
+        }
+    }
+
+    #[inline]
+    fn get(&self) -> &'static Level {
+        &LEVELS[self.level as usize - 1]
+    }
+
+    #[inline]
+    pub fn get_level(&self) -> u8 {
+        self.level
+    }
+
+    // Get the approximate Elo rating of the level.
+    #[inline]
+    pub fn get_elo(&self) -> u32 {
+        self.get().elo
+    }
+
+    #[inline]
+    pub fn get_depth(&self) -> i32 {
+        self.get().depth
+    }
+
+    // Get the standard deviation of the noise added to move ratings, in
+    // pawns.
+    #[inline]
+    pub fn get_noise(&self) -> f64 {
+        self.get().noise
+    }
+
+    // Get the probability of playing a random move other than the best one.
+    #[inline]
+    pub fn get_blunder_chance(&self) -> f64 {
+        self.get().blunder_chance
+    }
+
+    // Get the limits of the search of the level.
+    pub fn get_limits(&self) -> SearchLimits {
+        SearchLimits::new()
+            .with_depth(self.get_depth())
+            .with_time(Self::MOVE_TIME)
+    }
+
+    // Pick a move for the current player at this level.
+    pub fn get_move(&self, board: &Board, search: &Search, params: &EvalParams) -> Move {
+        self.get_move_and_value(board, search, params).0
+    }
+
+    // Pick a move like `get_move`, along with the value of the position
+    // for the current player, which is the rating of the best move whether
+    // or not it is the one picked.
+    pub fn get_move_and_value(
+        &self,
+        board: &Board,
+        search: &Search,
+        params: &EvalParams,
+    ) -> (Move, f64) {
+        self.get_move_with_progress(board, search, &self.get_limits(), params, |_| {})
+    }
+
+    // Pick a move like `get_move_and_value` with a search within `limits`,
+    // which are usually the ones of `get_limits`, reporting the result of
+    // every depth as soon as it is finished. The move is picked among the
+    // ones rated by the deepest depth, so nothing is searched twice.
+    pub fn get_move_with_progress(
+        &self,
+        board: &Board,
+        search: &Search,
+        limits: &SearchLimits,
+        params: &EvalParams,
+        progress: impl FnMut(&SearchResult),
+    ) -> (Move, f64) {
+        // Only the levels that don't always play the best move need the
+        // rating of the others.
+        let result = if self.get_noise() > 0.0 || self.get_blunder_chance() > 0.0 {
+            search.run_with_move_values(board, limits, params, progress)
+        } else {
+            search.run_with_progress(board, limits, params, progress)
+        };
+        (self.pick_move(&result, params), result.get_value())
+    }
+
+    // Pick the move to play among the ones rated by a search, which is a
+    // random one at the blunder chance of the level, and otherwise the best
+    // one once noise is added to every rating.
+    fn pick_move(&self, result: &SearchResult, params: &EvalParams) -> Move {
+        let best = result.get_best_move();
+        let move_values = result.get_move_values();
+        if move_values.len() < 2 {
+            return best;
+        }
+
+        let mut rng = Rng::from_time();
+        if rng.next_f64() < self.get_blunder_chance() {
+            let others = move_values
+                .iter()
+                .map(|(m, _)| *m)
+                .filter(|m| *m != best)
+                .collect::<Vec<Move>>();
+            return others[rng.below(others.len() as u64) as usize];
+        }
+
+        // The noise is scaled by the value of a pawn, so that it means the
+        // same with any evaluation parameters.
+        let pawn = params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)));
+        move_values
+            .iter()
+            .map(|(m, value)| (*m, value + rng.next_gaussian() * self.get_noise() * pawn))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(m, _)| m)
+            .unwrap()
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self { level: 10 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Evaluate;
+
+    // White mates with Ra8, and every other move lets black live.
+    const MATE_IN_ONE: &str = "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1";
+
+    #[test]
+    fn every_move_is_rated_once() {
+        let board = Board::from_fen(MATE_IN_ONE).unwrap();
+        let limits = SearchLimits::new().with_depth(1);
+        let result = Search::default().run_with_move_values(
+            &board,
+            &limits,
+            &EvalParams::default(),
+            |_| {},
+        );
+        let move_values = result.get_move_values();
+        assert_eq!(move_values.len(), board.get_legal_moves().len());
+        let best = move_values
+            .iter()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        assert_eq!(*best, (result.get_best_move(), result.get_value()));
+        assert_eq!(board.get_san(best.0), "Ra8#");
+    }
+
+    #[test]
+    fn levels_without_noise_play_the_best_move() {
+        let board = Board::from_fen(MATE_IN_ONE).unwrap();
+        let difficulty = Difficulty::from_elo(1800);
+        assert_eq!(difficulty.get_noise(), 0.0);
+        let m = difficulty.get_move(&board, &Search::default(), &EvalParams::default());
+        assert_eq!(board.get_san(m), "Ra8#");
+    }
+
+    #[test]
+    fn stopped_searches_play_their_best_move() {
+        let board = Board::from_fen(MATE_IN_ONE).unwrap();
+        let difficulty = Difficulty::new(Difficulty::MAX_LEVEL).unwrap();
+        let limits = difficulty.get_limits().with_stop_check(|| true);
+        let (m, _) = difficulty.get_move_with_progress(
+            &board,
+            &Search::default(),
+            &limits,
+            &EvalParams::default(),
+            |_| {},
+        );
+        assert!(board.get_legal_moves().contains(&m));
+    }
+
+    #[test]
+    fn strongest_levels_search_deep() {
+        let strongest = Difficulty::new(Difficulty::MAX_LEVEL).unwrap();
+        assert!(strongest.get_depth() >= 8);
+        let limits = strongest.get_limits();
+        assert_eq!(limits.get_time(), Some(Difficulty::MOVE_TIME));
+    }
+
+    #[test]
+    fn weakest_level_plays_legal_moves() {
+        let board = Board::new();
+        let difficulty = Difficulty::new(Difficulty::MIN_LEVEL).unwrap();
+        let search = Search::default();
+        for _ in 0..10 {
+            let m = difficulty.get_move(&board, &search, &EvalParams::default());
+            assert!(board.get_legal_moves().contains(&m));
+        }
+    }
+}
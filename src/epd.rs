@@ -0,0 +1,120 @@
+use crate::board::Board;
+use std::path::Path;
+
+// A position from an EPD file, with its operations.
+//
+// An EPD line starts with the first four fields of a FEN string, followed
+// by operations like `bm Nf3; id "WAC.001";`. Each operation is an opcode
+// and a list of operands, and ends with a semicolon.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Epd {
+    board: Board,
+    operations: Vec<(String, Vec<String>)>,
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let mut fields = line.splitn(5, char::is_whitespace);
+        let fen = fields.by_ref().take(4).collect::<Vec<&str>>().join(" ");
+        let board = Board::from_fen(&fen)?;
+        let mut rest = fields.next().unwrap_or("").trim();
+
+        // Plain FEN lines still have their move counters after the position.
+        let words = rest.split_whitespace().collect::<Vec<&str>>();
+        if words.len() == 2 && words.iter().all(|word| word.parse::<u32>().is_ok()) {
+            rest = "";
+        }
+
+        Ok(Self {
+            board,
+            operations: parse_operations(rest),
+        })
+    }
+
+    #[inline]
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    #[inline]
+    pub fn get_operations(&self) -> &[(String, Vec<String>)] {
+        &self.operations
+    }
+
+    // Get the operands of an operation, if the position has it.
+    pub fn get_operation(&self, opcode: &str) -> Option<&[String]> {
+        self.operations
+            .iter()
+            .find(|(op, _)| op == opcode)
+            .map(|(_, operands)| operands.as_slice())
+    }
+
+    // Get the name of the position from its `id` operation.
+    pub fn get_id(&self) -> Option<&str> {
+        self.get_operation("id")
+            .and_then(|operands| operands.first())
+            .map(|id| id.as_str())
+    }
+}
+
+// Read every position of an EPD file. Empty lines and lines starting with
+// `#` are skipped.
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Vec<Epd>, String> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read `{}`: {}", path.display(), e))?;
+
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            Epd::parse(line).map_err(|e| format!("{}:{}: {}", path.display(), i + 1, e))
+        })
+        .collect()
+}
+
+fn parse_operations(text: &str) -> Vec<(String, Vec<String>)> {
+    let mut result = vec![];
+    let mut words: Vec<String> = vec![];
+    let mut word = String::new();
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => {
+                for ch in chars.by_ref() {
+                    if ch == '"' {
+                        break;
+                    }
+                    word.push(ch);
+                }
+                words.push(std::mem::take(&mut word));
+            }
+            ';' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if !words.is_empty() {
+                    let opcode = words.remove(0);
+                    result.push((opcode, std::mem::take(&mut words)));
+                }
+            }
+            ch if ch.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            ch => word.push(ch),
+        }
+    }
+
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        let opcode = words.remove(0);
+        result.push((opcode, words));
+    }
+    result
+}
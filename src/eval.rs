@@ -0,0 +1,498 @@
+use crate::{
+    board::Board,
+    params::EvalParams,
+    piece::{Color, Piece, Position},
+};
+
+// A positional term of the evaluation, added on top of the material and
+// piece-square value of every piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Term {
+    DoubledPawns,
+    IsolatedPawns,
+    BackwardPawns,
+    PassedPawns,
+    Mobility,
+    BishopPair,
+    RookOpenFiles,
+    PawnShield,
+    KingAttackers,
+}
+
+impl Term {
+    pub const ALL: [Self; 9] = [
+        Self::DoubledPawns,
+        Self::IsolatedPawns,
+        Self::BackwardPawns,
+        Self::PassedPawns,
+        Self::Mobility,
+        Self::BishopPair,
+        Self::RookOpenFiles,
+        Self::PawnShield,
+        Self::KingAttackers,
+    ];
+
+    #[inline]
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::DoubledPawns => "doubled pawns",
+            Self::IsolatedPawns => "isolated pawns",
+            Self::BackwardPawns => "backward pawns",
+            Self::PassedPawns => "passed pawns",
+            Self::Mobility => "mobility",
+            Self::BishopPair => "bishop pair",
+            Self::RookOpenFiles => "rooks on open files",
+            Self::PawnShield => "pawn shield",
+            Self::KingAttackers => "king attackers",
+        }
+    }
+
+    // Get the weighted value of this term for the pieces of one color,
+    // without subtracting the value for the other color. `pawns` are the
+    // pawns of `board`, which are only sorted once for every term.
+    pub fn get_value(
+        &self,
+        board: &Board,
+        pawns: &PawnFiles,
+        color: Color,
+        params: &EvalParams,
+    ) -> f64 {
+        let weights = params.get_weights();
+        match self {
+            Self::DoubledPawns => pawns.count_doubled(color) as f64 * weights.doubled_pawn,
+            Self::IsolatedPawns => pawns.count_isolated(color) as f64 * weights.isolated_pawn,
+            Self::BackwardPawns => {
+                pawns.count_backward(board, color) as f64 * weights.backward_pawn
+            }
+            Self::PassedPawns => pawns
+                .get_passed(color)
+                .iter()
+                .map(|pos| weights.passed_pawn[relative_row(*pos, color) as usize])
+                .sum(),
+            Self::Mobility => count_mobility(board, color) as f64 * weights.mobility,
+            Self::BishopPair => {
+                if count_pieces(board, color, Piece::is_bishop) >= 2 {
+                    weights.bishop_pair
+                } else {
+                    0.0
+                }
+            }
+            Self::RookOpenFiles => get_pieces(board, color)
+                .filter(|piece| piece.is_rook())
+                .map(|rook| {
+                    let col = rook.get_pos().get_col() as usize;
+                    if pawns.files[0][col] + pawns.files[1][col] == 0 {
+                        weights.rook_open_file
+                    } else if pawns.files[color_index(color)][col] == 0 {
+                        weights.rook_half_open_file
+                    } else {
+                        0.0
+                    }
+                })
+                .sum(),
+            Self::PawnShield => {
+                count_pawn_shield(board, color) as f64
+                    * weights.pawn_shield
+                    * board.get_game_phase()
+            }
+            Self::KingAttackers => {
+                count_king_attackers(board, color) as f64
+                    * weights.king_attacker
+                    * board.get_game_phase()
+            }
+        }
+    }
+}
+
+// Get the value of all the positional terms for a player, relative to
+// the opponent.
+pub fn get_positional_value(board: &Board, ally_color: Color, params: &EvalParams) -> f64 {
+    let pawns = PawnFiles::new(board);
+    Term::ALL
+        .iter()
+        .map(|term| {
+            term.get_value(board, &pawns, ally_color, params)
+                - term.get_value(board, &pawns, !ally_color, params)
+        })
+        .sum()
+}
+
+// The value of a single piece in an `Explanation`: its material, and the
+// bonus or penalty for the square it stands on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PieceValue {
+    piece: Piece,
+    material: f64,
+    position: f64,
+}
+
+impl PieceValue {
+    #[inline]
+    pub fn get_piece(&self) -> Piece {
+        self.piece
+    }
+
+    #[inline]
+    pub fn get_material(&self) -> f64 {
+        self.material
+    }
+
+    #[inline]
+    pub fn get_position(&self) -> f64 {
+        self.position
+    }
+
+    #[inline]
+    pub fn get_value(&self) -> f64 {
+        self.material + self.position
+    }
+}
+
+// A breakdown of the score `Board::value_for` gives a player, into the
+// value of every piece on the board and of every positional term.
+//
+// Everything is from the point of view of `color`: the pieces and terms of
+// the opponent count against it, so their values are negative.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+    color: Color,
+    phase: f64,
+    pieces: Vec<PieceValue>,
+    terms: Vec<(Term, f64)>,
+}
+
+impl Explanation {
+    pub fn new(board: &Board, color: Color, params: &EvalParams) -> Self {
+        let phase = board.get_game_phase();
+        let pieces = board
+            .squares()
+            .iter()
+            .filter_map(|square| square.get_piece())
+            .map(|piece| {
+                let sign = if piece.get_color() == color {
+                    1.0
+                } else {
+                    -1.0
+                };
+                PieceValue {
+                    piece,
+                    material: sign * params.get_material_value(piece),
+                    position: sign * piece.get_position_weight(phase, params),
+                }
+            })
+            .collect();
+        let pawns = PawnFiles::new(board);
+        let terms = Term::ALL
+            .iter()
+            .map(|term| {
+                (
+                    *term,
+                    term.get_value(board, &pawns, color, params)
+                        - term.get_value(board, &pawns, !color, params),
+                )
+            })
+            .collect();
+
+        Self {
+            color,
+            phase,
+            pieces,
+            terms,
+        }
+    }
+
+    #[inline]
+    pub fn get_color(&self) -> Color {
+        self.color
+    }
+
+    #[inline]
+    pub fn get_phase(&self) -> f64 {
+        self.phase
+    }
+
+    #[inline]
+    pub fn get_pieces(&self) -> &[PieceValue] {
+        &self.pieces
+    }
+
+    #[inline]
+    pub fn get_terms(&self) -> &[(Term, f64)] {
+        &self.terms
+    }
+
+    // Get the value of the piece standing on a square, if there is one.
+    pub fn get_piece_value(&self, pos: Position) -> Option<PieceValue> {
+        self.pieces
+            .iter()
+            .find(|value| value.piece.get_pos() == pos)
+            .copied()
+    }
+
+    pub fn get_term(&self, term: Term) -> f64 {
+        self.terms
+            .iter()
+            .filter(|(t, _)| *t == term)
+            .map(|(_, value)| value)
+            .sum()
+    }
+
+    pub fn get_material(&self) -> f64 {
+        self.pieces.iter().map(PieceValue::get_material).sum()
+    }
+
+    pub fn get_position(&self) -> f64 {
+        self.pieces.iter().map(PieceValue::get_position).sum()
+    }
+
+    // Get the total score, the same as `Board::value_for`.
+    pub fn get_total(&self) -> f64 {
+        self.get_material()
+            + self.get_position()
+            + self.terms.iter().map(|(_, value)| value).sum::<f64>()
+    }
+}
+
+impl core::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        writeln!(f, "evaluation for {} (phase {:.2})", self.color, self.phase)?;
+        writeln!(f, "  material: {:+.2}", self.get_material())?;
+        writeln!(f, "  piece-square: {:+.2}", self.get_position())?;
+        for value in &self.pieces {
+            writeln!(
+                f,
+                "    {} {} on {}: {:+.2}",
+                value.piece.get_color(),
+                value.piece.get_name(),
+                value.piece.get_pos(),
+                value.position
+            )?;
+        }
+        for (term, value) in &self.terms {
+            writeln!(f, "  {}: {:+.2}", term.get_name(), value)?;
+        }
+        write!(f, "  total: {:+.2}", self.get_total())
+    }
+}
+
+#[inline]
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+// How many rows a square is away from a player's back rank.
+#[inline]
+fn relative_row(pos: Position, color: Color) -> i32 {
+    match color {
+        Color::White => pos.get_row(),
+        Color::Black => 7 - pos.get_row(),
+    }
+}
+
+fn get_pieces(board: &Board, color: Color) -> impl Iterator<Item = Piece> + '_ {
+    board
+        .squares()
+        .iter()
+        .filter_map(|square| square.get_piece())
+        .filter(move |piece| piece.get_color() == color)
+}
+
+fn count_pieces(board: &Board, color: Color, is_kind: fn(&Piece) -> bool) -> usize {
+    get_pieces(board, color).filter(is_kind).count()
+}
+
+fn count_mobility(board: &Board, color: Color) -> usize {
+    get_pieces(board, color)
+        .filter(|piece| !piece.is_pawn() && !piece.is_king())
+        .map(|piece| {
+            board
+                .get_attacked_squares(piece)
+                .into_iter()
+                .filter(|pos| !board.has_ally_piece(*pos, color))
+                .count()
+        })
+        .sum()
+}
+
+// Count the pawns on the king's file and the files next to it, one or two
+// rows in front of the king.
+fn count_pawn_shield(board: &Board, color: Color) -> usize {
+    let king_pos = match board.get_king_pos(color) {
+        Some(pos) => pos,
+        None => return 0,
+    };
+
+    let mut result = 0;
+    for dcol in -1..=1 {
+        let mut pos = Position::new(king_pos.get_row(), king_pos.get_col() + dcol);
+        for _ in 0..2 {
+            pos = pos.pawn_up(color);
+            if !pos.is_on_board() {
+                break;
+            }
+            if let Some(Piece::Pawn(c, _)) = board.get_piece(pos) {
+                if c == color {
+                    result += 1;
+                    break;
+                }
+            }
+        }
+    }
+    result
+}
+
+// Count the enemy pieces, other than pawns and the king, that attack the
+// king or a square next to it.
+fn count_king_attackers(board: &Board, color: Color) -> usize {
+    let king_pos = match board.get_king_pos(color) {
+        Some(pos) => pos,
+        None => return 0,
+    };
+
+    get_pieces(board, !color)
+        .filter(|piece| !piece.is_pawn() && !piece.is_king())
+        .filter(|piece| {
+            board
+                .get_attacked_squares(*piece)
+                .iter()
+                .any(|pos| *pos == king_pos || pos.is_adjacent_to(king_pos))
+        })
+        .count()
+}
+
+// The pawns of both players, sorted by file.
+pub struct PawnFiles {
+    // The number of pawns on each file, for white then black.
+    files: [[usize; 8]; 2],
+    pawns: [Vec<Position>; 2],
+}
+
+impl PawnFiles {
+    pub fn new(board: &Board) -> Self {
+        let mut result = Self {
+            files: [[0; 8]; 2],
+            pawns: [vec![], vec![]],
+        };
+        for square in board.squares() {
+            if let Some(Piece::Pawn(color, pos)) = square.get_piece() {
+                result.files[color_index(color)][pos.get_col() as usize] += 1;
+                result.pawns[color_index(color)].push(pos);
+            }
+        }
+        result
+    }
+
+    #[inline]
+    fn has_pawn_on_neighbor_file(&self, color: Color, col: i32) -> bool {
+        let files = &self.files[color_index(color)];
+        (col > 0 && files[col as usize - 1] > 0) || (col < 7 && files[col as usize + 1] > 0)
+    }
+
+    fn count_doubled(&self, color: Color) -> usize {
+        self.files[color_index(color)]
+            .iter()
+            .map(|count| count.saturating_sub(1))
+            .sum()
+    }
+
+    fn count_isolated(&self, color: Color) -> usize {
+        self.pawns[color_index(color)]
+            .iter()
+            .filter(|pos| !self.has_pawn_on_neighbor_file(color, pos.get_col()))
+            .count()
+    }
+
+    // A pawn is passed when no enemy pawn can stop or capture it on its way
+    // to promotion.
+    fn get_passed(&self, color: Color) -> Vec<Position> {
+        self.pawns[color_index(color)]
+            .iter()
+            .filter(|pos| {
+                !self.pawns[color_index(!color)].iter().any(|enemy| {
+                    (enemy.get_col() - pos.get_col()).abs() <= 1
+                        && relative_row(*enemy, color) > relative_row(**pos, color)
+                })
+            })
+            .copied()
+            .collect()
+    }
+
+    // A pawn is backward when all the pawns on the files next to it are
+    // further up the board, and it cannot safely move up because an enemy
+    // pawn guards the square in front of it.
+    fn count_backward(&self, board: &Board, color: Color) -> usize {
+        self.pawns[color_index(color)]
+            .iter()
+            .filter(|pos| {
+                if !self.has_pawn_on_neighbor_file(color, pos.get_col()) {
+                    return false;
+                }
+                let supported = self.pawns[color_index(color)].iter().any(|ally| {
+                    (ally.get_col() - pos.get_col()).abs() == 1
+                        && relative_row(*ally, color) <= relative_row(**pos, color)
+                });
+                if supported {
+                    return false;
+                }
+
+                let stop = pos.pawn_up(color);
+                if !stop.is_on_board() {
+                    return false;
+                }
+                let guard = stop.pawn_up(color);
+                [guard.next_left(), guard.next_right()].iter().any(|guard| {
+                    guard.is_on_board()
+                        && matches!(board.get_piece(*guard), Some(Piece::Pawn(c, _)) if c == !color)
+                })
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Evaluate;
+
+    #[test]
+    fn explanation_adds_up_to_the_evaluation() {
+        let mut doubled = EvalParams::default();
+        let values: Vec<f64> = doubled.get_values().iter().map(|v| v * 2.0).collect();
+        doubled.set_values(&values);
+
+        let mut phases = vec![];
+        for fen in [
+            // The opening, with every piece still on the board.
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+            // A middlegame, after a pair of bishops has been traded.
+            "r1bq1rk1/pp3ppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2QK2R w KQ - 0 9",
+            // A rook ending with passed, doubled and isolated pawns.
+            "8/5k2/1p3pp1/1P6/1P4P1/5K2/r7/3R4 w - - 0 40",
+            // A pawn ending, where the phase is zero.
+            "8/8/4k3/3p4/3P1p2/4K3/6P1/8 b - - 0 50",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            phases.push(board.get_game_phase());
+            for params in [&EvalParams::default(), &doubled] {
+                for color in [Color::White, Color::Black] {
+                    let total = board.explain_eval(color, params).get_total();
+                    let value = board.value_for(color, params);
+                    assert!(
+                        (total - value).abs() < 1e-9,
+                        "{} for {}: {} != {}",
+                        fen,
+                        color,
+                        total,
+                        value
+                    );
+                }
+            }
+        }
+        assert_eq!(phases[0], 1.0);
+        assert!(phases.windows(2).all(|pair| pair[0] > pair[1]));
+        assert_eq!(phases[3], 0.0);
+    }
+}
@@ -1,6 +1,18 @@
 use core::convert::TryFrom;
+use std::cell::RefCell;
 use crate::board::Board;
+use crate::book::Book;
+use crate::difficulty::Difficulty;
+use crate::params::EvalParams;
 use crate::piece::{Color, Position, Piece};
+use crate::search::Search;
+
+thread_local! {
+    // The opening book played by `get_next_move`, and whether its moves are
+    // picked at random.
+    #[allow(clippy::missing_const_for_thread_local)]
+    static OPENING_BOOK: RefCell<Option<(Book, bool)>> = RefCell::new(None);
+}
 
 // The result of a move being played on the board.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -155,8 +167,8 @@ impl Move {
 pub trait Evaluate: Sized {
     // Get the value of the board for a given color.
     // This subtracts the opponents value, and accounts for piece positions
-    // and material value.
-    fn value_for(&self, color: Color) -> f64;
+    // and material value, weighted by `params`.
+    fn value_for(&self, color: Color, params: &EvalParams) -> f64;
 
     // Get the current player's color.
     fn get_current_player_color(&self) -> Color;
@@ -167,6 +179,18 @@ pub trait Evaluate: Sized {
     // Apply a move to the board for evaluation.
     fn apply_eval_move(&self, m: Move) -> Self;
 
+    // Get the exact value of the board for a given color from the endgame
+    // tablebase, if one is loaded and knows the position.
+    fn get_tablebase_value(&self, _color: Color) -> Option<f64> {
+        None
+    }
+
+    // Get the best move for the current player and its value from the
+    // endgame tablebase, if one is loaded and knows the position.
+    fn get_tablebase_move(&self) -> Option<(Move, f64)> {
+        None
+    }
+
     // Get the best move for the current player with `depth` number of moves
     // of lookahead.
     //
@@ -177,25 +201,18 @@ pub trait Evaluate: Sized {
     //
     // It's best not to use the rating value by itself for anything, as it
     // is relative to the other player's move ratings as well.
-    fn get_best_next_move(&self, depth: i32) -> (Move, u64, f64) {
-        let legal_moves = self.get_legal_moves();
+    fn get_best_next_move(&self, depth: i32, params: &EvalParams) -> (Move, u64, f64) {
+        if let Some((m, value)) = self.get_tablebase_move() {
+            return (m, 1, value);
+        }
+
+        let (move_values, board_count) = self.get_move_values(depth, params);
         let mut best_move_value = -999999.0;
         let mut best_move = Move::Resign;
 
-        let color = self.get_current_player_color();
-
-        let mut board_count = 0;
-        for m in &legal_moves {
-            let child_board_value = self.apply_eval_move(*m).minimax(
-                depth,
-                -1000000.0,
-                1000000.0,
-                false,
-                color,
-                &mut board_count,
-            );
+        for (m, child_board_value) in move_values {
             if child_board_value >= best_move_value {
-                best_move = *m;
+                best_move = m;
                 best_move_value = child_board_value;
             }
         }
@@ -203,8 +220,35 @@ pub trait Evaluate: Sized {
         (best_move, board_count, best_move_value)
     }
 
+    // Get every legal move for the current player with its exact rating
+    // after `depth` number of moves of lookahead, along with the number of
+    // boards evaluated.
+    fn get_move_values(&self, depth: i32, params: &EvalParams) -> (Vec<(Move, f64)>, u64) {
+        let color = self.get_current_player_color();
+
+        let mut board_count = 0;
+        let move_values = self
+            .get_legal_moves()
+            .into_iter()
+            .map(|m| {
+                let value = self.apply_eval_move(m).minimax(
+                    depth,
+                    -1000000.0,
+                    1000000.0,
+                    false,
+                    color,
+                    params,
+                    &mut board_count,
+                );
+                (m, value)
+            })
+            .collect();
+
+        (move_values, board_count)
+    }
+
     // Get the move for the oppsite player
-    fn get_worst_next_move(&self, depth: i32) -> (Move, u64, f64) {
+    fn get_worst_next_move(&self, depth: i32, params: &EvalParams) -> (Move, u64, f64) {
         let legal_moves = self.get_legal_moves();
         let mut best_move_value = -999999.0;
         let mut best_move = Move::Resign;
@@ -219,6 +263,7 @@ pub trait Evaluate: Sized {
                 1000000.0,
                 true,
                 !color,
+                params,
                 &mut board_count,
             );
 
@@ -238,6 +283,7 @@ pub trait Evaluate: Sized {
     // In other words, choose moves with the assumption that your opponent will make the
     // best possible replies to your moves. Moves that are seemingly good, but are easily countered,
     // are categorically eliminated by this algorithm.
+    #[allow(clippy::too_many_arguments)]
     fn minimax(
         &self,
         depth: i32,
@@ -245,12 +291,17 @@ pub trait Evaluate: Sized {
         mut beta: f64,
         is_maximizing: bool,
         getting_move_for: Color,
+        params: &EvalParams,
         board_count: &mut u64,
     ) -> f64 {
         *board_count += 1;
 
+        if let Some(value) = self.get_tablebase_value(getting_move_for) {
+            return value;
+        }
+
         if depth == 0 {
-            return self.value_for(getting_move_for);
+            return self.value_for(getting_move_for, params);
         }
 
         let legal_moves = self.get_legal_moves();
@@ -266,6 +317,7 @@ pub trait Evaluate: Sized {
                     beta,
                     !is_maximizing,
                     getting_move_for,
+                    params,
                     board_count,
                 );
 
@@ -291,6 +343,7 @@ pub trait Evaluate: Sized {
                     beta,
                     !is_maximizing,
                     getting_move_for,
+                    params,
                     board_count,
                 );
                 if child_board_value < best_move_value {
@@ -311,11 +364,38 @@ pub trait Evaluate: Sized {
     }
 }
 
-pub fn get_next_move(b: &Board, best: bool) -> Move {
-    let (m, _, _) = if best {
-        b.get_best_next_move(2)
-    } else {
-        b.get_worst_next_move(4)
-    };
-    m
-}
\ No newline at end of file
+// Use an opening book for the next moves of `get_next_move`, or stop using
+// one with `None`.
+//
+// If `random` is true, book moves are picked at random by their weights,
+// otherwise the heaviest book move is always played.
+pub fn set_opening_book(book: Option<Book>, random: bool) {
+    OPENING_BOOK.with(|opening_book| {
+        *opening_book.borrow_mut() = book.map(|book| (book, random));
+    });
+}
+
+// Get a move from the opening book, if one is set and knows this position.
+pub fn get_book_move(b: &Board) -> Option<Move> {
+    OPENING_BOOK.with(|opening_book| {
+        opening_book
+            .borrow()
+            .as_ref()
+            .and_then(|(book, random)| book.get_move(b, *random))
+    })
+}
+
+// Get the computer's next move, from the opening book if it knows the
+// position, otherwise played at a difficulty level.
+pub fn get_next_move(
+    b: &Board,
+    difficulty: Difficulty,
+    search: &Search,
+    params: &EvalParams,
+) -> Move {
+    if let Some(m) = get_book_move(b) {
+        return m;
+    }
+
+    difficulty.get_move(b, search, params)
+}
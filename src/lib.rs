@@ -1,13 +1,32 @@
 extern crate web_sys;
 
-mod board;
-mod game;
-mod piece;
+pub mod adjudication;
+pub mod board;
+pub mod book;
+pub mod difficulty;
+pub mod epd;
+pub mod eval;
+pub mod game;
+pub mod params;
+pub mod piece;
+pub mod puzzle;
+pub mod review;
+pub mod search;
+pub mod tablebase;
+pub mod tactics;
 mod utils;
+mod worker;
+mod zobrist;
 
+use adjudication::{Adjudicator, Decision};
 use board::Board;
+use book::Book;
+use difficulty::Difficulty;
 
-use game::get_next_move;
+use game::set_opening_book;
+use params::EvalParams;
+use puzzle::{Puzzle, PuzzleAttempt, Step};
+use review::{Classification, Review};
 use piece::{Color, Position, Piece};
 use std::cell::{Cell, RefCell};
 use std::rc::Rc;
@@ -18,6 +37,7 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{window, Element, HtmlElement, HtmlImageElement, MouseEvent};
 
+use crate::game::Evaluate;
 use crate::game::GameResult;
 use crate::game::Move;
 
@@ -35,7 +55,33 @@ macro_rules! log {
 
 thread_local! {
     // ONly allow select one square at the time
+    #[allow(clippy::missing_const_for_thread_local)]
     static IS_SELECTING: Cell<bool> = Cell::new(false);
+    // The evaluation parameters the computer plays with
+    static EVAL_PARAMS: RefCell<EvalParams> = RefCell::new(EvalParams::default());
+    // The difficulty level the computer plays at
+    static DIFFICULTY: Cell<Difficulty> = Cell::new(Difficulty::default());
+    // Whether the computer thinks about its next move while the player
+    // thinks about theirs
+    static PONDER: Cell<bool> = const { Cell::new(false) };
+    // Decides when the computer resigns, and when it offers or accepts a draw
+    static ADJUDICATOR: RefCell<Adjudicator> = RefCell::new(Adjudicator::default());
+    // Whether the computer offered a draw with its last move
+    static DRAW_OFFERED: Cell<bool> = const { Cell::new(false) };
+    // Whether the game ended without a move on the board, by a draw agreement
+    static GAME_OVER: Cell<bool> = const { Cell::new(false) };
+    // The board the player is to move on, while it is their turn
+    static PLAYER_BOARD: Cell<Option<Board>> = const { Cell::new(None) };
+    // The moves played so far, from the starting position
+    static HISTORY: RefCell<Vec<Move>> = const { RefCell::new(Vec::new()) };
+    // The result of the game in PGN, once it is over
+    static GAME_RESULT: Cell<Option<&'static str>> = const { Cell::new(None) };
+    // The puzzles loaded from a file, and the player's puzzle rating
+    static PUZZLES: RefCell<Vec<Puzzle>> = const { RefCell::new(Vec::new()) };
+    static PUZZLE_RATING: Cell<f64> = const { Cell::new(puzzle::DEFAULT_RATING) };
+    // Counts the puzzles started, so that a puzzle left for the next one
+    // stops taking moves
+    static PUZZLE_COUNT: Cell<u32> = const { Cell::new(0) };
 }
 const ROW: usize = 8;
 const COL: usize = 8;
@@ -43,9 +89,16 @@ static PLAYERCOLOR: Color = Color::White;
 
 #[wasm_bindgen(start)]
 pub async fn run() -> Result<(), JsValue> {
+    // The module is also loaded by the search worker, which has no page.
+    if window().is_none() {
+        return Ok(());
+    }
+
+    worker::start();
     let board = Rc::new(RefCell::new(Board::new()));
     create_board();
     update_board(&board.borrow());
+    EVAL_PARAMS.with(|params| worker::analyze(&board.borrow(), &params.borrow()));
 
     // render loop goes here
     render_loop(Rc::clone(&board));
@@ -53,6 +106,71 @@ pub async fn run() -> Result<(), JsValue> {
     Ok(())
 }
 
+// Load a Polyglot opening book from the bytes of a `.bin` file. The computer
+// plays moves from the book for as long as it knows the position.
+#[wasm_bindgen]
+pub fn load_opening_book(bytes: &[u8], random: bool) -> Result<(), JsValue> {
+    let book = Book::from_bytes(bytes).map_err(|e| JsValue::from_str(&e))?;
+    log!("Loaded opening book with {} entries", book.len());
+    set_opening_book(Some(book), random);
+    worker::send_book(bytes, random);
+    Ok(())
+}
+
+// Set the evaluation parameters the computer plays with, either from an
+// object or from a JSON string. Sections that are left out keep their
+// default values.
+#[wasm_bindgen]
+pub fn set_eval_params(params: JsValue) -> Result<(), JsValue> {
+    let text = match params.as_string() {
+        Some(text) => text,
+        None => js_sys::JSON::stringify(&params)?.into(),
+    };
+    let params = EvalParams::from_json(&text).map_err(|e| JsValue::from_str(&e))?;
+    EVAL_PARAMS.with(|eval_params| *eval_params.borrow_mut() = params);
+    Ok(())
+}
+
+// Set the difficulty level the computer plays at, from 1 to 20.
+#[wasm_bindgen]
+pub fn set_difficulty(level: u8) -> Result<(), JsValue> {
+    let difficulty = Difficulty::new(level).map_err(|e| JsValue::from_str(&e))?;
+    DIFFICULTY.with(|current| current.set(difficulty));
+    Ok(())
+}
+
+// Set the difficulty level closest to an Elo rating, and get that level.
+#[wasm_bindgen]
+pub fn set_difficulty_elo(elo: u32) -> u8 {
+    let difficulty = Difficulty::from_elo(elo);
+    DIFFICULTY.with(|current| current.set(difficulty));
+    difficulty.get_level()
+}
+
+#[wasm_bindgen]
+pub fn get_difficulty() -> u8 {
+    DIFFICULTY.with(|current| current.get().get_level())
+}
+
+// Let the computer think about its next move on the player's time, by
+// searching the reply it expects them to play.
+#[wasm_bindgen]
+pub fn set_ponder(enabled: bool) {
+    PONDER.with(|ponder| ponder.set(enabled));
+}
+
+#[wasm_bindgen]
+pub fn get_ponder() -> bool {
+    PONDER.with(|ponder| ponder.get())
+}
+
+// Get the approximate Elo rating of a difficulty level.
+#[wasm_bindgen]
+pub fn get_difficulty_elo(level: u8) -> Result<u32, JsValue> {
+    let difficulty = Difficulty::new(level).map_err(|e| JsValue::from_str(&e))?;
+    Ok(difficulty.get_elo())
+}
+
 fn create_board() {
     let window = window().expect("no global `window` exists");
     let document = window.document().expect("should have a document on window");
@@ -249,7 +367,8 @@ pub fn get_hint_pos(board: &Board, pos: Position) -> Vec<Position> {
     result
 }
 
-pub fn update_hint_squares(hint_pos: Vec<Position>) {
+// Highlight squares on the board, with the style of a CSS class.
+pub fn update_hint_squares(hint_pos: Vec<Position>, class: &str) {
     let window = window().expect("no global `window` exists");
     let document = window.document().expect("should have a document on window");
     let squares = document.get_elements_by_class_name("square");
@@ -269,7 +388,7 @@ pub fn update_hint_squares(hint_pos: Vec<Position>) {
             .dyn_into::<Element>()
             .expect("failed to cast element");
 
-        hint_box.set_class_name("hint");
+        hint_box.set_class_name(class);
 
         // Check if an <img> child is present
         if let Some(image_element) = square_element.query_selector("img").unwrap() {
@@ -286,15 +405,453 @@ pub fn update_hint_squares(hint_pos: Vec<Position>) {
     }
 }
 
+// Get the move of the piece on one square to another, as selected on the
+// board.
+fn get_selected_move(board: &Board, from: Position, to: Position) -> Move {
+    match board.get_piece(from){
+        // Need to update promotion feature
+        Some(Piece::Pawn(_, _)) => Move::Piece(from, to),
+        Some(Piece::King(_, _)) => {
+            // Regular move
+            if to.is_adjacent_to(from) {
+                Move::Piece(from, to)
+            } else {
+                if to.get_col() > from.get_col() {
+                    Move::KingSideCastle
+                } else {
+                    Move::QueenSideCastle
+                }
+            }
+        }
+        _ => Move::Piece(from, to),
+    }
+}
+
+// Get the squares a move goes from and to.
+fn get_move_squares(board: &Board, m: Move) -> Vec<Position> {
+    let row = if board.get_turn_color() == Color::White { 0 } else { 7 };
+    match m {
+        Move::KingSideCastle => vec![Position::new(row, 4), Position::new(row, 6)],
+        Move::QueenSideCastle => vec![Position::new(row, 4), Position::new(row, 2)],
+        Move::Piece(from, to) | Move::Promotion(from, to, _) => vec![from, to],
+        Move::Resign => vec![],
+    }
+}
+
+// Suggest a move to the player, by searching their position at the
+// computer's full strength, and highlight its squares. The move comes with
+// the tactic it sets up, if there is one.
+#[wasm_bindgen]
+pub fn show_hint() {
+    let board = match PLAYER_BOARD.with(|player_board| player_board.get()) {
+        Some(board) if !GAME_OVER.with(|game_over| game_over.get()) => board,
+        _ => return,
+    };
+    let params = EVAL_PARAMS.with(|params| params.borrow().clone());
+    wasm_bindgen_futures::spawn_local(async move {
+        let difficulty = Difficulty::new(Difficulty::MAX_LEVEL).unwrap();
+        let (m, _) = worker::get_move(&board, difficulty, &params).await;
+        // The player may have moved in the meantime.
+        if PLAYER_BOARD.with(|player_board| player_board.get()) != Some(board) {
+            return;
+        }
+
+        update_hint_squares(get_move_squares(&board, m), "hint-move");
+        let hint = match tactics::find_move_motifs(&board, m).first() {
+            Some(motif) => format!("Hint: {} ({})", board.get_san(m), motif.get_name()),
+            None => format!("Hint: {}", board.get_san(m)),
+        };
+        show_status(Some(&hint));
+    });
+}
+
+// Show the value a search gave the current player's position on the
+// evaluation bar, from white's side. Once the game is over the bar keeps
+// showing its result instead.
+pub fn update_rating_bar(board: &Board, value: f64) {
+    if GAME_OVER.with(|game_over| game_over.get())
+        || GAME_RESULT.with(|game_result| game_result.get()).is_some()
+    {
+        return;
+    }
+    let (fill, score) =
+        EVAL_PARAMS.with(|params| board.rating_bar(value, &params.borrow()));
+    set_rating_bar(fill, &score);
+}
+
+// Fill the evaluation bar for white from 0 to 1, and write a score on it.
+fn set_rating_bar(fill: f64, score: &str) {
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    if let Some(bar) = document.get_element_by_id("eval-bar-fill") {
+        bar.set_attribute("style", &format!("height: {:.1}%", fill * 100.0))
+            .expect("failed to set the height of the evaluation bar");
+    }
+    if let Some(label) = document.get_element_by_id("eval-bar-score") {
+        label.set_text_content(Some(score));
+    }
+}
+
+// Show that the computer is thinking, with its progress so far, or hide
+// the indicator with `None`.
+pub fn show_thinking(progress: Option<&str>) {
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    if let Some(indicator) = document.get_element_by_id("thinking") {
+        indicator
+            .class_list()
+            .toggle_with_force("hidden", progress.is_none())
+            .expect("failed to toggle the thinking indicator");
+    }
+    if let Some(text) = document.get_element_by_id("thinking-progress") {
+        text.set_text_content(progress);
+    }
+}
+
+// Show a message about the game under the board, or hide it with `None`.
+pub fn show_status(message: Option<&str>) {
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    if let Some(status) = document.get_element_by_id("status") {
+        status
+            .class_list()
+            .toggle_with_force("hidden", message.is_none())
+            .expect("failed to toggle the status");
+        status.set_text_content(message);
+    }
+}
+
+// Show or hide the button to accept the draw the computer offers.
+fn show_draw_offer(offered: bool) {
+    DRAW_OFFERED.with(|draw_offered| draw_offered.set(offered));
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    if let Some(button) = document.get_element_by_id("accept-draw") {
+        button
+            .class_list()
+            .toggle_with_force("hidden", !offered)
+            .expect("failed to toggle the accept draw button");
+    }
+}
+
+// End the game as a draw that both players agreed to.
+fn agree_draw() {
+    GAME_OVER.with(|game_over| game_over.set(true));
+    worker::stop_analysis();
+    show_draw_offer(false);
+    show_status(Some("Drawn by agreement"));
+    finish_game("1/2-1/2");
+    log!("Drawn game");
+}
+
+// Offer the computer a draw. It accepts late in the game when it doesn't
+// think it is better, and the game ends. Returns whether it accepted.
+#[wasm_bindgen]
+pub fn offer_draw() -> bool {
+    if GAME_OVER.with(|game_over| game_over.get()) {
+        return false;
+    }
+    if ADJUDICATOR.with(|adjudicator| adjudicator.borrow().accepts_draw()) {
+        agree_draw();
+        true
+    } else {
+        show_status(Some("The computer declines the draw"));
+        false
+    }
+}
+
+// Accept the draw the computer offered with its last move. Returns whether
+// there was an offer to accept.
+#[wasm_bindgen]
+pub fn accept_draw() -> bool {
+    let offered = DRAW_OFFERED.with(|draw_offered| draw_offered.get());
+    if offered && !GAME_OVER.with(|game_over| game_over.get()) {
+        agree_draw();
+        true
+    } else {
+        false
+    }
+}
+
+// Get the result of a game won by `winner`, in PGN.
+fn get_victory_result(winner: Color) -> &'static str {
+    match winner {
+        Color::White => "1-0",
+        Color::Black => "0-1",
+    }
+}
+
+// Record the result of the game once it is over, show it on the evaluation
+// bar, and offer to review it.
+fn finish_game(result: &'static str) {
+    GAME_RESULT.with(|game_result| game_result.set(Some(result)));
+    match result {
+        "1-0" => set_rating_bar(1.0, "1-0"),
+        "0-1" => set_rating_bar(0.0, "0-1"),
+        _ => set_rating_bar(0.5, "½-½"),
+    }
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    if let Some(button) = document.get_element_by_id("review-game") {
+        button
+            .class_list()
+            .remove_1("hidden")
+            .expect("failed to show the review button");
+    }
+}
+
+// Review the game once it is over: the engine analyses every position in
+// it, and the review panel shows how well both players played, with the
+// better move for each error and the game as an annotated PGN.
+#[wasm_bindgen]
+pub fn review_game() {
+    if GAME_RESULT.with(|game_result| game_result.get()).is_none() {
+        return;
+    }
+    show_status(Some("Reviewing the game"));
+    let moves = HISTORY.with(|history| history.borrow().clone());
+    EVAL_PARAMS.with(|params| worker::review(&Board::new(), &moves, &params.borrow()));
+}
+
+// Create an element with some text in it, and append it to `parent`.
+fn append_text(parent: &Element, tag: &str, text: &str) -> Element {
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    let element = document
+        .create_element(tag)
+        .expect("failed to create element");
+    element.set_text_content(Some(text));
+    parent
+        .append_child(&element)
+        .expect("failed to append child");
+    element
+}
+
+// Fill the review panel with the review of the game, and show it.
+pub fn show_review(review: &Review) {
+    show_status(None);
+    let window = window().expect("no global `window` exists");
+    let document = window.document().expect("should have a document on window");
+    let panel = match document.get_element_by_id("review") {
+        Some(panel) => panel,
+        None => return,
+    };
+
+    if let Some(summary) = document.get_element_by_id("review-summary") {
+        summary.set_text_content(None);
+        let header = append_text(&summary, "tr", "");
+        for title in ["", "Accuracy", "Avg. loss", "Inaccuracies", "Mistakes", "Blunders"] {
+            append_text(&header, "th", title);
+        }
+        for (color, name) in [(Color::White, "White"), (Color::Black, "Black")] {
+            let row = append_text(&summary, "tr", "");
+            append_text(&row, "th", name);
+            append_text(&row, "td", &format!("{:.1}%", review.get_accuracy(color)));
+            append_text(&row, "td", &format!("{:.0}", review.get_average_loss(color)));
+            for classification in Classification::ALL.iter().filter(|c| c.is_error()) {
+                append_text(&row, "td", &review.count(color, *classification).to_string());
+            }
+        }
+    }
+
+    if let Some(errors) = document.get_element_by_id("review-errors") {
+        errors.set_text_content(None);
+        for (i, m) in review.get_moves().iter().enumerate() {
+            let classification = m.get_classification();
+            if !classification.is_error() {
+                continue;
+            }
+            let number = review.get_move_number(i);
+            let dots = if m.get_color() == Color::White { "." } else { "..." };
+            let mut text = format!(
+                "{}{} {}: {}",
+                number,
+                dots,
+                m.get_san(),
+                classification.get_name()
+            );
+            if let Some(better) = m.get_better_san() {
+                text += &format!(", {} was best", better);
+            }
+            let item = append_text(&errors, "li", &text);
+            item.class_list()
+                .add_1(&classification.get_name().to_lowercase())
+                .expect("failed to add class");
+        }
+    }
+
+    if let Some(link) = document.get_element_by_id("review-pgn") {
+        let result = GAME_RESULT.with(|game_result| game_result.get()).unwrap_or("*");
+        let black = format!(
+            "Computer (level {})",
+            DIFFICULTY.with(|difficulty| difficulty.get().get_level())
+        );
+        let tags = [("White", "Player"), ("Black", black.as_str())];
+        let pgn = review.to_pgn(&tags, result);
+        let href = format!(
+            "data:application/x-chess-pgn;charset=utf-8,{}",
+            String::from(js_sys::encode_uri_component(&pgn))
+        );
+        link.set_attribute("href", &href)
+            .expect("failed to set the link to the PGN");
+    }
+
+    panel
+        .class_list()
+        .remove_1("hidden")
+        .expect("failed to show the review panel");
+}
+
+// Load puzzles from the text of a CSV file in the format of the Lichess
+// puzzle database, and get the number of puzzles loaded. Puzzles that can't
+// be read are skipped with a warning.
+#[wasm_bindgen]
+pub fn load_puzzles(text: &str) -> Result<usize, JsValue> {
+    let (puzzles, errors) = puzzle::parse_puzzles(text);
+    for e in &errors {
+        web_sys::console::warn_1(&format!("Skipped puzzle at {}", e).into());
+    }
+    if puzzles.is_empty() {
+        return Err(JsValue::from_str("no puzzles in the file"));
+    }
+    let count = puzzles.len();
+    PUZZLES.with(|current| *current.borrow_mut() = puzzles);
+    log!("Loaded {} puzzles", count);
+    Ok(count)
+}
+
+#[wasm_bindgen]
+pub fn set_puzzle_rating(rating: f64) {
+    PUZZLE_RATING.with(|current| current.set(rating));
+}
+
+#[wasm_bindgen]
+pub fn get_puzzle_rating() -> f64 {
+    PUZZLE_RATING.with(|current| current.get())
+}
+
+// Wait for the player to select a move on the board, showing the squares
+// the selected piece can move to. Returns `None` if they select a square
+// without one of their pieces first.
+async fn select_move(board: &Board) -> Option<Move> {
+    let from = get_selected_square().await.ok()?;
+    if board
+        .get_piece(from)
+        .is_none_or(|piece| piece.get_color() != board.get_turn_color())
+    {
+        return None;
+    }
+    update_hint_squares(get_hint_pos(board, from), "hint");
+    let to = get_selected_square().await.ok()?;
+    update_board(board);
+    Some(get_selected_move(board, from, to))
+}
+
+// Show a puzzle's board, with the last move played on it.
+fn show_puzzle_board(board: &Board, last_move: Option<(Board, Move)>) {
+    update_board(board);
+    if let Some((before, m)) = last_move {
+        update_hint_squares(get_move_squares(&before, m), "last-move");
+    }
+}
+
+// Play a puzzle picked close to the player's puzzle rating, ending the game
+// in progress. The opponent plays the setup move, and the player has to
+// find the moves of the solution, or any move that checkmates. Returns the
+// player's puzzle rating once the puzzle is over.
+#[wasm_bindgen]
+pub async fn play_puzzle() -> Result<f64, JsValue> {
+    let rating = get_puzzle_rating();
+    let puzzle = PUZZLES
+        .with(|puzzles| puzzle::pick_puzzle(&puzzles.borrow(), rating).cloned())
+        .ok_or_else(|| JsValue::from_str("no puzzles loaded"))?;
+
+    // Leave the game for the puzzles.
+    GAME_OVER.with(|game_over| game_over.set(true));
+    PLAYER_BOARD.with(|player_board| player_board.set(None));
+    worker::stop_analysis();
+    show_draw_offer(false);
+    let count = PUZZLE_COUNT.with(|count| {
+        count.set(count.get() + 1);
+        count.get()
+    });
+    let is_current = || PUZZLE_COUNT.with(|current| current.get()) == count;
+
+    let mut attempt = PuzzleAttempt::new(puzzle.clone());
+    show_puzzle_board(
+        &attempt.get_board(),
+        Some((puzzle.get_board(), puzzle.get_moves()[0])),
+    );
+    let prompt = format!(
+        "Puzzle {} ({}): {} to move",
+        puzzle.get_id(),
+        puzzle.get_rating(),
+        puzzle.get_color()
+    );
+    show_status(Some(&prompt));
+
+    let solved = loop {
+        let board = attempt.get_board();
+        let m = select_move(&board).await;
+        // Another puzzle was started in the meantime.
+        if !is_current() {
+            return Ok(get_puzzle_rating());
+        }
+        let m = match m {
+            Some(m) => m,
+            None => {
+                update_board(&board);
+                continue;
+            }
+        };
+
+        match attempt.play(m) {
+            Ok(Step::Correct(reply)) => {
+                show_puzzle_board(
+                    &attempt.get_board(),
+                    Some((board.apply_eval_move(m), reply)),
+                );
+                show_status(Some(&format!("{} is right. {}", board.get_san(m), prompt)));
+            }
+            Ok(Step::Solved) => {
+                show_puzzle_board(&attempt.get_board(), Some((board, m)));
+                show_status(Some(&format!("{} solves the puzzle!", board.get_san(m))));
+                break true;
+            }
+            Ok(Step::Wrong(expected)) => {
+                update_board(&board);
+                update_hint_squares(get_move_squares(&board, expected), "hint-move");
+                show_status(Some(&format!(
+                    "{} is wrong. The solution was {}.",
+                    board.get_san(m),
+                    board.get_san(expected)
+                )));
+                break false;
+            }
+            // Illegal moves don't count.
+            Err(_) => update_board(&board),
+        }
+    };
+
+    let new_rating = puzzle::update_rating(rating, puzzle.get_rating(), solved);
+    set_puzzle_rating(new_rating);
+    Ok(new_rating)
+}
+
 // Render loop function
 pub fn render_loop(board: Rc<RefCell<Board>>) {
     let mut board_clone = Rc::clone(&board);
 
     if board.borrow().get_turn_color() == PLAYERCOLOR {
+        PLAYER_BOARD.with(|player_board| player_board.set(Some(*board.borrow())));
         // Get the first selected square
         let first_selected_square_future = get_selected_square();
         wasm_bindgen_futures::spawn_local(async move {
             let first_selected_square = first_selected_square_future.await;
+            // The game can end by a draw agreement while the player thinks.
+            if GAME_OVER.with(|game_over| game_over.get()) {
+                return;
+            }
             match first_selected_square {
                 Ok(first_square) => {
                     let from: Position = first_square;
@@ -305,12 +862,15 @@ pub fn render_loop(board: Rc<RefCell<Board>>) {
                     let hint_positions = get_hint_pos(&board.borrow(), from);
                     if !hint_positions.is_empty() {
                         // Check that the selected square has a piece
-                        update_hint_squares(hint_positions);
+                        update_hint_squares(hint_positions, "hint");
                     }
 
                     // Wait for the user to select the second square
                     let second_selected_square: Result<Position, &'static str> =
                         get_selected_square().await;
+                    if GAME_OVER.with(|game_over| game_over.get()) {
+                        return;
+                    }
                     match second_selected_square {
                         Ok(second_square) => {
                             // Do something with the second selected square
@@ -319,38 +879,36 @@ pub fn render_loop(board: Rc<RefCell<Board>>) {
 
                             let to = second_square;
 
-                            // Need to update the promotion feature
-                            let m = match board.borrow().get_piece(from){
-                                // Need to update promotion feature
-                                Some(Piece::Pawn(_, _)) => Move::Piece(from, to),
-                                Some(Piece::King(_, _)) => {
-                                    // Regular move
-                                    if to.is_adjacent_to(from) {
-                                        Move::Piece(from, to)
-                                    } else {
-                                        if to.get_col() > from.get_col() {
-                                            Move::KingSideCastle
-                                        } else {
-                                            Move::QueenSideCastle
-                                        }
-                                    }
-                                }
-                                _ => Move::Piece(from, to),
-                            };
+                            let m = get_selected_move(&board.borrow(), from, to);
                             // Perform game logic based on the selected squares
-                            match board.borrow_mut().play_move(m) {
+                            let result = board.borrow_mut().play_move(m);
+                            if !matches!(result, GameResult::IllegalMove(_)) {
+                                PLAYER_BOARD.with(|player_board| player_board.set(None));
+                                HISTORY.with(|history| history.borrow_mut().push(m));
+                            }
+                            match result {
                                 GameResult::Continuing(next_board) => {
                                     log!("Continuing");
                                     board_clone = Rc::new(RefCell::new(next_board));
+                                    // Moving declines the computer's draw offer.
+                                    show_draw_offer(false);
+                                    show_status(None);
+                                    EVAL_PARAMS.with(|params| {
+                                        worker::analyze(&next_board, &params.borrow())
+                                    });
                                 }
-                                GameResult::Victory(next_board, _) => {
+                                GameResult::Victory(next_board, winner) => {
                                     log!("You won the game!");
+                                    worker::stop_analysis();
+                                    finish_game(get_victory_result(winner));
                                     board_clone = Rc::new(RefCell::new(next_board));
                                     update_board(&board_clone.borrow());
                                     return;
                                 }
                                 GameResult::Stalemate => {
                                     log!("Drawn Game");
+                                    worker::stop_analysis();
+                                    finish_game("1/2-1/2");
                                     return;
                                 }
                                 GameResult::IllegalMove(_) => {
@@ -372,31 +930,71 @@ pub fn render_loop(board: Rc<RefCell<Board>>) {
             render_loop(Rc::clone(&board_clone));
         });
     } else {
-        // Computer makes decisions
-        let m = get_next_move(&board.borrow(), true);
-
-        match board.borrow_mut().play_move(m) {
-            GameResult::Continuing(next_board) => {
-                log!("Continuing");
-                board_clone = Rc::new(RefCell::new(next_board));
-                update_board(&board_clone.borrow());
-            }
-            GameResult::Victory(next_board, _) => {
-                log!("You lost the game!");
-                board_clone = Rc::new(RefCell::new(next_board));
-                update_board(&board_clone.borrow());
+        // Computer makes decisions, searching in the worker while the
+        // page keeps running
+        let difficulty = DIFFICULTY.with(|difficulty| difficulty.get());
+        let params = EVAL_PARAMS.with(|params| params.borrow().clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            let current_board = *board.borrow();
+            let (mut m, value) = worker::get_move(&current_board, difficulty, &params).await;
+            // The game can end while the computer thinks, like when the
+            // player starts solving puzzles.
+            if GAME_OVER.with(|game_over| game_over.get()) {
                 return;
             }
-            GameResult::Stalemate => {
-                log!("Drawm game");
-                update_board(&board_clone.borrow());
-                return;
+            // Book moves come without a value, and don't change the
+            // computer's mind about the game.
+            let decision = value.map_or(Decision::Play, |value| {
+                ADJUDICATOR.with(|adjudicator| adjudicator.borrow_mut().update(value, &params))
+            });
+            if decision == Decision::Resign {
+                m = Move::Resign;
             }
-            GameResult::IllegalMove(_) => {
-                log!("IllegalMove");
+
+            let result = board.borrow_mut().play_move(m);
+            if m != Move::Resign && !matches!(result, GameResult::IllegalMove(_)) {
+                HISTORY.with(|history| history.borrow_mut().push(m));
+            }
+            match result {
+                GameResult::Continuing(next_board) => {
+                    log!("Continuing");
+                    board_clone = Rc::new(RefCell::new(next_board));
+                    update_board(&board_clone.borrow());
+                    worker::analyze(&next_board, &params);
+                    if decision == Decision::OfferDraw {
+                        show_draw_offer(true);
+                        show_status(Some("The computer offers a draw"));
+                    }
+                    if PONDER.with(|ponder| ponder.get()) {
+                        worker::ponder(&next_board, difficulty, &params);
+                    }
+                }
+                GameResult::Victory(next_board, winner) => {
+                    worker::stop_analysis();
+                    finish_game(get_victory_result(winner));
+                    if winner == PLAYERCOLOR {
+                        log!("The computer resigns, you won the game!");
+                        show_status(Some("The computer resigns"));
+                    } else {
+                        log!("You lost the game!");
+                    }
+                    board_clone = Rc::new(RefCell::new(next_board));
+                    update_board(&board_clone.borrow());
+                    return;
+                }
+                GameResult::Stalemate => {
+                    log!("Drawm game");
+                    worker::stop_analysis();
+                    finish_game("1/2-1/2");
+                    update_board(&board_clone.borrow());
+                    return;
+                }
+                GameResult::IllegalMove(_) => {
+                    log!("IllegalMove");
+                }
             }
-        }
 
-        render_loop(Rc::clone(&board_clone));
+            render_loop(Rc::clone(&board_clone));
+        });
     }
 }
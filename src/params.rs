@@ -0,0 +1,592 @@
+use crate::piece::{Color, Piece};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// A table of weights for every square of the board, seen from white's side:
+// the first row is the eighth rank, and the first column the A file. Black
+// uses the same tables flipped upside down.
+pub type Table = [[f64; 8]; 8];
+
+// A value for each kind of piece.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PieceValues<T> {
+    pub king: T,
+    pub queen: T,
+    pub rook: T,
+    pub bishop: T,
+    pub knight: T,
+    pub pawn: T,
+}
+
+impl<T: Copy> PieceValues<T> {
+    #[inline]
+    pub fn get(&self, piece: Piece) -> T {
+        match piece {
+            Piece::King(_, _) => self.king,
+            Piece::Queen(_, _) => self.queen,
+            Piece::Rook(_, _) => self.rook,
+            Piece::Bishop(_, _) => self.bishop,
+            Piece::Knight(_, _) => self.knight,
+            Piece::Pawn(_, _) => self.pawn,
+        }
+    }
+}
+
+// The weights of the positional terms of the evaluation, see `eval::Term`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    pub doubled_pawn: f64,
+    pub isolated_pawn: f64,
+    pub backward_pawn: f64,
+    // Indexed by how many rows the passed pawn has advanced from its own
+    // back rank.
+    pub passed_pawn: [f64; 8],
+    // Per square a knight, bishop, rook or queen can move to.
+    pub mobility: f64,
+    pub bishop_pair: f64,
+    pub rook_open_file: f64,
+    pub rook_half_open_file: f64,
+    // Per pawn in front of the king. Scaled down as pieces get traded.
+    pub pawn_shield: f64,
+    // Per enemy piece attacking the squares around the king. Scaled down
+    // as pieces get traded.
+    pub king_attacker: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            doubled_pawn: -1.5,
+            isolated_pawn: -1.5,
+            backward_pawn: -1.0,
+            passed_pawn: [0.0, 0.5, 1.0, 1.5, 2.5, 4.0, 6.0, 0.0],
+            mobility: 0.1,
+            bishop_pair: 5.0,
+            rook_open_file: 2.0,
+            rook_half_open_file: 1.0,
+            pawn_shield: 1.0,
+            king_attacker: -1.5,
+        }
+    }
+}
+
+// Every number used to evaluate a board.
+//
+// The defaults are the engine's own values. Other sets can be loaded from a
+// TOML or JSON file, to play with a different personality or to try out
+// tuned values without recompiling. Any section left out of the file keeps
+// its default value.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EvalParams {
+    // What the material values are multiplied by. The piece-square tables
+    // and positional weights are in the scaled units, so a pawn is worth
+    // 10 by default.
+    material_scale: f64,
+    material: PieceValues<f64>,
+    middlegame: PieceValues<Table>,
+    endgame: PieceValues<Table>,
+    weights: Weights,
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            material_scale: 10.0,
+            material: PieceValues {
+                king: 9999.0,
+                queen: 9.0,
+                rook: 5.0,
+                bishop: 3.0,
+                knight: 3.0,
+                pawn: 1.0,
+            },
+            middlegame: PieceValues {
+                king: KING_MIDDLEGAME_WEIGHTS,
+                queen: QUEEN_MIDDLEGAME_WEIGHTS,
+                rook: ROOK_MIDDLEGAME_WEIGHTS,
+                bishop: BISHOP_MIDDLEGAME_WEIGHTS,
+                knight: KNIGHT_MIDDLEGAME_WEIGHTS,
+                pawn: PAWN_MIDDLEGAME_WEIGHTS,
+            },
+            endgame: PieceValues {
+                king: KING_ENDGAME_WEIGHTS,
+                queen: QUEEN_ENDGAME_WEIGHTS,
+                rook: ROOK_ENDGAME_WEIGHTS,
+                bishop: BISHOP_ENDGAME_WEIGHTS,
+                knight: KNIGHT_ENDGAME_WEIGHTS,
+                pawn: PAWN_ENDGAME_WEIGHTS,
+            },
+            weights: Weights::default(),
+        }
+    }
+}
+
+impl EvalParams {
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        toml::from_str(text).map_err(|e| format!("invalid evaluation parameters: {}", e))
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        serde_json::from_str(text).map_err(|e| format!("invalid evaluation parameters: {}", e))
+    }
+
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string(self).map_err(|e| format!("could not write evaluation parameters: {}", e))
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| format!("could not write evaluation parameters: {}", e))
+    }
+
+    // Read parameters from a `.toml` or `.json` file.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("could not read `{}`: {}", path.display(), e))?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&text),
+            Some("json") => Self::from_json(&text),
+            _ => Err(format!(
+                "unknown evaluation parameters format `{}`, expected `.toml` or `.json`",
+                path.display()
+            )),
+        }
+    }
+
+    // Write the parameters to a `.toml` or `.json` file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        let path = path.as_ref();
+        let text = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => self.to_toml()?,
+            Some("json") => self.to_json()?,
+            _ => {
+                return Err(format!(
+                    "unknown evaluation parameters format `{}`, expected `.toml` or `.json`",
+                    path.display()
+                ))
+            }
+        };
+        std::fs::write(path, text)
+            .map_err(|e| format!("could not write `{}`: {}", path.display(), e))
+    }
+
+    #[inline]
+    pub fn get_material_scale(&self) -> f64 {
+        self.material_scale
+    }
+
+    #[inline]
+    pub fn get_material(&self) -> &PieceValues<f64> {
+        &self.material
+    }
+
+    #[inline]
+    pub fn get_middlegame(&self) -> &PieceValues<Table> {
+        &self.middlegame
+    }
+
+    #[inline]
+    pub fn get_endgame(&self) -> &PieceValues<Table> {
+        &self.endgame
+    }
+
+    #[inline]
+    pub fn get_weights(&self) -> &Weights {
+        &self.weights
+    }
+
+    // Get every number that can be tuned as a flat list. The material scale
+    // and the value of the king are left out, since changing them does not
+    // change which side is better.
+    pub fn get_values(&self) -> Vec<f64> {
+        self.clone().values_mut().into_iter().map(|value| *value).collect()
+    }
+
+    // Set every number that can be tuned from a list made by `get_values`.
+    pub fn set_values(&mut self, values: &[f64]) {
+        for (value, new_value) in self.values_mut().into_iter().zip(values) {
+            *value = *new_value;
+        }
+    }
+
+    fn values_mut(&mut self) -> Vec<&mut f64> {
+        let mut result = vec![
+            &mut self.material.queen,
+            &mut self.material.rook,
+            &mut self.material.bishop,
+            &mut self.material.knight,
+            &mut self.material.pawn,
+        ];
+        for tables in [&mut self.middlegame, &mut self.endgame] {
+            for table in [
+                &mut tables.king,
+                &mut tables.queen,
+                &mut tables.rook,
+                &mut tables.bishop,
+                &mut tables.knight,
+                &mut tables.pawn,
+            ] {
+                result.extend(table.iter_mut().flatten());
+            }
+        }
+
+        let weights = &mut self.weights;
+        result.extend(vec![
+            &mut weights.doubled_pawn,
+            &mut weights.isolated_pawn,
+            &mut weights.backward_pawn,
+            &mut weights.mobility,
+            &mut weights.bishop_pair,
+            &mut weights.rook_open_file,
+            &mut weights.rook_half_open_file,
+            &mut weights.pawn_shield,
+            &mut weights.king_attacker,
+        ]);
+        result.extend(weights.passed_pawn.iter_mut());
+        result
+    }
+
+    // Get the scaled material value of a piece.
+    #[inline]
+    pub fn get_material_value(&self, piece: Piece) -> f64 {
+        self.material.get(piece) * self.material_scale
+    }
+
+    #[inline]
+    pub fn get_middlegame_weight(&self, piece: Piece) -> f64 {
+        lookup(&self.middlegame.get(piece), piece)
+    }
+
+    #[inline]
+    pub fn get_endgame_weight(&self, piece: Piece) -> f64 {
+        lookup(&self.endgame.get(piece), piece)
+    }
+}
+
+// Look up the weight of the square a piece stands on. The tables are written
+// for white, so black pieces read them mirrored from the other side.
+#[inline]
+fn lookup(table: &Table, piece: Piece) -> f64 {
+    let pos = piece.get_pos();
+    let row = match piece.get_color() {
+        Color::White => 7 - pos.get_row(),
+        Color::Black => pos.get_row(),
+    };
+    table[row as usize][pos.get_col() as usize]
+}
+
+const KING_MIDDLEGAME_WEIGHTS: Table = [
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
+    [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
+    [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
+    [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
+];
+
+const QUEEN_MIDDLEGAME_WEIGHTS: Table = [
+    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
+    [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
+    [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
+];
+
+const ROOK_MIDDLEGAME_WEIGHTS: Table = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+    [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
+];
+
+const BISHOP_MIDDLEGAME_WEIGHTS: Table = [
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+    [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
+    [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
+    [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
+    [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+];
+
+const KNIGHT_MIDDLEGAME_WEIGHTS: Table = [
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+    [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
+    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+    [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
+    [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+];
+
+const PAWN_MIDDLEGAME_WEIGHTS: Table = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
+    [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
+    [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
+    [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
+    [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
+    [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+// In the endgame the king should walk up to the center to support its
+// pawns, and passed pawns become worth a lot more the closer they get
+// to promotion.
+const KING_ENDGAME_WEIGHTS: Table = [
+    [-5.0, -4.0, -3.0, -2.0, -2.0, -3.0, -4.0, -5.0],
+    [-3.0, -2.0, -1.0, 0.0, 0.0, -1.0, -2.0, -3.0],
+    [-3.0, -1.0, 2.0, 3.0, 3.0, 2.0, -1.0, -3.0],
+    [-3.0, -1.0, 3.0, 4.0, 4.0, 3.0, -1.0, -3.0],
+    [-3.0, -1.0, 3.0, 4.0, 4.0, 3.0, -1.0, -3.0],
+    [-3.0, -1.0, 2.0, 3.0, 3.0, 2.0, -1.0, -3.0],
+    [-3.0, -3.0, 0.0, 0.0, 0.0, 0.0, -3.0, -3.0],
+    [-5.0, -3.0, -3.0, -3.0, -3.0, -3.0, -3.0, -5.0],
+];
+
+const QUEEN_ENDGAME_WEIGHTS: Table = [
+    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-0.5, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -0.5],
+    [-0.5, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -0.5],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+];
+
+const ROOK_ENDGAME_WEIGHTS: Table = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+const BISHOP_ENDGAME_WEIGHTS: Table = [
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+];
+
+const KNIGHT_ENDGAME_WEIGHTS: Table = [
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+];
+
+const PAWN_ENDGAME_WEIGHTS: Table = [
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [8.0, 8.0, 8.0, 8.0, 8.0, 8.0, 8.0, 8.0],
+    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
+    [3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0, 3.0],
+    [1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5, 1.5],
+    [0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::Position;
+
+    // The tables black had of its own before it read white's mirrored. Like
+    // white's, their first row is the eighth rank.
+    const BLACK_KING: Table = [
+        [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
+        [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
+        [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
+        [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
+        [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+        [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+        [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+        [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
+    ];
+
+    const BLACK_QUEEN: Table = [
+        [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
+        [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
+        [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+        [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
+        [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
+        [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
+        [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+        [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
+    ];
+
+    const BLACK_ROOK: Table = [
+        [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
+        [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+        [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+        [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+        [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+        [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
+        [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    ];
+
+    const BLACK_BISHOP: Table = [
+        [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+        [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
+        [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
+        [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
+        [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
+        [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
+        [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
+        [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
+    ];
+
+    const BLACK_KNIGHT: Table = [
+        [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+        [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
+        [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
+        [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
+        [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
+        [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
+        [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
+        [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
+    ];
+
+    const BLACK_PAWN: Table = [
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
+        [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
+        [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
+        [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
+        [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
+        [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
+        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    ];
+
+    #[test]
+    fn toml_and_json_round_trip() {
+        let params = EvalParams::default();
+        assert_eq!(
+            EvalParams::from_toml(&params.to_toml().unwrap()),
+            Ok(params.clone())
+        );
+        assert_eq!(
+            EvalParams::from_json(&params.to_json().unwrap()),
+            Ok(params)
+        );
+    }
+
+    #[test]
+    fn missing_fields_keep_their_defaults() {
+        let params = EvalParams::from_toml(
+            "material_scale = 20.0\n\
+             \n\
+             [weights]\n\
+             mobility = 0.25\n",
+        )
+        .unwrap();
+        let expected = EvalParams {
+            material_scale: 20.0,
+            weights: Weights {
+                mobility: 0.25,
+                ..Weights::default()
+            },
+            ..EvalParams::default()
+        };
+        assert_eq!(params, expected);
+
+        let params = EvalParams::from_json(r#"{ "weights": { "bishop_pair": 3.0 } }"#).unwrap();
+        let expected = EvalParams {
+            weights: Weights {
+                bishop_pair: 3.0,
+                ..Weights::default()
+            },
+            ..EvalParams::default()
+        };
+        assert_eq!(params, expected);
+
+        assert_eq!(EvalParams::from_toml(""), Ok(EvalParams::default()));
+        assert_eq!(EvalParams::from_json("{}"), Ok(EvalParams::default()));
+    }
+
+    #[test]
+    fn malformed_input_is_rejected() {
+        assert!(EvalParams::from_toml("material_scale = ").is_err());
+        assert!(EvalParams::from_toml("material_scale = \"ten\"").is_err());
+        assert!(EvalParams::from_toml("[weights]\npassed_pawn = [1.0, 2.0]").is_err());
+        assert!(EvalParams::from_json("{ \"material_scale\": 10.0").is_err());
+        assert!(EvalParams::from_json("{ \"material_scale\": \"ten\" }").is_err());
+        assert!(EvalParams::from_json("{ \"weights\": { \"passed_pawn\": [1.0] } }").is_err());
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let params = EvalParams::default();
+        let values = params.get_values();
+        // Five material values, two sets of six tables and the weights.
+        assert_eq!(values.len(), 5 + 2 * 6 * 64 + 9 + 8);
+
+        let mut changed = params.clone();
+        let new_values: Vec<f64> = (0..values.len()).map(|i| i as f64).collect();
+        changed.set_values(&new_values);
+        assert_eq!(changed.get_values(), new_values);
+        assert_ne!(changed, params);
+        assert_eq!(changed.get_material_scale(), params.get_material_scale());
+        assert_eq!(changed.get_material().king, params.get_material().king);
+
+        changed.set_values(&values);
+        assert_eq!(changed, params);
+    }
+
+    #[test]
+    fn black_reads_the_mirrored_tables() {
+        let params = EvalParams::default();
+        let black = PieceValues {
+            king: BLACK_KING,
+            queen: BLACK_QUEEN,
+            rook: BLACK_ROOK,
+            bishop: BLACK_BISHOP,
+            knight: BLACK_KNIGHT,
+            pawn: BLACK_PAWN,
+        };
+        for row in 0..8 {
+            for col in 0..8 {
+                let pos = Position::new(row, col);
+                for piece in [
+                    Piece::King(Color::Black, pos),
+                    Piece::Queen(Color::Black, pos),
+                    Piece::Rook(Color::Black, pos),
+                    Piece::Bishop(Color::Black, pos),
+                    Piece::Knight(Color::Black, pos),
+                    Piece::Pawn(Color::Black, pos),
+                ] {
+                    let expected = black.get(piece)[(7 - row) as usize][col as usize];
+                    assert_eq!(params.get_middlegame_weight(piece), expected);
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{board::Board, game::Move};
+use crate::{board::Board, game::Move, params::EvalParams};
 use core::convert::TryFrom;
 use wasm_bindgen::prelude::*;
 
@@ -299,6 +299,7 @@ impl Position {
     // position, moving diagonally.
     //
     // This does _not_ include the `from` position, and includes the `to` position.
+    #[allow(clippy::needless_late_init)]
     pub fn diagonals_to(&self, to: Self) -> Vec<Self> {
         if !self.is_diagonal_to(to) {
             return Vec::new();
@@ -410,7 +411,7 @@ impl Piece {
     }
 
     #[inline]
-    pub fn get_type(&self) -> &str {
+    pub fn get_type(&self) -> &'static str {
         match self {
             Self::King(_, _) => "K",
             Self::Queen(_, _) => "Q",
@@ -433,44 +434,42 @@ impl Piece {
         }
     }
 
+    // Get how much a piece counts towards the game phase. Only minor and
+    // major pieces count, so that the phase follows trades of pieces rather
+    // than pawns.
+    #[inline]
+    pub fn get_phase_value(&self) -> i32 {
+        match self {
+            Self::Queen(_, _) => 4,
+            Self::Rook(_, _) => 2,
+            Self::Bishop(_, _) | Self::Knight(_, _) => 1,
+            Self::King(_, _) | Self::Pawn(_, _) => 0,
+        }
+    }
+
     // Get the weighted value of a piece. This simply factors in position
-    // to the pieces value. For example, a knight that is in the center is
-    // more favorable than a knight on the side of the board. Similarly,
-    // a king in the center of the board is highly unfavorable compared to
-    // a king its respective side.
+    // to the pieces value, see `get_position_weight`.
     //
-    // Additionally, the weighted value of the piece is 10 times greater than
-    // its material value, plus or minus a weight ranging between 5.0 and -5.0.
+    // With the default parameters, the weighted value of the piece is 10
+    // times greater than its material value, plus or minus a weight ranging
+    // between 8.0 and -5.0.
     #[inline]
-    pub fn get_weighted_value(&self) -> f64 {
-        let weights = match self {
-            Self::King(c, _) => match c {
-                Color::White => WHITE_KING_POSITION_WEIGHTS,
-                Color::Black => BLACK_KING_POSITION_WEIGHTS,
-            },
-            Self::Queen(c, _) => match c {
-                Color::White => WHITE_QUEEN_POSITION_WEIGHTS,
-                Color::Black => BLACK_QUEEN_POSITION_WEIGHTS,
-            },
-            Self::Rook(c, _) => match c {
-                Color::White => WHITE_ROOK_POSITION_WEIGHTS,
-                Color::Black => BLACK_ROOK_POSITION_WEIGHTS,
-            },
-            Self::Bishop(c, _) => match c {
-                Color::White => WHITE_BISHOP_POSITION_WEIGHTS,
-                Color::Black => BLACK_BISHOP_POSITION_WEIGHTS,
-            },
-            Self::Knight(c, _) => match c {
-                Color::White => WHITE_KNIGHT_POSITION_WEIGHTS,
-                Color::Black => BLACK_KNIGHT_POSITION_WEIGHTS,
-            },
-            Self::Pawn(c, _) => match c {
-                Color::White => WHITE_PAWN_POSITION_WEIGHTS,
-                Color::Black => BLACK_PAWN_POSITION_WEIGHTS,
-            },
-        };
-        weights[(7 - self.get_pos().get_row()) as usize][self.get_pos().get_col() as usize]
-            + (self.get_material_value() * 10) as f64
+    pub fn get_weighted_value(&self, phase: f64, params: &EvalParams) -> f64 {
+        self.get_position_weight(phase, params) + params.get_material_value(*self)
+    }
+
+    // Get the bonus or penalty for the square a piece stands on. For example,
+    // a knight that is in the center is more favorable than a knight on the
+    // side of the board. Similarly, a king in the center of the board is
+    // highly unfavorable compared to a king its respective side, at least
+    // until most pieces are traded.
+    //
+    // Each piece has a middlegame and an endgame table of weights, which
+    // are blended using `phase`: 1.0 uses only the middlegame weights,
+    // 0.0 only the endgame ones. See `Board::get_game_phase`.
+    #[inline]
+    pub fn get_position_weight(&self, phase: f64, params: &EvalParams) -> f64 {
+        params.get_middlegame_weight(*self) * phase + params.get_endgame_weight(*self) * (1.0 - phase)
     }
 
     #[inline]
@@ -584,6 +583,25 @@ impl Piece {
 
     #[inline]
     pub(crate) fn get_legal_moves(&self, board: &Board) -> Vec<Move> {
+        let color = self.get_color();
+        self.get_candidate_moves(board)
+            .into_iter()
+            .filter(|x| match x {
+                Move::Piece(from, to) => {
+                    if from.is_on_board() && to.is_on_board() {
+                        board.is_legal_move(*x, color)
+                    } else {
+                        false
+                    }
+                }
+                _ => board.is_legal_move(*x, color),
+            })
+            .collect::<Vec<Move>>()
+    }
+
+    // Get the moves the piece could make, before checking that they are
+    // legal.
+    pub(crate) fn get_candidate_moves(&self, board: &Board) -> Vec<Move> {
         let mut result = Vec::new();
         match *self {
             Self::Pawn(ally_color, pos) => {
@@ -606,16 +624,32 @@ impl Piece {
                     result.push(Move::Piece(pos, next_up))
                 }
 
+                // A pawn reaching the last row is promoted to any of these.
+                let mut push = |to: Position| {
+                    if to.get_row() == 0 || to.get_row() == 7 {
+                        for piece in [
+                            Self::Queen(ally_color, to),
+                            Self::Rook(ally_color, to),
+                            Self::Bishop(ally_color, to),
+                            Self::Knight(ally_color, to),
+                        ] {
+                            result.push(Move::Promotion(pos, to, piece))
+                        }
+                    } else {
+                        result.push(Move::Piece(pos, to))
+                    }
+                };
+
                 if up.is_on_board() && board.has_no_piece(up) {
-                    result.push(Move::Piece(pos, up))
+                    push(up)
                 }
 
                 if up_left.is_on_board() && board.has_enemy_piece(up_left, ally_color) {
-                    result.push(Move::Piece(pos, up_left))
+                    push(up_left)
                 }
 
                 if up_right.is_on_board() && board.has_enemy_piece(up_right, ally_color) {
-                    result.push(Move::Piece(pos, up_right))
+                    push(up_right)
                 }
             }
 
@@ -636,7 +670,8 @@ impl Piece {
                 }
                 if board.can_kingside_castle(ally_color) {
                     result.push(Move::KingSideCastle);
-                } else if board.can_queenside_castle(ally_color) {
+                }
+                if board.can_queenside_castle(ally_color) {
                     result.push(Move::QueenSideCastle);
                 }
             }
@@ -727,20 +762,7 @@ impl Piece {
             }
         }
 
-        let color = self.get_color();
         result
-            .into_iter()
-            .filter(|x| match x {
-                Move::Piece(from, to) => {
-                    if from.is_on_board() && to.is_on_board() {
-                        board.is_legal_move(*x, color)
-                    } else {
-                        false
-                    }
-                }
-                _ => board.is_legal_move(*x, color),
-            })
-            .collect::<Vec<Move>>()
     }
 
     // Verify that moving to a new position is a legal move.
@@ -914,134 +936,3 @@ impl Piece {
         }
     }
 }
-
-const WHITE_KING_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
-    [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
-    [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
-    [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
-];
-
-const BLACK_KING_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [2.0, 3.0, 1.0, 0.0, 0.0, 1.0, 3.0, 2.0],
-    [2.0, 2.0, 0.0, 0.0, 0.0, 0.0, 2.0, 2.0],
-    [-1.0, -2.0, -2.0, -2.0, -2.0, -2.0, -2.0, -1.0],
-    [-2.0, -3.0, -3.0, -4.0, -4.0, -3.0, -3.0, -2.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-    [-3.0, -4.0, -4.0, -5.0, -5.0, -4.0, -4.0, -3.0],
-];
-
-const WHITE_QUEEN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
-];
-const BLACK_QUEEN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-1.0, -0.0, -1.0, -0.5, -0.5, -0.5, -1.0, -2.0],
-    [-1.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, 0.5, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [-0.5, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -0.5],
-    [-1.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, -1.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-2.0, -1.0, -1.0, -0.5, -0.5, -1.0, -1.0, -2.0],
-];
-
-const WHITE_ROOK_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
-];
-
-const BLACK_ROOK_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.5, 0.5, 0.0, 0.0, 0.0],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [-0.5, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -0.5],
-    [0.5, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5],
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-];
-
-const WHITE_BISHOP_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
-    [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
-    [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
-    [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
-    [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-];
-
-const BLACK_BISHOP_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-    [-1.0, 0.5, 0.0, 0.0, 0.0, 0.0, 0.5, -1.0],
-    [-1.0, 1.0, 1.0, 1.0, 1.0, 1.0, 1.0, -1.0],
-    [-1.0, 0.0, 1.0, 1.0, 1.0, 1.0, 0.0, -1.0],
-    [-1.0, 0.5, 0.5, 1.0, 1.0, 0.5, 0.5, -1.0],
-    [-1.0, 0.0, 0.5, 1.0, 1.0, 0.5, 0.0, -1.0],
-    [-1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, -1.0],
-    [-2.0, -1.0, -1.0, -1.0, -1.0, -1.0, -1.0, -2.0],
-];
-
-const WHITE_KNIGHT_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
-    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
-    [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
-    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
-    [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
-    [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-];
-
-const BLACK_KNIGHT_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-    [-4.0, -2.0, 0.0, 0.5, 0.5, 0.0, -2.0, -4.0],
-    [-3.0, 0.5, 1.0, 1.5, 1.5, 1.0, 0.5, -3.0],
-    [-3.0, 0.0, 1.5, 2.0, 2.0, 1.5, 0.0, -3.0],
-    [-3.0, 0.5, 1.5, 2.0, 2.0, 1.5, 0.5, -3.0],
-    [-3.0, 0.0, 1.0, 1.5, 1.5, 1.0, 0.0, -3.0],
-    [-4.0, -2.0, 0.0, 0.0, 0.0, 0.0, -2.0, -4.0],
-    [-5.0, -4.0, -3.0, -3.0, -3.0, -3.0, -4.0, -5.0],
-];
-
-const WHITE_PAWN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
-    [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
-    [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
-    [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
-    [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
-    [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-];
-
-const BLACK_PAWN_POSITION_WEIGHTS: [[f64; 8]; 8] = [
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-    [0.5, 1.5, -1.0, -2.0, -2.0, 1.0, 1.5, 0.5],
-    [0.5, -0.5, -1.0, 0.0, 0.0, -1.0, -0.5, 0.5],
-    [0.0, 0.0, 0.0, 2.0, 2.0, 0.0, 0.0, 0.0],
-    [0.5, 0.5, 1.0, 2.5, 2.5, 1.0, 0.5, 0.5],
-    [1.0, 1.0, 2.0, 3.0, 3.0, 2.0, 1.0, 1.0],
-    [5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0, 5.0],
-    [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-];
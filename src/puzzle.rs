@@ -0,0 +1,368 @@
+use crate::{
+    board::Board,
+    game::{Evaluate, GameResult, Move},
+    piece::Color,
+    utils::Rng,
+};
+use std::path::Path;
+
+// The rating of a player who hasn't solved any puzzles yet.
+pub const DEFAULT_RATING: f64 = 1500.0;
+
+// How much the rating of a player moves after a puzzle, at most.
+const RATING_K: f64 = 32.0;
+
+// Puzzles are picked within this many points of the player's rating, when
+// there are any.
+const RATING_WINDOW: f64 = 200.0;
+
+// A puzzle from a CSV file in the format of the Lichess puzzle database.
+//
+// A line has the fields `PuzzleId,FEN,Moves,Rating,RatingDeviation,
+// Popularity,NbPlays,Themes,GameUrl,OpeningTags`, of which the last ones
+// may be left out. The position is the one before the opponent's move that
+// sets up the puzzle, and the moves, in UCI notation, start with that move
+// and go on with the solution, alternating with the opponent's replies.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Puzzle {
+    id: String,
+    board: Board,
+    moves: Vec<Move>,
+    rating: u32,
+    themes: Vec<String>,
+}
+
+impl Puzzle {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let fields = line.trim().split(',').collect::<Vec<&str>>();
+        if fields.len() < 4 {
+            return Err(format!("invalid puzzle `{}`", line.trim()));
+        }
+
+        let board = Board::from_fen(fields[1])?;
+        let mut moves = vec![];
+        let mut next = board;
+        for uci in fields[2].split_whitespace() {
+            let m = next.parse_uci(uci)?;
+            next = match next.play_move(m) {
+                GameResult::IllegalMove(_) => {
+                    return Err(format!("illegal move `{}` in puzzle `{}`", uci, fields[0]))
+                }
+                _ => next.apply_eval_move(m),
+            };
+            moves.push(m);
+        }
+        // The setup move and at least one move to find.
+        if moves.len() < 2 {
+            return Err(format!("puzzle `{}` has no solution", fields[0]));
+        }
+
+        Ok(Self {
+            id: fields[0].to_string(),
+            board,
+            moves,
+            rating: fields[3]
+                .parse()
+                .map_err(|_| format!("invalid rating `{}`", fields[3]))?,
+            themes: fields
+                .get(7)
+                .map(|themes| themes.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    #[inline]
+    pub fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    // Get the board before the opponent's setup move.
+    #[inline]
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    // Get the setup move followed by the solution.
+    #[inline]
+    pub fn get_moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    #[inline]
+    pub fn get_rating(&self) -> u32 {
+        self.rating
+    }
+
+    #[inline]
+    pub fn get_themes(&self) -> &[String] {
+        &self.themes
+    }
+
+    // Get the color of the player solving the puzzle.
+    #[inline]
+    pub fn get_color(&self) -> Color {
+        !self.board.get_turn_color()
+    }
+}
+
+// Read every puzzle of a CSV file, in text. Empty lines and the header
+// line are skipped. Puzzles that can't be read are skipped too, so that one
+// bad row doesn't lose a whole database, and are given back as errors with
+// the number of their line.
+pub fn parse_puzzles(text: &str) -> (Vec<Puzzle>, Vec<String>) {
+    let mut puzzles = vec![];
+    let mut errors = vec![];
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with("PuzzleId") {
+            continue;
+        }
+        match Puzzle::parse(line) {
+            Ok(puzzle) => puzzles.push(puzzle),
+            Err(e) => errors.push(format!("line {}: {}", i + 1, e)),
+        }
+    }
+    (puzzles, errors)
+}
+
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<(Vec<Puzzle>, Vec<String>), String> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read `{}`: {}", path.display(), e))?;
+    let (puzzles, errors) = parse_puzzles(&text);
+    let errors = errors
+        .into_iter()
+        .map(|e| format!("{}: {}", path.display(), e))
+        .collect();
+    Ok((puzzles, errors))
+}
+
+// Pick a puzzle at random for a player with some rating, among the ones
+// rated close to it, or the closest one if none is.
+pub fn pick_puzzle(puzzles: &[Puzzle], rating: f64) -> Option<&Puzzle> {
+    let distance = |puzzle: &Puzzle| (puzzle.rating as f64 - rating).abs();
+    let close = puzzles
+        .iter()
+        .filter(|puzzle| distance(puzzle) <= RATING_WINDOW)
+        .collect::<Vec<&Puzzle>>();
+    if close.is_empty() {
+        return puzzles
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)));
+    }
+    let i = Rng::from_time().below(close.len() as u64) as usize;
+    Some(close[i])
+}
+
+// Get the rating of a player after they solved or failed a puzzle, like the
+// Elo rating of a game against the puzzle.
+pub fn update_rating(rating: f64, puzzle_rating: u32, solved: bool) -> f64 {
+    let expected = 1.0 / (1.0 + 10f64.powf((puzzle_rating as f64 - rating) / 400.0));
+    let score = if solved { 1.0 } else { 0.0 };
+    rating + RATING_K * (score - expected)
+}
+
+// What happened to a move played on a puzzle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    // The move is the right one, and the opponent replied with a move.
+    Correct(Move),
+    // The move finished the puzzle.
+    Solved,
+    // The move is wrong. This is the one of the solution.
+    Wrong(Move),
+}
+
+// A player's attempt at a puzzle, from the position after the setup move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PuzzleAttempt {
+    puzzle: Puzzle,
+    board: Board,
+    // The index of the next move of the solution.
+    ply: usize,
+    failed: bool,
+}
+
+impl PuzzleAttempt {
+    // Start a puzzle by playing the opponent's setup move.
+    pub fn new(puzzle: Puzzle) -> Self {
+        let board = puzzle.board.apply_eval_move(puzzle.moves[0]);
+        Self {
+            puzzle,
+            board,
+            ply: 1,
+            failed: false,
+        }
+    }
+
+    #[inline]
+    pub fn get_puzzle(&self) -> &Puzzle {
+        &self.puzzle
+    }
+
+    // Get the board the player is to move on.
+    #[inline]
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    // Get the move of the solution the player is expected to play, unless
+    // the puzzle is over.
+    pub fn get_expected_move(&self) -> Option<Move> {
+        if self.failed {
+            return None;
+        }
+        self.puzzle.moves.get(self.ply).copied()
+    }
+
+    #[inline]
+    pub fn is_solved(&self) -> bool {
+        !self.failed && self.ply >= self.puzzle.moves.len()
+    }
+
+    #[inline]
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    // Play the player's move, and the opponent's reply if it is the right
+    // one. Besides the move of the solution, any move that checkmates is
+    // right, and solves the puzzle. Illegal moves are an error, and don't
+    // count as an attempt.
+    pub fn play(&mut self, m: Move) -> Result<Step, String> {
+        let expected = self
+            .get_expected_move()
+            .ok_or_else(|| String::from("the puzzle is over"))?;
+        if m == Move::Resign {
+            return Err(String::from("a puzzle can't be resigned"));
+        }
+
+        // Moves are compared by the boards they lead to, so that a pawn
+        // moved to the last row is the promotion to a queen.
+        let next = match self.board.play_move(m) {
+            GameResult::Continuing(next) => next,
+            GameResult::Victory(next, _) => {
+                self.board = next;
+                self.ply = self.puzzle.moves.len();
+                return Ok(Step::Solved);
+            }
+            GameResult::Stalemate => self.board.apply_eval_move(m),
+            GameResult::IllegalMove(_) => {
+                return Err(format!("illegal move `{}`", self.board.get_uci(m)))
+            }
+        };
+        if next != self.board.apply_eval_move(expected) {
+            self.failed = true;
+            return Ok(Step::Wrong(expected));
+        }
+
+        self.board = next;
+        self.ply += 1;
+        match self.puzzle.moves.get(self.ply) {
+            Some(reply) => {
+                let reply = *reply;
+                self.board = self.board.apply_eval_move(reply);
+                self.ply += 1;
+                // A solution that ends on the opponent's move is solved by
+                // the move before it.
+                if self.is_solved() {
+                    Ok(Step::Solved)
+                } else {
+                    Ok(Step::Correct(reply))
+                }
+            }
+            None => Ok(Step::Solved),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::{Position, BLACK, WHITE};
+
+    const PROMOTION: &str = "P1,8/4P3/8/8/8/8/k7/6K1 b - - 0 1,a2a3 e7e8q,1200";
+    const MATE: &str =
+        "M1,rr5k/6pp/8/8/8/2N5/5PPP/6K1 w - - 0 1,c3d5 a8a1,1000,80,90,10,mate mateIn1,,";
+
+    #[test]
+    fn parse_promotion_puzzle() {
+        let puzzle = Puzzle::parse(PROMOTION).unwrap();
+        assert_eq!(puzzle.get_id(), "P1");
+        assert_eq!(puzzle.get_rating(), 1200);
+        assert_eq!(puzzle.get_color(), WHITE);
+        assert!(matches!(puzzle.get_moves()[1], Move::Promotion(_, _, _)));
+
+        // Solved by the pawn moved to the last row, as on the page.
+        let mut attempt = PuzzleAttempt::new(puzzle);
+        let pushed = Move::Piece(Position::new(6, 4), Position::new(7, 4));
+        assert_eq!(attempt.play(pushed), Ok(Step::Solved));
+        assert!(attempt.is_solved());
+    }
+
+    #[test]
+    fn bad_rows_are_skipped() {
+        let text = format!(
+            "PuzzleId,FEN,Moves,Rating\n{}\nBAD,8/8/8/8/8/8/8/8 w - - 0 1,e2e4,1500\n\n{}\n",
+            PROMOTION, MATE
+        );
+        let (puzzles, errors) = parse_puzzles(&text);
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("line 3:"), "{}", errors[0]);
+        assert_eq!(puzzles[1].get_themes(), ["mate", "mateIn1"]);
+    }
+
+    #[test]
+    fn any_mate_solves() {
+        let puzzle = Puzzle::parse(MATE).unwrap();
+        assert_eq!(puzzle.get_color(), BLACK);
+
+        let mut attempt = PuzzleAttempt::new(puzzle.clone());
+        let board = attempt.get_board();
+        assert_eq!(
+            attempt.play(board.parse_san("Rb1").unwrap()),
+            Ok(Step::Solved)
+        );
+
+        let mut attempt = PuzzleAttempt::new(puzzle);
+        let expected = attempt.get_expected_move().unwrap();
+        let board = attempt.get_board();
+        assert_eq!(
+            attempt.play(board.parse_san("Rb2").unwrap()),
+            Ok(Step::Wrong(expected))
+        );
+        assert!(attempt.is_failed());
+        assert_eq!(attempt.get_expected_move(), None);
+    }
+
+    #[test]
+    fn illegal_moves_are_not_attempts() {
+        let mut attempt = PuzzleAttempt::new(Puzzle::parse(MATE).unwrap());
+        let illegal = Move::Piece(Position::new(7, 0), Position::new(5, 2));
+        assert!(attempt.play(illegal).is_err());
+        assert!(!attempt.is_failed());
+    }
+
+    #[test]
+    fn rating_updates() {
+        // Against an equal rating, a puzzle is worth half the K factor.
+        assert!((update_rating(1500.0, 1500, true) - 1516.0).abs() < 1e-9);
+        assert!((update_rating(1500.0, 1500, false) - 1484.0).abs() < 1e-9);
+        // Solving an easy puzzle earns little, failing it costs a lot.
+        let easy_solved = update_rating(1500.0, 1100, true) - 1500.0;
+        let easy_failed = 1500.0 - update_rating(1500.0, 1100, false);
+        assert!(easy_solved < 4.0 && easy_failed > 28.0);
+    }
+
+    #[test]
+    fn picks_close_puzzles() {
+        let puzzles = vec![
+            Puzzle::parse(PROMOTION).unwrap(),
+            Puzzle::parse(MATE).unwrap(),
+        ];
+        assert_eq!(pick_puzzle(&puzzles, 1250.0).unwrap().get_id(), "P1");
+        // Nothing within the window, so the closest one.
+        assert_eq!(pick_puzzle(&puzzles, 500.0).unwrap().get_id(), "M1");
+        assert!(pick_puzzle(&[], 1500.0).is_none());
+    }
+}
@@ -0,0 +1,569 @@
+use crate::{
+    board::Board,
+    game::{Evaluate, GameResult, Move},
+    params::EvalParams,
+    piece::{Color, Piece, Position, WHITE},
+    search::{get_mate_plies, is_mate_value, Search, SearchLimits, MATE_VALUE},
+};
+
+// The losses in centipawns from which a move is an inaccuracy, a mistake
+// or a blunder.
+const INACCURACY_LOSS: f64 = 50.0;
+const MISTAKE_LOSS: f64 = 100.0;
+const BLUNDER_LOSS: f64 = 300.0;
+
+// Values are capped to this many centipawns either way, so that missing a
+// mate in a won game, or playing on in a lost one, costs a bounded amount.
+const MAX_CENTIPAWNS: f64 = 1000.0;
+
+// The longest line of moves written, as recommended by the PGN standard.
+const MAX_LINE_LENGTH: usize = 79;
+
+// How good a move was, from how much of the value of the position it lost
+// compared to the engine's best move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Classification {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Classification {
+    pub const ALL: [Self; 5] = [
+        Self::Best,
+        Self::Good,
+        Self::Inaccuracy,
+        Self::Mistake,
+        Self::Blunder,
+    ];
+
+    // Classify a move other than the best one from the centipawns it lost.
+    pub fn from_loss(loss: f64) -> Self {
+        if loss >= BLUNDER_LOSS {
+            Self::Blunder
+        } else if loss >= MISTAKE_LOSS {
+            Self::Mistake
+        } else if loss >= INACCURACY_LOSS {
+            Self::Inaccuracy
+        } else {
+            Self::Good
+        }
+    }
+
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Best => "Best",
+            Self::Good => "Good",
+            Self::Inaccuracy => "Inaccuracy",
+            Self::Mistake => "Mistake",
+            Self::Blunder => "Blunder",
+        }
+    }
+
+    // Get the Numeric Annotation Glyph a move is marked with in PGN, like
+    // `$4` for `??`. Moves that aren't errors aren't marked.
+    pub fn get_nag(&self) -> Option<u8> {
+        match self {
+            Self::Inaccuracy => Some(6),
+            Self::Mistake => Some(2),
+            Self::Blunder => Some(4),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn is_error(&self) -> bool {
+        self.get_nag().is_some()
+    }
+}
+
+// What the engine thinks of a position: its best move, if the game isn't
+// over, and the value of the position for the player to move.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Analysis {
+    best_move: Option<Move>,
+    value: f64,
+}
+
+impl Analysis {
+    pub fn new(best_move: Option<Move>, value: f64) -> Self {
+        Self { best_move, value }
+    }
+
+    // Search a position within some limits. Checkmates and stalemates are
+    // valued without searching.
+    pub fn run(board: &Board, search: &Search, limits: &SearchLimits, params: &EvalParams) -> Self {
+        if board.get_legal_moves().is_empty() {
+            let value = if board.is_checkmate() {
+                -MATE_VALUE
+            } else {
+                0.0
+            };
+            return Self::new(None, value);
+        }
+        let result = search.run_with_limits(board, limits, params);
+        Self::new(Some(result.get_best_move()), result.get_value())
+    }
+
+    #[inline]
+    pub fn get_best_move(&self) -> Option<Move> {
+        self.best_move
+    }
+
+    #[inline]
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+}
+
+// A move of a reviewed game, with what the engine thinks of it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveReview {
+    board: Board,
+    m: Move,
+    best_move: Option<Move>,
+    // The values of the position before and after the move, for the player
+    // making it.
+    value_before: f64,
+    value_after: f64,
+    // The centipawns lost compared to the best move.
+    loss: f64,
+    // How close the move came to the best one, from 0 to 100, by the
+    // chances of winning it gave away.
+    accuracy: f64,
+    classification: Classification,
+}
+
+impl MoveReview {
+    fn new(board: Board, m: Move, before: &Analysis, after: &Analysis, pawn: f64) -> Self {
+        let value_before = before.get_value();
+        let value_after = -after.get_value();
+        let cp_before = to_centipawns(value_before, pawn);
+        let cp_after = to_centipawns(value_after, pawn);
+
+        let is_best = before
+            .get_best_move()
+            .is_some_and(|best_move| board.is_same_move(best_move, m));
+        let loss = if is_best {
+            0.0
+        } else {
+            (cp_before - cp_after).max(0.0)
+        };
+        let win_loss = if is_best {
+            0.0
+        } else {
+            (get_win_chance(cp_before) - get_win_chance(cp_after)).max(0.0)
+        };
+        let accuracy = (103.1668 * (-0.04354 * win_loss).exp() - 3.1669).clamp(0.0, 100.0);
+
+        Self {
+            board,
+            m,
+            best_move: before.get_best_move(),
+            value_before,
+            value_after,
+            loss,
+            accuracy,
+            classification: if is_best {
+                Classification::Best
+            } else {
+                Classification::from_loss(loss)
+            },
+        }
+    }
+
+    // Get the board the move was played on.
+    #[inline]
+    pub fn get_board(&self) -> Board {
+        self.board
+    }
+
+    #[inline]
+    pub fn get_move(&self) -> Move {
+        self.m
+    }
+
+    #[inline]
+    pub fn get_color(&self) -> Color {
+        self.board.get_turn_color()
+    }
+
+    #[inline]
+    pub fn get_best_move(&self) -> Option<Move> {
+        self.best_move
+    }
+
+    #[inline]
+    pub fn get_value_before(&self) -> f64 {
+        self.value_before
+    }
+
+    #[inline]
+    pub fn get_value_after(&self) -> f64 {
+        self.value_after
+    }
+
+    #[inline]
+    pub fn get_loss(&self) -> f64 {
+        self.loss
+    }
+
+    #[inline]
+    pub fn get_accuracy(&self) -> f64 {
+        self.accuracy
+    }
+
+    #[inline]
+    pub fn get_classification(&self) -> Classification {
+        self.classification
+    }
+
+    #[inline]
+    pub fn get_san(&self) -> String {
+        self.board.get_san(self.m)
+    }
+
+    // Get the engine's move in SAN, if it is better than the one played.
+    pub fn get_better_san(&self) -> Option<String> {
+        match self.best_move {
+            Some(best_move) if !self.board.is_same_move(best_move, self.m) => {
+                Some(self.board.get_san(best_move))
+            }
+            _ => None,
+        }
+    }
+}
+
+// A game reviewed by the engine, move by move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Review {
+    start: Board,
+    moves: Vec<MoveReview>,
+    pawn: f64,
+}
+
+impl Review {
+    // Review the moves of a game from the analyses of every position in
+    // it, starting with the one before the first move and ending with the
+    // one after the last.
+    pub fn new(
+        start: Board,
+        moves: &[Move],
+        analyses: &[Analysis],
+        params: &EvalParams,
+    ) -> Result<Self, String> {
+        if analyses.len() != moves.len() + 1 {
+            return Err(format!(
+                "expected {} analyses for {} moves, got {}",
+                moves.len() + 1,
+                moves.len(),
+                analyses.len()
+            ));
+        }
+
+        let pawn = params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)));
+        let mut board = start;
+        let mut reviews = vec![];
+        for (i, m) in moves.iter().enumerate() {
+            reviews.push(MoveReview::new(
+                board,
+                *m,
+                &analyses[i],
+                &analyses[i + 1],
+                pawn,
+            ));
+            board = match board.play_move(*m) {
+                GameResult::Continuing(next_board) | GameResult::Victory(next_board, _)
+                    if *m != Move::Resign =>
+                {
+                    next_board
+                }
+                // The game is over after a stalemate, so the move is only
+                // valid as the last one.
+                GameResult::Stalemate if i + 1 == moves.len() => board.apply_eval_move(*m),
+                _ => return Err(format!("illegal move {} in the game", board.get_san(*m))),
+            };
+        }
+
+        Ok(Self {
+            start,
+            moves: reviews,
+            pawn,
+        })
+    }
+
+    // Search every position of a game within some limits and review its
+    // moves. The progress is told the number of positions searched so far
+    // and the number of positions in the game.
+    pub fn run(
+        start: Board,
+        moves: &[Move],
+        search: &Search,
+        limits: &SearchLimits,
+        params: &EvalParams,
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<Self, String> {
+        let mut board = start;
+        let mut analyses = vec![];
+        for (i, m) in moves.iter().enumerate() {
+            progress(i, moves.len() + 1);
+            analyses.push(Analysis::run(&board, search, limits, params));
+            board = board.apply_eval_move(*m);
+        }
+        progress(moves.len(), moves.len() + 1);
+        analyses.push(Analysis::run(&board, search, limits, params));
+        Self::new(start, moves, &analyses, params)
+    }
+
+    #[inline]
+    pub fn get_start(&self) -> Board {
+        self.start
+    }
+
+    #[inline]
+    pub fn get_moves(&self) -> &[MoveReview] {
+        &self.moves
+    }
+
+    // Get the average centipawn loss of a player's moves.
+    pub fn get_average_loss(&self, color: Color) -> f64 {
+        self.get_average(color, |review| review.get_loss())
+    }
+
+    // Get the accuracy of a player's moves, from 0 to 100.
+    pub fn get_accuracy(&self, color: Color) -> f64 {
+        self.get_average(color, |review| review.get_accuracy())
+    }
+
+    // Count a player's moves of a classification.
+    pub fn count(&self, color: Color, classification: Classification) -> usize {
+        self.moves
+            .iter()
+            .filter(|review| {
+                review.get_color() == color && review.get_classification() == classification
+            })
+            .count()
+    }
+
+    fn get_average(&self, color: Color, value: impl Fn(&MoveReview) -> f64) -> f64 {
+        let values = self
+            .moves
+            .iter()
+            .filter(|review| review.get_color() == color)
+            .map(value)
+            .collect::<Vec<f64>>();
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
+        }
+    }
+
+    // Write the game in Portable Game Notation with the review in it. Every
+    // move has the engine's evaluation after it from white's side, and the
+    // errors are marked with their glyph, explained in a comment and
+    // followed by the better move as a variation.
+    pub fn to_pgn(&self, tags: &[(&str, &str)], result: &str) -> String {
+        let mut pgn = String::new();
+        for (name, value) in tags {
+            pgn += &format!("[{} \"{}\"]\n", name, escape(value));
+        }
+        pgn += &format!("[Result \"{}\"]\n", result);
+        if self.start != Board::new() {
+            pgn += "[SetUp \"1\"]\n";
+            pgn += &format!("[FEN \"{}\"]\n", self.start.to_fen());
+        }
+        for color in [Color::White, Color::Black] {
+            let name = if color == WHITE { "White" } else { "Black" };
+            pgn += &format!(
+                "[{}Accuracy \"{:.1}\"]\n[{}ACPL \"{:.0}\"]\n",
+                name,
+                self.get_accuracy(color),
+                name,
+                self.get_average_loss(color)
+            );
+        }
+        pgn += "\n";
+
+        let mut tokens = vec![];
+        for (i, review) in self.moves.iter().enumerate() {
+            let number = self.get_move_number(i);
+            let is_white = review.get_color() == WHITE;
+            // Black's moves follow the comment on white's, so they are
+            // numbered too.
+            if is_white {
+                tokens.push(format!("{}.", number));
+            } else {
+                tokens.push(format!("{}...", number));
+            }
+            tokens.push(review.get_san());
+
+            let classification = review.get_classification();
+            if let Some(nag) = classification.get_nag() {
+                tokens.push(format!("${}", nag));
+            }
+            // A checkmate has no value to show beyond the `#` of its move.
+            let mut comment = if review.get_san().ends_with('#') {
+                String::new()
+            } else {
+                format!(
+                    "[%eval {}]",
+                    self.format_value(review.get_value_after(), review.get_color())
+                )
+            };
+            if classification.is_error() {
+                comment += &format!(
+                    " {} ({} \u{2192} {}).",
+                    classification.get_name(),
+                    self.format_value(review.get_value_before(), review.get_color()),
+                    self.format_value(review.get_value_after(), review.get_color())
+                );
+                if let Some(better) = review.get_better_san() {
+                    comment += &format!(" {} was best.", better);
+                }
+            }
+            if !comment.is_empty() {
+                tokens.push(format!("{{{}}}", comment.trim_start()));
+            }
+            if let (true, Some(better)) = (classification.is_error(), review.get_better_san()) {
+                let number = if is_white {
+                    format!("{}.", number)
+                } else {
+                    format!("{}...", number)
+                };
+                tokens.push(format!("({} {})", number, better));
+            }
+        }
+        tokens.push(result.to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+                pgn += &line;
+                pgn += "\n";
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line += &token;
+        }
+        pgn += &line;
+        pgn += "\n";
+        pgn
+    }
+
+    // Get the number of a move, counted from the first one in the game,
+    // which is black's when the game starts with black to move.
+    pub fn get_move_number(&self, index: usize) -> usize {
+        let ply = index + (self.start.get_turn_color() != WHITE) as usize;
+        ply / 2 + 1
+    }
+
+    // Format the value of a position for a player from white's side, in
+    // pawns like `-1.25`, or as a mate like `#3` or `#-3`.
+    pub fn format_value(&self, value: f64, color: Color) -> String {
+        let value = if color == WHITE { value } else { -value };
+        match get_mate_plies(value) {
+            Some(plies) => {
+                let moves = (plies.abs() + 1) / 2;
+                format!("#{}{}", if value < 0.0 { "-" } else { "" }, moves)
+            }
+            None => format!("{:.2}", value / self.pawn),
+        }
+    }
+}
+
+// Convert a value to centipawns, capped so that mates count as a decided
+// game rather than an endless advantage.
+fn to_centipawns(value: f64, pawn: f64) -> f64 {
+    if is_mate_value(value) {
+        value.signum() * MAX_CENTIPAWNS
+    } else {
+        (value / pawn * 100.0).clamp(-MAX_CENTIPAWNS, MAX_CENTIPAWNS)
+    }
+}
+
+// Get the chances of winning in percent with an advantage in centipawns,
+// as estimated from games between club players.
+fn get_win_chance(centipawns: f64) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * centipawns).exp()) - 1.0)
+}
+
+fn escape(tag: &str) -> String {
+    tag.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::piece::BLACK;
+
+    fn pawns(pawns: f64) -> f64 {
+        pawns * EvalParams::default().get_material_value(Piece::Pawn(WHITE, Position::new(0, 0)))
+    }
+
+    #[test]
+    fn classification_by_loss() {
+        assert_eq!(Classification::from_loss(20.0), Classification::Good);
+        assert_eq!(Classification::from_loss(60.0), Classification::Inaccuracy);
+        assert_eq!(Classification::from_loss(150.0), Classification::Mistake);
+        assert_eq!(Classification::from_loss(400.0), Classification::Blunder);
+        assert!(!Classification::Best.is_error());
+        assert_eq!(Classification::Blunder.get_nag(), Some(4));
+    }
+
+    #[test]
+    fn blunder_with_better_move() {
+        let board = Board::new();
+        let moves = [board.parse_san("f3").unwrap()];
+        let analyses = [
+            Analysis::new(board.parse_san("e4").ok(), pawns(0.3)),
+            // Four pawns up for black after the move.
+            Analysis::new(None, pawns(4.0)),
+        ];
+        let review = Review::new(board, &moves, &analyses, &EvalParams::default()).unwrap();
+
+        let m = review.get_moves()[0];
+        assert_eq!(m.get_classification(), Classification::Blunder);
+        assert!((m.get_loss() - 430.0).abs() < 1e-6);
+        assert!(m.get_accuracy() < 50.0);
+        assert_eq!(m.get_better_san().as_deref(), Some("e4"));
+        assert_eq!(review.count(WHITE, Classification::Blunder), 1);
+        assert_eq!(review.get_average_loss(BLACK), 0.0);
+
+        let pgn = review.to_pgn(&[("White", "A")], "*");
+        assert!(pgn.contains("1. f3 $4"), "{}", pgn);
+        assert!(pgn.contains("(1. e4)"), "{}", pgn);
+        assert!(pgn.contains("[WhiteACPL \"430\"]"), "{}", pgn);
+    }
+
+    // The search writes a promotion to a queen as a plain pawn move, while
+    // games are read with promotions spelled out.
+    #[test]
+    fn promotion_to_best_move() {
+        let board = Board::from_fen("8/4P3/8/8/8/8/k7/6K1 w - - 0 1").unwrap();
+        let moves = [board.parse_san("e8=Q").unwrap()];
+        let pushed = Move::Piece(Position::new(6, 4), Position::new(7, 4));
+        let analyses = [
+            Analysis::new(Some(pushed), pawns(9.0)),
+            Analysis::new(None, pawns(-9.0)),
+        ];
+        let review = Review::new(board, &moves, &analyses, &EvalParams::default()).unwrap();
+
+        let m = review.get_moves()[0];
+        assert_eq!(m.get_classification(), Classification::Best);
+        assert_eq!(m.get_loss(), 0.0);
+        assert_eq!(m.get_better_san(), None);
+    }
+
+    #[test]
+    fn analyses_must_match_moves() {
+        let board = Board::new();
+        let moves = [board.parse_san("e4").unwrap()];
+        let analyses = [Analysis::new(None, 0.0)];
+        assert!(Review::new(board, &moves, &analyses, &EvalParams::default()).is_err());
+    }
+}
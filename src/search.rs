@@ -0,0 +1,1451 @@
+use crate::{
+    board::{Board, BoardBuilder},
+    game::{Evaluate, Move},
+    params::EvalParams,
+    piece::{Color, Piece, Position, WHITE},
+    utils::Timer,
+};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// The value of checkmating the other player right away. Mates that take
+// more moves are worth a little less, so that the quickest one is played.
+pub const MATE_VALUE: f64 = 1000000.0;
+
+// The longest mate that is still told apart from a regular value.
+const MAX_MATE_PLIES: f64 = 1000.0;
+
+// The deepest a search goes when its depth isn't limited.
+pub const MAX_DEPTH: i32 = 64;
+
+// How many boards a thread evaluates between two checks of the limits of
+// the search.
+const CHECK_INTERVAL: u64 = 1024;
+
+// The width of the windows of searches that only check whether a value is
+// above a bound, in the units of the evaluation.
+const NULL_WINDOW: f64 = 0.01;
+
+// The least depth left for null move pruning to be tried.
+const NULL_MOVE_MIN_DEPTH: i32 = 2;
+
+// The number of moves searched at full depth before the later quiet ones
+// are reduced.
+const LMR_MIN_MOVES: usize = 3;
+
+// The least depth left for late moves to be reduced.
+const LMR_MIN_DEPTH: i32 = 3;
+
+// For every depth left, how far in pawns below alpha the static value of a
+// position must be for its quiet moves to be skipped.
+const FUTILITY_MARGINS: [f64; 3] = [0.0, 2.0, 5.0];
+
+// How far in pawns above beta, for every move of depth left, the static
+// value of a position must be for it to be cut off without a search.
+const REVERSE_FUTILITY_MARGIN: f64 = 1.2;
+
+// The deepest depth left where reverse futility pruning is tried.
+const REVERSE_FUTILITY_MAX_DEPTH: i32 = 3;
+
+// Is a value a forced mate, for either player?
+#[inline]
+pub fn is_mate_value(value: f64) -> bool {
+    value.abs() >= MATE_VALUE - MAX_MATE_PLIES
+}
+
+// Get the number of plies until mate for a mate value, positive when the
+// player the value is for mates and negative when they are mated.
+pub fn get_mate_plies(value: f64) -> Option<i32> {
+    if !is_mate_value(value) {
+        return None;
+    }
+    let plies = (MATE_VALUE - value.abs()).round() as i32;
+    Some(if value > 0.0 { plies } else { -plies })
+}
+
+// How a stored value relates to the real value of a position: it is exact,
+// or the search stopped early because the real value was known to be at
+// least or at most the stored one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+// A position remembered by the transposition table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Entry {
+    value: f64,
+    depth: i32,
+    bound: Bound,
+    best_move: Option<Move>,
+    generation: u8,
+}
+
+impl Entry {
+    // Pack an entry into 64 bits: the value as a 32 bit float, the depth,
+    // the bound, the best move and the generation.
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        (self.value as f32).to_bits() as u64
+            | ((self.depth.clamp(0, 255) as u64) << 32)
+            | (bound << 40)
+            | ((self.best_move.map_or(0, encode_move) as u64) << 42)
+            | (((self.generation & 0x3f) as u64) << 58)
+    }
+
+    fn unpack(data: u64, board: &Board) -> Self {
+        let bound = match (data >> 40) & 0x3 {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Self {
+            value: f32::from_bits(data as u32) as f64,
+            depth: ((data >> 32) & 0xff) as i32,
+            bound,
+            best_move: decode_move(((data >> 42) & 0xffff) as u16, board),
+            generation: ((data >> 58) & 0x3f) as u8,
+        }
+    }
+}
+
+#[inline]
+fn get_square(pos: Position) -> u16 {
+    (pos.get_row() * 8 + pos.get_col()) as u16
+}
+
+#[inline]
+fn get_position(square: u16) -> Position {
+    Position::new(square as i32 / 8, square as i32 % 8)
+}
+
+// Encode a move into 16 bits: the from and to squares, and its kind in the
+// top 4 bits. Zero is left for no move at all.
+fn encode_move(m: Move) -> u16 {
+    match m {
+        Move::Piece(from, to) => 1 << 12 | get_square(from) << 6 | get_square(to),
+        Move::KingSideCastle => 2 << 12,
+        Move::QueenSideCastle => 3 << 12,
+        Move::Promotion(from, to, piece) => {
+            let kind = if piece.is_queen() {
+                4
+            } else if piece.is_rook() {
+                5
+            } else if piece.is_bishop() {
+                6
+            } else {
+                7
+            };
+            kind << 12 | get_square(from) << 6 | get_square(to)
+        }
+        Move::Resign => 0,
+    }
+}
+
+// Decode a move for the current player of a board.
+fn decode_move(code: u16, board: &Board) -> Option<Move> {
+    let from = get_position((code >> 6) & 0x3f);
+    let to = get_position(code & 0x3f);
+    let color = board.get_turn_color();
+    Some(match code >> 12 {
+        1 => Move::Piece(from, to),
+        2 => Move::KingSideCastle,
+        3 => Move::QueenSideCastle,
+        4 => Move::Promotion(from, to, Piece::Queen(color, to)),
+        5 => Move::Promotion(from, to, Piece::Rook(color, to)),
+        6 => Move::Promotion(from, to, Piece::Bishop(color, to)),
+        7 => Move::Promotion(from, to, Piece::Knight(color, to)),
+        _ => return None,
+    })
+}
+
+// A table of the values of positions that were already searched, shared
+// by every thread of a search.
+//
+// Every slot is two atomic words, the key xor-ed with the data and the data
+// itself, so that threads never need to lock it. A slot torn by two threads
+// writing at once doesn't match its key anymore, and is just ignored.
+pub struct TranspositionTable {
+    slots: Vec<[AtomicU64; 2]>,
+    generation: AtomicU8,
+}
+
+impl TranspositionTable {
+    // Create a table using about `megabytes` of memory.
+    pub fn new(megabytes: usize) -> Self {
+        let len = (megabytes * 1024 * 1024 / std::mem::size_of::<[AtomicU64; 2]>()).max(1);
+        Self {
+            slots: (0..len)
+                .map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+                .collect(),
+            generation: AtomicU8::new(0),
+        }
+    }
+
+    // Forget every position.
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot[0].store(0, Ordering::Relaxed);
+            slot[1].store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Get the number of positions the table can hold.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // Get the permille of the table used by the current search, like the
+    // `hashfull` of the UCI protocol.
+    pub fn get_hashfull(&self) -> u32 {
+        let generation = self.generation.load(Ordering::Relaxed) & 0x3f;
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| {
+                let data = slot[1].load(Ordering::Relaxed);
+                data != 0 && ((data >> 58) & 0x3f) as u8 == generation
+            })
+            .count();
+        (used * 1000 / sample.max(1)) as u32
+    }
+
+    // Start a new search, so that the entries of older ones get replaced
+    // first.
+    fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn get_slot(&self, key: u64) -> &[AtomicU64; 2] {
+        &self.slots[((key as u128 * self.slots.len() as u128) >> 64) as usize]
+    }
+
+    fn probe(&self, key: u64, board: &Board) -> Option<Entry> {
+        let slot = self.get_slot(key);
+        let data = slot[1].load(Ordering::Relaxed);
+        if slot[0].load(Ordering::Relaxed) ^ data != key || data == 0 {
+            return None;
+        }
+        Some(Entry::unpack(data, board))
+    }
+
+    fn store(&self, key: u64, board: &Board, mut entry: Entry) {
+        let slot = self.get_slot(key);
+        let generation = self.generation.load(Ordering::Relaxed) & 0x3f;
+        entry.generation = generation;
+
+        // Keep deeper results of the same search, unless the new one is
+        // exact, and keep the best move of the position if the new result
+        // doesn't have one.
+        let old_data = slot[1].load(Ordering::Relaxed);
+        if old_data != 0 {
+            let old_entry = Entry::unpack(old_data, board);
+            let same_position = slot[0].load(Ordering::Relaxed) ^ old_data == key;
+            if old_entry.generation == generation
+                && old_entry.depth > entry.depth
+                && entry.bound != Bound::Exact
+            {
+                return;
+            }
+            if same_position && entry.best_move.is_none() {
+                entry.best_move = old_entry.best_move;
+            }
+        }
+
+        let data = entry.pack();
+        slot[0].store(key ^ data, Ordering::Relaxed);
+        slot[1].store(data, Ordering::Relaxed);
+    }
+
+    // Follow the best moves stored in the transposition table from the best
+    // move of the root.
+    fn get_pv(&self, board: &Board, best_move: Move, max_len: i32) -> Vec<Move> {
+        let mut pv = vec![best_move];
+        let mut board = board.apply_eval_move(best_move);
+        while (pv.len() as i32) < max_len {
+            let m = match self
+                .probe(board.get_zobrist_key(), &board)
+                .and_then(|entry| entry.best_move)
+            {
+                Some(m) if board.get_legal_moves().contains(&m) => m,
+                _ => break,
+            };
+            pv.push(m);
+            board = board.apply_eval_move(m);
+        }
+        pv
+    }
+}
+
+// Mate values are stored relative to the position they are found in, and
+// read back relative to the root of the search.
+#[inline]
+fn value_to_table(value: f64, ply: i32) -> f64 {
+    if is_mate_value(value) {
+        value + value.signum() * ply as f64
+    } else {
+        value
+    }
+}
+
+#[inline]
+fn value_from_table(value: f64, ply: i32) -> f64 {
+    if is_mate_value(value) {
+        value - value.signum() * ply as f64
+    } else {
+        value
+    }
+}
+
+// The outcome of a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    best_move: Move,
+    value: f64,
+    depth: i32,
+    nodes: u64,
+    pv: Vec<Move>,
+    move_values: Vec<(Move, f64)>,
+}
+
+impl SearchResult {
+    #[inline]
+    pub fn get_best_move(&self) -> Move {
+        self.best_move
+    }
+
+    // Get the value of the best move for the player to move, which is a
+    // mate value if the search found a forced mate.
+    #[inline]
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    // Get the deepest depth the search finished.
+    #[inline]
+    pub fn get_depth(&self) -> i32 {
+        self.depth
+    }
+
+    // Get the number of boards evaluated by every thread.
+    #[inline]
+    pub fn get_nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    // Get the principal variation, the line of best play expected from
+    // both players, starting with the best move.
+    #[inline]
+    pub fn get_pv(&self) -> &[Move] {
+        &self.pv
+    }
+
+    // Get the reply to the best move the search expects, which is worth
+    // thinking about while the other player does.
+    #[inline]
+    pub fn get_ponder_move(&self) -> Option<Move> {
+        self.pv.get(1).copied()
+    }
+
+    // Get every legal move of the current player with its value, from the
+    // deepest depth finished. This is empty unless the search was run with
+    // `run_with_move_values`.
+    #[inline]
+    pub fn get_move_values(&self) -> &[(Move, f64)] {
+        &self.move_values
+    }
+}
+
+// When a search stops: after a number of moves of lookahead, a number of
+// boards evaluated or some time, or as soon as its stop signal is set from
+// another thread, like when a UCI engine is told to `stop`. A search
+// stopped early still plays the best move it has found so far.
+#[derive(Clone, Debug, Default)]
+pub struct SearchLimits {
+    depth: Option<i32>,
+    nodes: Option<u64>,
+    time: Option<Duration>,
+    stop: Arc<AtomicBool>,
+    stop_check: Option<fn() -> bool>,
+}
+
+impl SearchLimits {
+    // Limits that only stop the search once it is told to, or once it
+    // reaches `MAX_DEPTH`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_depth(mut self, depth: i32) -> Self {
+        self.depth = Some(depth.clamp(0, MAX_DEPTH));
+        self
+    }
+
+    pub fn with_nodes(mut self, nodes: u64) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    // Stop the search once it has run for `time`, counted from its start.
+    pub fn with_time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    // Stop the search when `stop` is set, which can be done from any
+    // thread while the search runs.
+    pub fn with_stop_signal(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = stop;
+        self
+    }
+
+    // Stop the search once `check` returns true, which is asked every few
+    // boards while the search runs. This is for stop signals that can't be
+    // an `AtomicBool`, like the one a web page shares with the worker
+    // searching for it.
+    pub fn with_stop_check(mut self, check: fn() -> bool) -> Self {
+        self.stop_check = Some(check);
+        self
+    }
+
+    #[inline]
+    pub fn get_depth(&self) -> Option<i32> {
+        self.depth
+    }
+
+    #[inline]
+    pub fn get_nodes(&self) -> Option<u64> {
+        self.nodes
+    }
+
+    #[inline]
+    pub fn get_time(&self) -> Option<Duration> {
+        self.time
+    }
+
+    #[inline]
+    pub fn get_stop_signal(&self) -> &Arc<AtomicBool> {
+        &self.stop
+    }
+
+    // Tell the searches using these limits to stop.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.stop_check.is_some_and(|check| check())
+    }
+
+    // Has a search that evaluated `nodes` boards in `elapsed` time reached
+    // any of the limits, other than the depth?
+    fn is_reached(&self, nodes: u64, elapsed: Duration) -> bool {
+        self.is_stopped()
+            || self.nodes.is_some_and(|max| nodes >= max)
+            || self.time.is_some_and(|max| elapsed >= max)
+    }
+}
+
+// The selective parts of the search, which skip or shorten the lines that
+// are unlikely to matter to search deeper in the same time. Each one can be
+// turned off, to measure what it brings with `selfplay` or `suite`.
+//
+// - Null move pruning: when passing still leaves the current player above
+//   beta after a shallower search, the position is cut off. It is skipped
+//   in check and when the player only has pawns left, where passing may
+//   well be better than any move, like in zugzwang.
+// - Late move reductions: quiet moves ordered late are searched less deep,
+//   and again at full depth only if they turn out better than expected.
+// - Futility pruning: near the leaves, quiet moves are skipped when the
+//   static value is so far below alpha that they can't make up for it.
+// - Reverse futility pruning: near the leaves, positions whose static value
+//   is far above beta are cut off.
+// - Check extensions: moves that give check are searched a move deeper, so
+//   that the lines with checks are followed to their end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SearchFeatures {
+    null_move: bool,
+    late_move_reductions: bool,
+    futility: bool,
+    reverse_futility: bool,
+    check_extensions: bool,
+}
+
+impl SearchFeatures {
+    // The names of the features, as they are given to the tools.
+    pub const NAMES: [&'static str; 5] = [
+        "null-move",
+        "lmr",
+        "futility",
+        "reverse-futility",
+        "check-extensions",
+    ];
+
+    // Every feature turned on.
+    pub fn all() -> Self {
+        Self {
+            null_move: true,
+            late_move_reductions: true,
+            futility: true,
+            reverse_futility: true,
+            check_extensions: true,
+        }
+    }
+
+    // Every feature turned off, for a plain alpha-beta search.
+    pub fn none() -> Self {
+        Self {
+            null_move: false,
+            late_move_reductions: false,
+            futility: false,
+            reverse_futility: false,
+            check_extensions: false,
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Result<&mut bool, String> {
+        match name {
+            "null-move" => Ok(&mut self.null_move),
+            "lmr" => Ok(&mut self.late_move_reductions),
+            "futility" => Ok(&mut self.futility),
+            "reverse-futility" => Ok(&mut self.reverse_futility),
+            "check-extensions" => Ok(&mut self.check_extensions),
+            _ => Err(format!(
+                "unknown search feature `{}`, expected one of {}",
+                name,
+                Self::NAMES.join(", ")
+            )),
+        }
+    }
+
+    // Is a feature, given by its name, turned on?
+    pub fn get(&self, name: &str) -> Result<bool, String> {
+        let mut features = *self;
+        features.get_mut(name).map(|enabled| *enabled)
+    }
+
+    // Turn a feature, given by its name, on or off.
+    pub fn set(&mut self, name: &str, enabled: bool) -> Result<(), String> {
+        *self.get_mut(name)? = enabled;
+        Ok(())
+    }
+
+    // Turn off the features in a list of names separated by commas, like
+    // `lmr,futility`.
+    pub fn disable(&mut self, names: &str) -> Result<(), String> {
+        for name in names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            self.set(name, false)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn has_null_move(&self) -> bool {
+        self.null_move
+    }
+
+    #[inline]
+    pub fn has_late_move_reductions(&self) -> bool {
+        self.late_move_reductions
+    }
+
+    #[inline]
+    pub fn has_futility(&self) -> bool {
+        self.futility
+    }
+
+    #[inline]
+    pub fn has_reverse_futility(&self) -> bool {
+        self.reverse_futility
+    }
+
+    #[inline]
+    pub fn has_check_extensions(&self) -> bool {
+        self.check_extensions
+    }
+}
+
+impl Default for SearchFeatures {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+// A search that keeps its transposition table from one move to the next,
+// and spreads its work over several threads on native targets.
+//
+// The threads search the same position in Lazy SMP style: they share
+// nothing but the transposition table, and the helpers search the moves in
+// a different order and skip some depths, so they fill the table with
+// results that the main thread then finds instead of searching them again.
+pub struct Search {
+    table: Arc<TranspositionTable>,
+    threads: usize,
+    features: SearchFeatures,
+}
+
+impl Search {
+    // The default size of the transposition table, in megabytes.
+    pub const DEFAULT_HASH: usize = 16;
+
+    pub fn new(threads: usize, megabytes: usize) -> Self {
+        Self {
+            table: Arc::new(TranspositionTable::new(megabytes)),
+            threads: threads.max(1),
+            features: SearchFeatures::default(),
+        }
+    }
+
+    #[inline]
+    pub fn get_threads(&self) -> usize {
+        self.threads
+    }
+
+    // Set the number of threads searching. Web Assembly has no threads, so
+    // it always searches with one.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    #[inline]
+    pub fn get_features(&self) -> SearchFeatures {
+        self.features
+    }
+
+    pub fn set_features(&mut self, features: SearchFeatures) {
+        self.features = features;
+    }
+
+    #[inline]
+    pub fn get_table(&self) -> &TranspositionTable {
+        &self.table
+    }
+
+    // Replace the transposition table with one of another size.
+    pub fn set_hash(&mut self, megabytes: usize) {
+        self.table = Arc::new(TranspositionTable::new(megabytes));
+    }
+
+    // Forget every position searched so far, like at the start of a new
+    // game.
+    pub fn clear(&self) {
+        self.table.clear();
+    }
+
+    // Search for the best move of the current player with `depth` number of
+    // moves of lookahead, like `Evaluate::get_best_next_move`.
+    pub fn run(&self, board: &Board, depth: i32, params: &EvalParams) -> SearchResult {
+        self.run_with_limits(board, &SearchLimits::new().with_depth(depth), params)
+    }
+
+    // Search deeper and deeper for the best move of the current player,
+    // until one of the limits is reached.
+    pub fn run_with_limits(
+        &self,
+        board: &Board,
+        limits: &SearchLimits,
+        params: &EvalParams,
+    ) -> SearchResult {
+        self.run_with_progress(board, limits, params, |_| {})
+    }
+
+    // Search like `run_with_limits`, reporting the result of every depth
+    // as soon as it is finished, like UCI engines do with `info` lines.
+    pub fn run_with_progress(
+        &self,
+        board: &Board,
+        limits: &SearchLimits,
+        params: &EvalParams,
+        progress: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        self.search(board, limits, params, false, progress)
+    }
+
+    // Search like `run_with_progress`, also rating every legal move of the
+    // current player, for the difficulty levels that don't always play the
+    // best one. Every depth is slower, since the moves after the best one
+    // are searched with a full window instead of being cut off.
+    pub fn run_with_move_values(
+        &self,
+        board: &Board,
+        limits: &SearchLimits,
+        params: &EvalParams,
+        progress: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        self.search(board, limits, params, true, progress)
+    }
+
+    fn search(
+        &self,
+        board: &Board,
+        limits: &SearchLimits,
+        params: &EvalParams,
+        move_values: bool,
+        mut progress: impl FnMut(&SearchResult),
+    ) -> SearchResult {
+        let depth = limits.get_depth().unwrap_or(MAX_DEPTH);
+        self.table.new_search();
+        if let Some((m, value)) = board.get_tablebase_move() {
+            return SearchResult {
+                best_move: m,
+                value,
+                depth,
+                nodes: 1,
+                pv: vec![m],
+                move_values: vec![],
+            };
+        }
+
+        let stop = AtomicBool::new(false);
+        let nodes = AtomicU64::new(0);
+        let main = Thread {
+            id: 0,
+            table: &self.table,
+            params,
+            features: self.features,
+            pawn: params.get_material_value(Piece::Pawn(WHITE, Position::new(0, 0))),
+            root_depth: 0,
+            move_values,
+            limits,
+            timer: Timer::start(),
+            stop: &stop,
+            searched: &nodes,
+            nodes: 0,
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut result = std::thread::scope(|scope| {
+            for id in 1..self.threads {
+                let mut helper = Thread {
+                    id,
+                    move_values: false,
+                    ..main
+                };
+                scope.spawn(move || {
+                    helper.iterate(board, depth, &mut |_| {});
+                    helper.flush_nodes();
+                });
+            }
+
+            let mut main = main;
+            let result = main.iterate(board, depth, &mut progress);
+            // The helpers only help the main thread, so they stop with it.
+            stop.store(true, Ordering::Relaxed);
+            main.flush_nodes();
+            result
+        });
+        #[cfg(target_arch = "wasm32")]
+        let mut result = {
+            let mut main = main;
+            let result = main.iterate(board, depth, &mut progress);
+            main.flush_nodes();
+            result
+        };
+
+        result.nodes = nodes.load(Ordering::Relaxed);
+        result.pv = self.table.get_pv(board, result.best_move, result.depth + 1);
+        result
+    }
+}
+
+impl Default for Search {
+    fn default() -> Self {
+        Self::new(1, Self::DEFAULT_HASH)
+    }
+}
+
+// One of the threads of a search.
+#[derive(Clone, Copy)]
+struct Thread<'a> {
+    id: usize,
+    table: &'a TranspositionTable,
+    params: &'a EvalParams,
+    features: SearchFeatures,
+    // The value of a pawn, which the pruning margins are measured in.
+    pawn: f64,
+    // The depth of the current iteration.
+    root_depth: i32,
+    // Whether every move of the root is searched for its value.
+    move_values: bool,
+    limits: &'a SearchLimits,
+    timer: Timer,
+    // Set to stop every thread of the search.
+    stop: &'a AtomicBool,
+    // The number of boards evaluated by every thread, which each one adds
+    // its own to every `CHECK_INTERVAL` boards.
+    searched: &'a AtomicU64,
+    nodes: u64,
+}
+
+impl Thread<'_> {
+    // Search one depth after the other up to `depth`, so that every one
+    // starts with the best moves found by the one before.
+    fn iterate(
+        &mut self,
+        board: &Board,
+        depth: i32,
+        progress: &mut dyn FnMut(&SearchResult),
+    ) -> SearchResult {
+        // A search stopped before it finished any move still has one to
+        // play.
+        let mut result = SearchResult {
+            best_move: self
+                .get_ordered_moves(board)
+                .first()
+                .copied()
+                .unwrap_or(Move::Resign),
+            value: -MATE_VALUE,
+            depth: 0,
+            nodes: 0,
+            pv: vec![],
+            move_values: vec![],
+        };
+
+        for current in 0..=depth {
+            // Half of the helpers skip every other depth, so that they are
+            // often a depth ahead of the main thread.
+            if self.id % 2 == 1 && current % 2 == 1 && current < depth {
+                continue;
+            }
+            let mut move_values = vec![];
+            if let Some((best_move, value)) = self.search_root(board, current, &mut move_values) {
+                result.best_move = best_move;
+                result.value = value;
+            }
+            if self.stop.load(Ordering::Relaxed) {
+                break;
+            }
+            result.depth = current;
+            if self.move_values {
+                result.move_values = move_values;
+            }
+            progress(&SearchResult {
+                nodes: self.searched.load(Ordering::Relaxed) + self.nodes % CHECK_INTERVAL,
+                pv: self.table.get_pv(board, result.best_move, current + 1),
+                move_values: result.move_values.clone(),
+                ..result
+            });
+        }
+        result
+    }
+
+    // Get the best move of the root and its value. When the search is
+    // stopped before it finishes, this is the best move among the ones that
+    // were searched, or `None` if the first one wasn't. Since the first move
+    // is the best one of the depth before, a move that beats it is better.
+    // The value of every move searched is added to `move_values`, which is
+    // only exact for the best one unless the thread searches every move for
+    // its value.
+    fn search_root(
+        &mut self,
+        board: &Board,
+        depth: i32,
+        move_values: &mut Vec<(Move, f64)>,
+    ) -> Option<(Move, f64)> {
+        let mut moves = self.get_ordered_moves(board);
+        if moves.is_empty() {
+            return Some((Move::Resign, -MATE_VALUE));
+        }
+        // The helpers search the moves after the best one in another order.
+        if self.id > 0 && moves.len() > 2 {
+            let len = moves.len() - 1;
+            moves[1..].rotate_left(self.id % len);
+        }
+
+        self.root_depth = depth;
+        let mut alpha = f64::NEG_INFINITY;
+        let mut best_move = None;
+        for m in moves {
+            let bound = if self.move_values {
+                f64::NEG_INFINITY
+            } else {
+                alpha
+            };
+            let value = -self.negamax(
+                &board.apply_eval_move(m),
+                depth,
+                1,
+                f64::NEG_INFINITY,
+                -bound,
+                false,
+            );
+            if self.stop.load(Ordering::Relaxed) {
+                return best_move.map(|m| (m, alpha));
+            }
+            move_values.push((m, value));
+            if value > alpha {
+                alpha = value;
+                best_move = Some(m);
+            }
+        }
+
+        let entry = Entry {
+            value: alpha,
+            depth: depth + 1,
+            bound: Bound::Exact,
+            best_move,
+            generation: 0,
+        };
+        self.table.store(board.get_zobrist_key(), board, entry);
+        best_move.map(|m| (m, alpha))
+    }
+
+    // Count a board evaluated, and stop every thread once a limit of the
+    // search is reached. Returns whether the search is stopped.
+    fn count_node(&mut self) -> bool {
+        self.nodes += 1;
+        let unflushed = self.nodes % CHECK_INTERVAL;
+        if unflushed == 0 {
+            let searched = self.searched.fetch_add(CHECK_INTERVAL, Ordering::Relaxed);
+            if self
+                .limits
+                .is_reached(searched + CHECK_INTERVAL, self.timer.elapsed())
+            {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        } else if self.limits.get_nodes().is_some_and(|max| {
+            // Check a node limit at every board, so that small ones are
+            // kept exactly by a single thread.
+            self.searched.load(Ordering::Relaxed) + unflushed >= max
+        }) {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    // Add the boards evaluated since the last check to the total of the
+    // search.
+    fn flush_nodes(&self) {
+        self.searched
+            .fetch_add(self.nodes % CHECK_INTERVAL, Ordering::Relaxed);
+    }
+
+    // Get the legal moves of the current player, starting with the best one
+    // stored in the transposition table, then the captures of the most
+    // valuable pieces by the least valuable ones. Captures that lose
+    // material once the exchange is played out come last.
+    fn get_ordered_moves(&self, board: &Board) -> Vec<Move> {
+        let best_move = self
+            .table
+            .probe(board.get_zobrist_key(), board)
+            .and_then(|entry| entry.best_move);
+        let mut moves = board.get_legal_moves();
+        moves.sort_by_cached_key(|m| {
+            if Some(*m) == best_move {
+                return i64::MIN;
+            }
+            match m {
+                Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+                    match (board.get_piece(*to), board.get_piece(*from)) {
+                        (Some(victim), Some(attacker)) => {
+                            // Taking a piece worth at least as much never
+                            // loses material.
+                            if attacker.get_material_value() > victim.get_material_value() {
+                                let see = board.see(*m);
+                                if see < 0 {
+                                    return -see as i64;
+                                }
+                            }
+                            let victim = self.params.get_material_value(victim);
+                            let attacker = self.params.get_material_value(attacker);
+                            -((victim * 100.0 - attacker) as i64)
+                        }
+                        _ => 0,
+                    }
+                }
+                _ => 0,
+            }
+        });
+        moves
+    }
+
+    // Get the value of a board for its current player, searching `depth`
+    // more moves. `ply` is the number of moves played since the root, and
+    // `after_null_move` tells whether the last one was a null move.
+    fn negamax(
+        &mut self,
+        board: &Board,
+        depth: i32,
+        ply: i32,
+        mut alpha: f64,
+        beta: f64,
+        after_null_move: bool,
+    ) -> f64 {
+        if self.count_node() {
+            return 0.0;
+        }
+
+        let color = board.get_turn_color();
+        if depth <= 0 {
+            return board.value_for(color, self.params);
+        }
+
+        let key = board.get_zobrist_key();
+        if let Some(entry) = self.table.probe(key, board) {
+            let value = value_from_table(entry.value, ply);
+            if entry.depth >= depth
+                && match entry.bound {
+                    Bound::Exact => true,
+                    Bound::Lower => value >= beta,
+                    Bound::Upper => value <= alpha,
+                }
+            {
+                return value;
+            }
+        }
+
+        // Probing resolves the captures of the position first, so it is
+        // only done once per position: the value is stored as if it was
+        // searched to the end, and found in the table from then on.
+        if let Some(value) = board.get_tablebase_value(color) {
+            let entry = Entry {
+                value: value_to_table(value, ply),
+                depth: MAX_DEPTH,
+                bound: Bound::Exact,
+                best_move: None,
+                generation: 0,
+            };
+            self.table.store(key, board, entry);
+            return value;
+        }
+
+        let in_check = board.is_in_check(color);
+        // The value of the board without searching, which the pruning
+        // compares to the window. It means little in check, so nothing is
+        // pruned then.
+        let static_value = if in_check {
+            None
+        } else {
+            Some(board.value_for(color, self.params))
+        };
+
+        if let Some(static_value) = static_value {
+            if self.features.reverse_futility
+                && depth <= REVERSE_FUTILITY_MAX_DEPTH
+                && !is_mate_value(beta)
+                && static_value - REVERSE_FUTILITY_MARGIN * self.pawn * depth as f64 >= beta
+            {
+                return static_value;
+            }
+
+            if self.features.null_move
+                && !after_null_move
+                && depth >= NULL_MOVE_MIN_DEPTH
+                && static_value >= beta
+                && can_pass(board, color, in_check)
+            {
+                let reduction = if depth > 6 { 3 } else { 2 };
+                let passed = BoardBuilder::from(*board)
+                    .set_en_passant(None)
+                    .build()
+                    .change_turn();
+                let value = -self.negamax(
+                    &passed,
+                    depth - 1 - reduction,
+                    ply + 1,
+                    -beta,
+                    -beta + NULL_WINDOW,
+                    true,
+                );
+                if self.stop.load(Ordering::Relaxed) {
+                    return 0.0;
+                }
+                // A mate found after passing isn't a real one.
+                if value >= beta {
+                    return if is_mate_value(value) { beta } else { value };
+                }
+            }
+        }
+
+        let moves = self.get_ordered_moves(board);
+        if moves.is_empty() {
+            return if in_check {
+                -(MATE_VALUE - ply as f64)
+            } else {
+                0.0
+            };
+        }
+
+        let futility_value = static_value
+            .filter(|_| self.features.futility && (depth as usize) < FUTILITY_MARGINS.len())
+            .filter(|_| !is_mate_value(alpha))
+            .map(|static_value| static_value + FUTILITY_MARGINS[depth as usize] * self.pawn)
+            .filter(|futility_value| *futility_value <= alpha);
+
+        let original_alpha = alpha;
+        let mut best_value = f64::NEG_INFINITY;
+        let mut best_move = None;
+        for (i, m) in moves.into_iter().enumerate() {
+            let next_board = board.apply_eval_move(m);
+            let gives_check = next_board.is_in_check(!color);
+            let is_quiet = !gives_check && is_quiet(board, m);
+
+            // The first move is always searched, so that there is a best
+            // one.
+            if let Some(futility_value) = futility_value.filter(|_| is_quiet && i > 0) {
+                best_value = best_value.max(futility_value);
+                continue;
+            }
+
+            // Checks are extended, up to twice the depth of the iteration,
+            // so that long series of checks still end.
+            let extension = (self.features.check_extensions
+                && gives_check
+                && ply < 2 * (self.root_depth + 1)) as i32;
+            let next_depth = depth - 1 + extension;
+
+            let reduction = if self.features.late_move_reductions
+                && i >= LMR_MIN_MOVES
+                && depth >= LMR_MIN_DEPTH
+                && is_quiet
+                && !in_check
+                && alpha > f64::NEG_INFINITY
+            {
+                if i >= 2 * LMR_MIN_MOVES && depth >= 2 * LMR_MIN_DEPTH {
+                    2
+                } else {
+                    1
+                }
+            } else {
+                0
+            };
+
+            let mut value = f64::INFINITY;
+            if reduction > 0 {
+                value = -self.negamax(
+                    &next_board,
+                    next_depth - reduction,
+                    ply + 1,
+                    -alpha - NULL_WINDOW,
+                    -alpha,
+                    false,
+                );
+            }
+            // Moves that aren't reduced, or that were better than expected
+            // after a reduced search, are searched at full depth.
+            if value > alpha {
+                value = -self.negamax(&next_board, next_depth, ply + 1, -beta, -alpha, false);
+            }
+            if self.stop.load(Ordering::Relaxed) {
+                // The values of a stopped search are wrong, so they aren't
+                // stored either.
+                return 0.0;
+            }
+            if value > best_value {
+                best_value = value;
+                best_move = Some(m);
+            }
+            if value > alpha {
+                alpha = value;
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_value <= original_alpha {
+            Bound::Upper
+        } else if best_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        // A node where every move failed low has no best move worth
+        // trying first.
+        let entry = Entry {
+            value: value_to_table(best_value, ply),
+            depth,
+            bound,
+            best_move: best_move.filter(|_| bound != Bound::Upper),
+            generation: 0,
+        };
+        self.table.store(key, board, entry);
+        best_value
+    }
+}
+
+// Is a move neither a capture nor a promotion?
+fn is_quiet(board: &Board, m: Move) -> bool {
+    match m {
+        Move::Piece(from, to) => {
+            board.get_piece(to).is_none()
+                && !board.get_piece(from).is_some_and(|piece| {
+                    // Pawns that move sideways capture, even en passant.
+                    piece.is_pawn()
+                        && (from.get_col() != to.get_col()
+                            || to.get_row() == 0
+                            || to.get_row() == 7)
+                })
+        }
+        Move::Promotion(_, _, _) => false,
+        _ => true,
+    }
+}
+
+// Can a player pass for null move pruning? Not in check, and not with only
+// pawns left, where passing may well be better than any move.
+#[inline]
+fn can_pass(board: &Board, color: Color, in_check: bool) -> bool {
+    !in_check && has_pieces(board, color)
+}
+
+// Does a player have pieces other than pawns and their king?
+fn has_pieces(board: &Board, color: Color) -> bool {
+    (0..8).any(|row| {
+        (0..8).any(|col| {
+            board
+                .get_piece(Position::new(row, col))
+                .is_some_and(|piece| {
+                    piece.get_color() == color && !piece.is_pawn() && !piece.is_king()
+                })
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    // Forced mates, with the number of plies until mate.
+    const MATES: [(&str, i32); 4] = [
+        ("k2K4/8/8/8/8/3Q4/8/8 w - - 0 1", 3),
+        ("6k1/8/5K2/8/8/7R/8/8 w - - 0 1", 3),
+        ("8/8/8/8/8/7K/4R3/6k1 w - - 0 1", 5),
+        // Qg8+ Rxg8 Nf7#.
+        ("5r1k/6pp/7N/3Q4/8/8/5PPP/6K1 w - - 0 1", 3),
+    ];
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    // Check that a search found the mate of a position in `plies`.
+    fn assert_mates(board: &Board, result: &SearchResult, plies: i32, context: &str) {
+        assert_eq!(
+            get_mate_plies(result.get_value()),
+            Some(plies),
+            "{}",
+            context
+        );
+        let mut line = *board;
+        for m in result.get_pv().iter().take(plies as usize) {
+            assert!(line.get_legal_moves().contains(m), "{}", context);
+            line = line.apply_eval_move(*m);
+        }
+        if result.get_pv().len() >= plies as usize {
+            assert!(line.is_checkmate(), "{}: {:?}", context, result.get_pv());
+        }
+    }
+
+    #[test]
+    fn node_limits_are_kept_within_a_check_interval() {
+        let params = EvalParams::default();
+        for threads in [1, 3] {
+            let search = Search::new(threads, 1);
+            let limits = SearchLimits::new().with_nodes(5000);
+            let result = search.run_with_limits(&Board::new(), &limits, &params);
+            assert!(result.get_nodes() >= 5000, "{} threads", threads);
+            assert!(
+                result.get_nodes() <= 5000 + threads as u64 * CHECK_INTERVAL,
+                "{} threads searched {} nodes",
+                threads,
+                result.get_nodes()
+            );
+            assert!(Board::new()
+                .get_legal_moves()
+                .contains(&result.get_best_move()));
+        }
+    }
+
+    #[test]
+    fn stopped_searches_still_play_a_legal_move() {
+        let params = EvalParams::default();
+        let board = board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+
+        let limits = SearchLimits::new();
+        limits.stop();
+        let result = Search::default().run_with_limits(&board, &limits, &params);
+        assert!(board.get_legal_moves().contains(&result.get_best_move()));
+
+        let start = Instant::now();
+        let limits = SearchLimits::new().with_time(Duration::from_millis(1));
+        let result = Search::default().run_with_limits(&board, &limits, &params);
+        assert!(
+            start.elapsed() < Duration::from_millis(500),
+            "{:?}",
+            start.elapsed()
+        );
+        assert!(board.get_legal_moves().contains(&result.get_best_move()));
+    }
+
+    #[test]
+    fn every_feature_combination_finds_the_mates() {
+        let params = EvalParams::default();
+        for combination in 0..1 << SearchFeatures::NAMES.len() {
+            let mut features = SearchFeatures::none();
+            for (i, name) in SearchFeatures::NAMES.iter().enumerate() {
+                features.set(name, combination & 1 << i != 0).unwrap();
+            }
+            for (fen, plies) in MATES {
+                let mut search = Search::new(1, 1);
+                search.set_features(features);
+                let board = board(fen);
+                let result = search.run(&board, plies, &params);
+                assert_mates(
+                    &board,
+                    &result,
+                    plies,
+                    &format!("`{}` with {:?}", fen, features),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn null_moves_are_skipped_in_check_and_pawn_endings() {
+        // The player to move only has pawns, and so does the other one, so
+        // the search must be the same with null moves or without.
+        let params = EvalParams::default();
+        let board = board("8/5k2/1p4p1/p2p3p/P2P3P/1P4P1/5K2/8 w - - 0 1");
+        let results = [SearchFeatures::all(), {
+            let mut features = SearchFeatures::all();
+            features.set("null-move", false).unwrap();
+            features
+        }]
+        .map(|features| {
+            let mut search = Search::new(1, 1);
+            search.set_features(features);
+            search.run(&board, 5, &params)
+        });
+        assert_eq!(results[0], results[1]);
+
+        assert!(!can_pass(&board, WHITE, false));
+        let checked = Board::from_fen("4k3/8/8/8/8/8/3q4/4K2R w - - 0 1").unwrap();
+        assert!(checked.is_in_check(WHITE));
+        assert!(!can_pass(&checked, WHITE, true));
+        assert!(can_pass(&checked, WHITE, false));
+    }
+
+    #[test]
+    fn table_entries_round_trip() {
+        let board = Board::new();
+        let m = board.parse_uci("g1f3").unwrap();
+        for (value, bound, best_move) in [
+            (1.25, Bound::Exact, Some(m)),
+            (-0.5, Bound::Lower, None),
+            (MATE_VALUE - 7.0, Bound::Upper, Some(Move::KingSideCastle)),
+        ] {
+            let entry = Entry {
+                value,
+                depth: 12,
+                bound,
+                best_move,
+                generation: 63,
+            };
+            assert_eq!(Entry::unpack(entry.pack(), &board), entry);
+        }
+
+        let promotion = Board::from_fen("8/4P3/8/8/8/8/k7/4K3 w - - 0 1").unwrap();
+        let m = promotion.parse_uci("e7e8n").unwrap();
+        assert_eq!(decode_move(encode_move(m), &promotion), Some(m));
+    }
+
+    #[test]
+    fn mate_values_are_stored_relative_to_their_position() {
+        let table = TranspositionTable::new(1);
+        let board = Board::new();
+        let key = board.get_zobrist_key();
+
+        // Mated 5 plies from the root, in a position found 2 plies from it.
+        let value = -(MATE_VALUE - 5.0);
+        let entry = Entry {
+            value: value_to_table(value, 2),
+            depth: 4,
+            bound: Bound::Exact,
+            best_move: None,
+            generation: 0,
+        };
+        table.store(key, &board, entry);
+        let stored = table.probe(key, &board).unwrap();
+        assert_eq!(get_mate_plies(stored.value), Some(-3));
+        assert_eq!(value_from_table(stored.value, 2), value);
+        // Found again 4 plies from a later root, it is mated 7 plies away.
+        assert_eq!(get_mate_plies(value_from_table(stored.value, 4)), Some(-7));
+        assert_eq!(value_to_table(0.5, 3), 0.5);
+        assert_eq!(value_from_table(0.5, 3), 0.5);
+    }
+
+    #[test]
+    fn older_searches_are_replaced() {
+        let table = TranspositionTable::new(1);
+        let board = Board::new();
+        let key = board.get_zobrist_key();
+        let entry = |depth, bound| Entry {
+            value: depth as f64,
+            depth,
+            bound,
+            best_move: None,
+            generation: 0,
+        };
+
+        table.store(key, &board, entry(8, Bound::Lower));
+        // A shallower bound of the same search is not worth more.
+        table.store(key, &board, entry(3, Bound::Upper));
+        assert_eq!(table.probe(key, &board).unwrap().depth, 8);
+        // An exact value always is.
+        table.store(key, &board, entry(5, Bound::Exact));
+        assert_eq!(table.probe(key, &board).unwrap().depth, 5);
+
+        // Anything from a new search replaces what older ones found.
+        table.store(key, &board, entry(9, Bound::Lower));
+        table.new_search();
+        table.store(key, &board, entry(2, Bound::Upper));
+        let stored = table.probe(key, &board).unwrap();
+        assert_eq!((stored.depth, stored.bound), (2, Bound::Upper));
+        assert_eq!(stored.generation, 1);
+
+        table.clear();
+        assert!(table.probe(key, &board).is_none());
+    }
+
+    #[test]
+    fn threads_find_the_same_mates() {
+        let params = EvalParams::default();
+        for (fen, plies) in MATES {
+            let board = board(fen);
+            for threads in [1, 4] {
+                let result = Search::new(threads, 1).run(&board, plies, &params);
+                assert_mates(
+                    &board,
+                    &result,
+                    plies,
+                    &format!("`{}` with {} threads", fen, threads),
+                );
+            }
+        }
+    }
+}
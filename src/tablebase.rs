@@ -0,0 +1,1509 @@
+use crate::{
+    board::Board,
+    game::{Evaluate, Move},
+    piece::{Color, Piece, Position},
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex, OnceLock, RwLock,
+};
+
+// The tablebase used by the search. This is a global rather than a thread
+// local like the opening book, because it is probed from inside the search
+// and the tables are large enough that they should only ever be loaded once.
+static TABLEBASE: RwLock<Option<Arc<Tablebase>>> = RwLock::new(None);
+// The largest number of pieces of the tables of `TABLEBASE`, kept apart so
+// that the search can skip the positions with more pieces without a lock.
+static TABLEBASE_PIECES: AtomicUsize = AtomicUsize::new(0);
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xE8, 0x23, 0x5D];
+const DTZ_MAGIC: [u8; 4] = [0xD7, 0x66, 0x0C, 0xA5];
+
+// Flags stored in front of the compressed data of a table.
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// The value of a won tablebase position for the search. This is far more
+// than any material advantage, but less than the value of a checkmate.
+pub const TABLEBASE_WIN_VALUE: f64 = 5000.0;
+
+// Syzygy tables never have more than 7 pieces.
+const MAX_PIECES: usize = 7;
+
+// The game theoretical value of a position, for the player to move.
+//
+// A cursed win is a win that takes more than 50 moves without a capture or
+// pawn move, and a blessed loss is the opposite. This engine does not play
+// with the fifty-move rule, so the search treats them as a win and a loss.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss,
+    BlessedLoss,
+    Draw,
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            i32::MIN..=-2 => Self::Loss,
+            -1 => Self::BlessedLoss,
+            0 => Self::Draw,
+            1 => Self::CursedWin,
+            _ => Self::Win,
+        }
+    }
+
+    fn get_value(&self) -> i32 {
+        match self {
+            Self::Loss => -2,
+            Self::BlessedLoss => -1,
+            Self::Draw => 0,
+            Self::CursedWin => 1,
+            Self::Win => 2,
+        }
+    }
+
+    #[inline]
+    pub fn is_win(&self) -> bool {
+        *self > Self::Draw
+    }
+
+    #[inline]
+    pub fn is_loss(&self) -> bool {
+        *self < Self::Draw
+    }
+
+    // Get the value of this result for the search.
+    #[inline]
+    pub fn get_score(&self) -> f64 {
+        if self.is_win() {
+            TABLEBASE_WIN_VALUE
+        } else if self.is_loss() {
+            -TABLEBASE_WIN_VALUE
+        } else {
+            0.0
+        }
+    }
+}
+
+impl core::ops::Neg for Wdl {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self::from_value(-self.get_value())
+    }
+}
+
+// The result of probing a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Probe {
+    wdl: Wdl,
+    dtz: i32,
+}
+
+impl Probe {
+    // Get the win, draw or loss value of the position for the player to move.
+    #[inline]
+    pub fn get_wdl(&self) -> Wdl {
+        self.wdl
+    }
+
+    // Get the distance to zeroing the fifty-move counter in plies, that is
+    // the number of plies until the next capture or pawn move when both
+    // players play well. Positive when winning, negative when losing and
+    // zero when drawn.
+    #[inline]
+    pub fn get_dtz(&self) -> i32 {
+        self.dtz
+    }
+}
+
+// Use a tablebase for the search and `probe`, or stop using one with `None`.
+//
+// The tablebase is shared, so the same loaded tables can be switched on and
+// off again without reading them back from the disk.
+pub fn set_tablebase(tablebase: Option<Arc<Tablebase>>) {
+    let pieces = tablebase
+        .as_ref()
+        .map_or(0, |tablebase| tablebase.get_max_pieces());
+    *TABLEBASE.write().unwrap_or_else(|e| e.into_inner()) = tablebase;
+    TABLEBASE_PIECES.store(pieces, Ordering::Relaxed);
+}
+
+// Get the largest number of pieces, kings included, of the tables set with
+// `set_tablebase`, or 0 without a tablebase. Positions with more pieces are
+// not worth probing.
+#[inline]
+pub fn get_tablebase_pieces() -> usize {
+    TABLEBASE_PIECES.load(Ordering::Relaxed)
+}
+
+// Get the tablebase used by the search, if one is set.
+pub fn get_tablebase() -> Option<Arc<Tablebase>> {
+    TABLEBASE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+// Probe a position in the tablebase set with `set_tablebase`.
+pub fn probe(board: &Board) -> Option<Probe> {
+    get_tablebase().and_then(|tablebase| tablebase.probe(board))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+// Tables are known by their material, like `KRvK`, and kind.
+type TableKey = (String, TableKind);
+
+// What the tablebase answered for a position, besides its value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProbeState {
+    Ok,
+    // The best move is a capture or pawn move, so the DTZ table can not be
+    // trusted for this position.
+    ZeroingBestMove,
+    // The DTZ table only stores the other player's positions.
+    ChangeStm,
+}
+
+// A set of Syzygy tablebase files in local directories.
+//
+// Tables are only read from disk the first time a position needs them.
+#[derive(Debug, Default)]
+pub struct Tablebase {
+    // The paths of the `.rtbw` and `.rtbz` files, by material like `KRvK`.
+    files: HashMap<TableKey, PathBuf>,
+    loaded: Mutex<HashMap<TableKey, Option<Arc<Table>>>>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    // Find all the Syzygy tables in a local directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let mut tablebase = Self::default();
+        tablebase.add_directory(path)?;
+        Ok(tablebase)
+    }
+
+    // Add the Syzygy tables in another local directory.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, String> {
+        let path = path.as_ref();
+        let entries = std::fs::read_dir(path)
+            .map_err(|e| format!("could not read directory `{}`: {}", path.display(), e))?;
+
+        let mut count = 0;
+        for entry in entries.flatten() {
+            let file = entry.path();
+            let kind = match file.extension().and_then(|e| e.to_str()) {
+                Some("rtbw") => TableKind::Wdl,
+                Some("rtbz") => TableKind::Dtz,
+                _ => continue,
+            };
+            let name = match file.file_stem().and_then(|s| s.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let material = match Material::parse(&name) {
+                Some(material) => material,
+                None => continue,
+            };
+
+            self.max_pieces = self.max_pieces.max(material.piece_count);
+            self.files.insert((name, kind), file);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    // Get the largest number of pieces, kings included, of the tables found.
+    #[inline]
+    pub fn get_max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // Can this position be probed at all? Tables do not know about castling.
+    pub fn can_probe(&self, board: &Board) -> bool {
+        let count = board.get_piece_count();
+        (2..=self.max_pieces).contains(&count)
+            && !board.has_kingside_castling_right(Color::White)
+            && !board.has_queenside_castling_right(Color::White)
+            && !board.has_kingside_castling_right(Color::Black)
+            && !board.has_queenside_castling_right(Color::Black)
+    }
+
+    // Get the win, draw or loss value of a position for the player to move.
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.search(board, false).map(|(wdl, _)| wdl)
+    }
+
+    // Get the distance to zeroing of a position, see `Probe::get_dtz`.
+    //
+    // A mated position has a DTZ of -1.
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        self.dtz(board)
+    }
+
+    // Get both the WDL and DTZ value of a position.
+    pub fn probe(&self, board: &Board) -> Option<Probe> {
+        if !self.can_probe(board) {
+            return None;
+        }
+        let (wdl, _) = self.search(board, false)?;
+        let dtz = self.dtz(board)?;
+        Some(Probe { wdl, dtz })
+    }
+
+    // Get the move that keeps the best result and makes the most progress
+    // towards it, along with the value of the position.
+    //
+    // Winning moves are picked by the shortest distance to zeroing, so that
+    // the game is actually won, and losing moves by the longest one, so that
+    // the opponent has to prove the win.
+    pub fn get_best_move(&self, board: &Board) -> Option<(Move, Wdl)> {
+        if !self.can_probe(board) {
+            return None;
+        }
+
+        let mut best: Option<(Move, Wdl, i32)> = None;
+        for m in board.get_legal_moves() {
+            let next = board.apply_eval_move(m);
+            let (wdl, dtz) = if next.is_checkmate() {
+                (Wdl::Win, 1)
+            } else if is_zeroing(board, m) {
+                let wdl = -self.search(&next, false)?.0;
+                (wdl, dtz_before_zeroing(wdl))
+            } else {
+                let (wdl, _) = self.search(&next, false)?;
+                let dtz = -self.dtz(&next)?;
+                (-wdl, dtz + dtz.signum())
+            };
+
+            let better = match best {
+                None => true,
+                Some((_, best_wdl, best_dtz)) => {
+                    wdl > best_wdl
+                        || wdl == best_wdl && wdl != Wdl::Draw && dtz < best_dtz
+                }
+            };
+            if better {
+                best = Some((m, wdl, dtz));
+            }
+        }
+
+        best.map(|(m, wdl, _)| (m, wdl))
+    }
+
+    // Probe the WDL value of a position, resolving captures first.
+    //
+    // The generator stores "don't care" values for positions where the
+    // player to move has a winning capture, and tables know nothing about
+    // en-passant, so captures (and pawn moves for DTZ) are searched first.
+    fn search(&self, board: &Board, check_zeroing: bool) -> Option<(Wdl, ProbeState)> {
+        let moves = board.get_legal_moves();
+        let mut best = Wdl::Loss;
+        let mut count = 0;
+
+        for m in &moves {
+            if !is_capture(board, *m) && (!check_zeroing || !is_pawn_move(board, *m)) {
+                continue;
+            }
+            count += 1;
+
+            let (value, _) = self.search(&board.apply_eval_move(*m), false)?;
+            let value = -value;
+            if value > best {
+                best = value;
+                if value >= Wdl::Win {
+                    return Some((value, ProbeState::ZeroingBestMove));
+                }
+            }
+        }
+
+        // If every legal move was searched, the stored value can be wrong.
+        let no_more_moves = count > 0 && count == moves.len();
+        let value = if no_more_moves {
+            best
+        } else {
+            self.probe_wdl_table(board)?
+        };
+
+        if best >= value {
+            let state = if best > Wdl::Draw || no_more_moves {
+                ProbeState::ZeroingBestMove
+            } else {
+                ProbeState::Ok
+            };
+            return Some((best, state));
+        }
+
+        Some((value, ProbeState::Ok))
+    }
+
+    fn dtz(&self, board: &Board) -> Option<i32> {
+        let (wdl, state) = self.search(board, true)?;
+
+        // DTZ tables do not store draws.
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+
+        if state == ProbeState::ZeroingBestMove {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        let (dtz, state) = self.probe_dtz_table(board, wdl)?;
+        if state != ProbeState::ChangeStm {
+            let cursed = wdl == Wdl::BlessedLoss || wdl == Wdl::CursedWin;
+            return Some((dtz + if cursed { 100 } else { 0 }) * wdl.get_value().signum());
+        }
+
+        // The table stores the other player's positions, so do a one move
+        // search for the move that keeps the result with the lowest DTZ.
+        let mut min_dtz = i32::MAX;
+        for m in board.get_legal_moves() {
+            let zeroing = is_zeroing(board, m);
+            let next = board.apply_eval_move(m);
+
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&next, false)?.0)
+            } else {
+                -self.dtz(&next)?
+            };
+
+            // If the move mates, the DTZ is one
+            if dtz == 1 && next.is_checkmate() {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.get_value().signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        // With no legal moves the position is mate.
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Option<Wdl> {
+        if board.get_piece_count() == 2 {
+            return Some(Wdl::Draw);
+        }
+
+        let (table, black_stronger) = self.get_table(board, TableKind::Wdl)?;
+        match table.probe(board, black_stronger, Wdl::Draw)? {
+            (value, ProbeState::Ok) => Some(Wdl::from_value(value - 2)),
+            _ => None,
+        }
+    }
+
+    fn probe_dtz_table(&self, board: &Board, wdl: Wdl) -> Option<(i32, ProbeState)> {
+        let (table, black_stronger) = self.get_table(board, TableKind::Dtz)?;
+        table.probe(board, black_stronger, wdl)
+    }
+
+    // Find the table for the material on the board, and whether it is stored
+    // with the colors swapped.
+    fn get_table(&self, board: &Board, kind: TableKind) -> Option<(Arc<Table>, bool)> {
+        let white = material_name(board, Color::White);
+        let black = material_name(board, Color::Black);
+
+        for (name, black_stronger) in [
+            (format!("{}v{}", white, black), false),
+            (format!("{}v{}", black, white), true),
+        ] {
+            if let Some(table) = self.load_table(&name, kind) {
+                return Some((table, black_stronger && white != black));
+            }
+        }
+
+        None
+    }
+
+    fn load_table(&self, name: &str, kind: TableKind) -> Option<Arc<Table>> {
+        let key = (name.to_string(), kind);
+        let path = self.files.get(&key)?;
+
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        loaded
+            .entry(key)
+            .or_insert_with(|| {
+                let material = Material::parse(name)?;
+                let data = std::fs::read(path).ok()?;
+                Table::new(data, kind, material).ok().map(Arc::new)
+            })
+            .clone()
+    }
+}
+
+// Get the material of one player in the order used by table names: `KQRBNP`.
+fn material_name(board: &Board, color: Color) -> String {
+    let mut counts = [0; 6];
+    for square in board.squares() {
+        if let Some(piece) = square.get_piece() {
+            if piece.get_color() == color {
+                counts[piece_type(piece) - 1] += 1;
+            }
+        }
+    }
+
+    let mut name = String::new();
+    for (i, c) in ['P', 'N', 'B', 'R', 'Q', 'K'].iter().enumerate().rev() {
+        for _ in 0..counts[i] {
+            name.push(*c);
+        }
+    }
+    name
+}
+
+#[inline]
+fn is_capture(board: &Board, m: Move) -> bool {
+    match m {
+        Move::Piece(from, to) | Move::Promotion(from, to, _) => {
+            board.has_enemy_piece(to, board.get_turn_color())
+                || board.get_en_passant() == Some(to)
+                    && board.get_piece(from).is_some_and(|piece| piece.is_pawn())
+        }
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_pawn_move(board: &Board, m: Move) -> bool {
+    match m {
+        Move::Piece(from, _) | Move::Promotion(from, _, _) => {
+            board.get_piece(from).is_some_and(|piece| piece.is_pawn())
+        }
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_zeroing(board: &Board, m: Move) -> bool {
+    is_capture(board, m) || is_pawn_move(board, m)
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// The piece type numbers used in table files. Black pieces have 8 added.
+fn piece_type(piece: Piece) -> usize {
+    match piece {
+        Piece::Pawn(_, _) => 1,
+        Piece::Knight(_, _) => 2,
+        Piece::Bishop(_, _) => 3,
+        Piece::Rook(_, _) => 4,
+        Piece::Queen(_, _) => 5,
+        Piece::King(_, _) => 6,
+    }
+}
+
+fn piece_code(piece: Piece) -> u8 {
+    piece_type(piece) as u8
+        + match piece.get_color() {
+            Color::White => 0,
+            Color::Black => 8,
+        }
+}
+
+/* =================================================================================
+=================================================================================*/
+
+// Squares are numbered from A1 = 0 to H8 = 63, rank by rank.
+#[inline]
+fn rank_of(sq: usize) -> usize {
+    sq >> 3
+}
+
+#[inline]
+fn file_of(sq: usize) -> usize {
+    sq & 7
+}
+
+// How far a square is above the A1-H8 diagonal, negative when below.
+#[inline]
+fn off_diagonal(sq: usize) -> i32 {
+    rank_of(sq) as i32 - file_of(sq) as i32
+}
+
+// The tables used to turn positions into indices, shared by all tables.
+struct Indices {
+    // Squares A2-H7 to the number of squares left for the other pawns when
+    // the leading pawn stands on it.
+    map_pawns: [usize; 64],
+    // Squares below the A1-H8 diagonal to 0..28.
+    map_b1h1h7: [usize; 64],
+    // Squares in the A1-D1-D4 triangle to 0..10, the diagonal last.
+    map_a1d1d4: [usize; 64],
+    // The 462 legal placements of two kings, the first in the triangle.
+    map_kk: [[usize; 64]; 10],
+    // `binomial[k][n]` ways of choosing `k` out of `n` squares.
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+fn indices() -> &'static Indices {
+    static INDICES: OnceLock<Indices> = OnceLock::new();
+    INDICES.get_or_init(|| {
+        let mut ix = Indices {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for sq in 0..64 {
+            if off_diagonal(sq) < 0 {
+                ix.map_b1h1h7[sq] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = vec![];
+        code = 0;
+        for sq in 0..=27 {
+            if off_diagonal(sq) < 0 && file_of(sq) <= 3 {
+                ix.map_a1d1d4[sq] = code;
+                code += 1;
+            } else if off_diagonal(sq) == 0 && file_of(sq) <= 3 {
+                diagonal.push(sq);
+            }
+        }
+        for sq in diagonal {
+            ix.map_a1d1d4[sq] = code;
+            code += 1;
+        }
+
+        // If the first king is on the diagonal, the other one is never
+        // above it. Placements with both kings on the diagonal come last.
+        let mut both_on_diagonal = vec![];
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..=27 {
+                if ix.map_a1d1d4[s1] != idx || (idx == 0 && s1 != 1) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    let distance = (rank_of(s1) as i32 - rank_of(s2) as i32)
+                        .abs()
+                        .max((file_of(s1) as i32 - file_of(s2) as i32).abs());
+                    if distance <= 1 || off_diagonal(s1) == 0 && off_diagonal(s2) > 0 {
+                        continue;
+                    }
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        ix.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            ix.map_kk[idx][s2] = code;
+            code += 1;
+        }
+
+        ix.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                ix.binomial[k][n] = if k > 0 { ix.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { ix.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 47;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..=6 {
+                    let sq = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        ix.map_pawns[sq] = available;
+                        available = available.saturating_sub(1);
+                        ix.map_pawns[sq ^ 7] = available;
+                        available = available.saturating_sub(1);
+                    }
+                    ix.lead_pawn_idx[lead_pawns][sq] = idx;
+                    idx += ix.binomial[lead_pawns - 1][ix.map_pawns[sq]];
+                }
+                ix.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+
+        ix
+    })
+}
+
+// The material of a table, parsed from its name like `KRPvKR`.
+#[derive(Clone, Debug)]
+struct Material {
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // The pawns of the leading color, then of the other color.
+    pawn_count: [usize; 2],
+    symmetric: bool,
+}
+
+impl Material {
+    fn parse(name: &str) -> Option<Self> {
+        let mut sides = name.split('v');
+        let (white, black) = (sides.next()?, sides.next()?);
+        if sides.next().is_some() || !white.starts_with('K') || !black.starts_with('K') {
+            return None;
+        }
+
+        let mut counts = [[0; 7]; 2];
+        for (side, chars) in [white, black].iter().enumerate() {
+            for c in chars.chars() {
+                let kind = match c {
+                    'P' => 1,
+                    'N' => 2,
+                    'B' => 3,
+                    'R' => 4,
+                    'Q' => 5,
+                    'K' => 6,
+                    _ => return None,
+                };
+                counts[side][kind] += 1;
+            }
+        }
+
+        let piece_count = white.len() + black.len();
+        if piece_count > MAX_PIECES || counts[0][6] != 1 || counts[1][6] != 1 {
+            return None;
+        }
+
+        let (white_pawns, black_pawns) = (counts[0][1], counts[1][1]);
+        let white_leads = black_pawns == 0 || white_pawns > 0 && black_pawns >= white_pawns;
+
+        Some(Self {
+            piece_count,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: counts.iter().any(|side| side[1..6].contains(&1)),
+            pawn_count: if white_leads {
+                [white_pawns, black_pawns]
+            } else {
+                [black_pawns, white_pawns]
+            },
+            symmetric: white == black,
+        })
+    }
+}
+
+// The compressed data for one player and one leading pawn file of a table.
+#[derive(Clone, Debug, Default)]
+struct PairsData {
+    flags: u8,
+    pieces: [u8; MAX_PIECES],
+    group_len: [usize; MAX_PIECES + 1],
+    group_idx: [u64; MAX_PIECES + 1],
+    // The shortest symbol length, or the only value of a single value table.
+    min_sym_len: u8,
+    block_size: u64,
+    span: u64,
+    sparse_index_size: u64,
+    num_blocks: u64,
+    block_length_size: u64,
+    lowest_sym: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    btree: usize,
+    sparse_index: usize,
+    block_lengths: usize,
+    data: usize,
+    // Offsets of the DTZ value maps for each kind of result.
+    map_idx: [usize; 4],
+}
+
+// A single Syzygy table file, read into memory.
+#[derive(Debug)]
+struct Table {
+    data: Vec<u8>,
+    kind: TableKind,
+    material: Material,
+    // Indexed by player to move, then by the file of the leading pawn.
+    items: Vec<Vec<PairsData>>,
+}
+
+#[inline]
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, String> {
+    data.get(offset)
+        .copied()
+        .ok_or_else(|| String::from("unexpected end of table"))
+}
+
+#[inline]
+fn read_u16_le(data: &[u8], offset: usize) -> Result<u16, String> {
+    Ok(u16::from_le_bytes([read_u8(data, offset)?, read_u8(data, offset + 1)?]))
+}
+
+#[inline]
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(read_u16_le(data, offset)? as u32 | (read_u16_le(data, offset + 2)? as u32) << 16)
+}
+
+#[inline]
+fn read_u32_be(data: &[u8], offset: usize) -> Result<u32, String> {
+    Ok(read_u32_le(data, offset)?.swap_bytes())
+}
+
+impl Table {
+    fn new(data: Vec<u8>, kind: TableKind, material: Material) -> Result<Self, String> {
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if data.len() < 5 || data[..4] != magic {
+            return Err(String::from("invalid table magic"));
+        }
+
+        let flags = data[4];
+        if (flags & 2 != 0) != material.has_pawns || (flags & 1 != 0) == material.symmetric {
+            return Err(String::from("table does not match its material"));
+        }
+
+        let sides = if kind == TableKind::Wdl && !material.symmetric { 2 } else { 1 };
+        let files = if material.has_pawns { 4 } else { 1 };
+        let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+
+        let mut items = vec![vec![PairsData::default(); files]; sides];
+        let mut p = 5;
+        for file in 0..files {
+            let first = read_u8(&data, p)?;
+            let second = if both_pawns { read_u8(&data, p + 1)? } else { 0xFF };
+            let order = [[first & 0xF, second & 0xF], [first >> 4, second >> 4]];
+            p += 1 + both_pawns as usize;
+
+            for k in 0..material.piece_count {
+                let byte = read_u8(&data, p)?;
+                for (side, item) in items.iter_mut().enumerate() {
+                    item[file].pieces[k] = if side == 0 { byte & 0xF } else { byte >> 4 };
+                }
+                p += 1;
+            }
+
+            for (side, item) in items.iter_mut().enumerate() {
+                set_groups(&material, &mut item[file], order[side], file);
+            }
+        }
+
+        p += p & 1;
+        for file in 0..files {
+            for item in items.iter_mut() {
+                p = set_sizes(&mut item[file], &data, p)?;
+            }
+        }
+
+        if kind == TableKind::Dtz {
+            for d in items[0].iter_mut() {
+                if d.flags & FLAG_MAPPED == 0 {
+                    continue;
+                }
+                if d.flags & FLAG_WIDE != 0 {
+                    p += p & 1;
+                    for i in 0..4 {
+                        d.map_idx[i] = p + 2;
+                        p += 2 * read_u16_le(&data, p)? as usize + 2;
+                    }
+                } else {
+                    for i in 0..4 {
+                        d.map_idx[i] = p + 1;
+                        p += read_u8(&data, p)? as usize + 1;
+                    }
+                }
+            }
+            p += p & 1;
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].sparse_index = p;
+                p += item[file].sparse_index_size as usize * 6;
+            }
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                item[file].block_lengths = p;
+                p += item[file].block_length_size as usize * 2;
+            }
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                p = (p + 0x3F) & !0x3F;
+                item[file].data = p;
+                p += (item[file].num_blocks * item[file].block_size) as usize;
+            }
+        }
+
+        if p > data.len() {
+            return Err(String::from("unexpected end of table"));
+        }
+
+        Ok(Self {
+            data,
+            kind,
+            material,
+            items,
+        })
+    }
+
+    #[inline]
+    fn get(&self, stm: usize, file: usize) -> &PairsData {
+        &self.items[stm % self.items.len()][if self.material.has_pawns { file } else { 0 }]
+    }
+
+    // Look up the stored value of a position.
+    //
+    // `black_stronger` is true when the position has the colors of the table
+    // swapped, like a position with KvKR for the KRvK table.
+    fn probe(&self, board: &Board, black_stronger: bool, wdl: Wdl) -> Option<(i32, ProbeState)> {
+        let ix = indices();
+
+        // Symmetric tables only store white to move.
+        let black_symmetric = board.get_turn_color() == Color::Black && self.material.symmetric;
+        let flip = black_symmetric || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ (board.get_turn_color() == Color::Black) as usize;
+
+        let mut squares = [0usize; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut lead_pawn_color = None;
+        let mut file = 0;
+
+        // Tables with pawns are split by the file of the leading pawn, the
+        // one closest to the edge and then the lowest rank.
+        if self.material.has_pawns {
+            let code = self.get(0, 0).pieces[0] ^ flip_color;
+            let color = if code < 8 { Color::White } else { Color::Black };
+            lead_pawn_color = Some(color);
+            for sq in 0..64 {
+                let pos = Position::new(rank_of(sq) as i32, file_of(sq) as i32);
+                if board.get_piece(pos) == Some(Piece::Pawn(color, pos)) {
+                    squares[size] = sq ^ flip_squares;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+
+            let mut lead = 0;
+            for i in 1..lead_pawns {
+                if ix.map_pawns[squares[i]] > ix.map_pawns[squares[lead]] {
+                    lead = i;
+                }
+            }
+            squares.swap(0, lead);
+            file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+
+        // DTZ tables only store one player to move.
+        if self.kind == TableKind::Dtz {
+            let flags = self.get(stm, file).flags;
+            if (flags & FLAG_STM) as usize != stm
+                && (!self.material.symmetric || self.material.has_pawns)
+            {
+                return Some((0, ProbeState::ChangeStm));
+            }
+        }
+
+        for sq in 0..64 {
+            let pos = Position::new(rank_of(sq) as i32, file_of(sq) as i32);
+            if let Some(piece) = board.get_piece(pos) {
+                if piece.is_pawn() && Some(piece.get_color()) == lead_pawn_color {
+                    continue;
+                }
+                if size == MAX_PIECES {
+                    return None;
+                }
+                squares[size] = sq ^ flip_squares;
+                pieces[size] = piece_code(piece) ^ flip_color;
+                size += 1;
+            }
+        }
+
+        let d = self.get(stm, file);
+
+        // Order the pieces like the table does.
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // Mirror the board so that the leading piece is on files A-D.
+        if file_of(squares[0]) > 3 {
+            for sq in squares.iter_mut().take(size) {
+                *sq ^= 7;
+            }
+        }
+
+        let mut idx: u64;
+        if self.material.has_pawns {
+            idx = ix.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|sq| ix.map_pawns[*sq]);
+            for (i, sq) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += ix.binomial[i][ix.map_pawns[*sq]];
+            }
+        } else {
+            // Mirror the board so the leading piece is on ranks 1-4, then
+            // along the diagonal so the first piece off it is below it.
+            if rank_of(squares[0]) > 3 {
+                for sq in squares.iter_mut().take(size) {
+                    *sq ^= 56;
+                }
+            }
+
+            for i in 0..d.group_len[0] {
+                if off_diagonal(squares[i]) == 0 {
+                    continue;
+                }
+                if off_diagonal(squares[i]) > 0 {
+                    for sq in squares.iter_mut().take(size).skip(i) {
+                        *sq = ((*sq >> 3) | (*sq << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.material.has_unique_pieces {
+                let s = &squares;
+                let adjust1 = (s[1] > s[0]) as u64;
+                let adjust2 = (s[2] > s[0]) as u64 + (s[2] > s[1]) as u64;
+
+                idx = if off_diagonal(s[0]) != 0 {
+                    (ix.map_a1d1d4[s[0]] as u64 * 63 + (s[1] as u64 - adjust1)) * 62 + s[2] as u64
+                        - adjust2
+                } else if off_diagonal(s[1]) != 0 {
+                    (6 * 63 + rank_of(s[0]) as u64 * 28 + ix.map_b1h1h7[s[1]] as u64) * 62
+                        + s[2] as u64
+                        - adjust2
+                } else if off_diagonal(s[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank_of(s[0]) as u64 * 7 * 28
+                        + (rank_of(s[1]) as u64 - adjust1) * 28
+                        + ix.map_b1h1h7[s[2]] as u64
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank_of(s[0]) as u64 * 7 * 6
+                        + (rank_of(s[1]) as u64 - adjust1) * 6
+                        + (rank_of(s[2]) as u64 - adjust2)
+                };
+            } else {
+                idx = ix.map_kk[ix.map_a1d1d4[squares[0]]][squares[1]] as u64;
+            }
+        }
+
+        // Encode the remaining groups, pawns first, each sorted by square.
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.material.has_pawns && self.material.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let group_end = group_start + d.group_len[next];
+            squares[group_start..group_end].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..d.group_len[next] {
+                let sq = squares[group_start + i];
+                let adjust = squares[..group_start].iter().filter(|s| sq > **s).count();
+                let offset = if remaining_pawns { 8 } else { 0 };
+                n += ix.binomial[i + 1][sq - adjust - offset];
+            }
+
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        let value = self.decompress_pairs(d, idx).ok()? as i32;
+        Some((self.map_score(file, value, wdl).ok()?, ProbeState::Ok))
+    }
+
+    // Turn the stored value into a WDL value plus two, or a DTZ in plies.
+    fn map_score(&self, file: usize, value: i32, wdl: Wdl) -> Result<i32, String> {
+        if self.kind == TableKind::Wdl {
+            return Ok(value);
+        }
+
+        let d = self.get(0, file);
+        let mut value = value;
+        if d.flags & FLAG_MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Loss => 1,
+                Wdl::BlessedLoss => 3,
+                Wdl::Draw => 0,
+                Wdl::CursedWin => 2,
+                Wdl::Win => 0,
+            };
+            let base = d.map_idx[map];
+            value = if d.flags & FLAG_WIDE != 0 {
+                read_u16_le(&self.data, base + 2 * value as usize)? as i32
+            } else {
+                read_u8(&self.data, base + value as usize)? as i32
+            };
+        }
+
+        // Tables store moves rather than plies when nothing is lost by it.
+        if wdl == Wdl::Win && d.flags & FLAG_WIN_PLIES == 0
+            || wdl == Wdl::Loss && d.flags & FLAG_LOSS_PLIES == 0
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+
+        Ok(value + 1)
+    }
+
+    // Decode the value at `idx` from the Huffman coded, recursively paired
+    // symbols of a table.
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> Result<u16, String> {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return Ok(d.min_sym_len as u16);
+        }
+        let data = &self.data;
+
+        // Find the block holding `idx` from the closest sparse index entry.
+        let k = idx / d.span;
+        let entry = d.sparse_index + 6 * k as usize;
+        let mut block = read_u32_le(data, entry)? as usize;
+        let mut offset = read_u16_le(data, entry + 4)? as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        let block_length = |block: usize| -> Result<i64, String> {
+            Ok(read_u16_le(data, d.block_lengths + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or("invalid sparse index")?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + block * d.block_size as usize;
+        let mut buf64 = (read_u32_be(data, ptr)? as u64) << 32 | read_u32_be(data, ptr + 4)? as u64;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_sym_len = d.min_sym_len as usize;
+
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf64 < d.base64[len] {
+                len += 1;
+                if len == d.base64.len() {
+                    return Err(String::from("invalid symbol"));
+                }
+            }
+
+            sym = ((buf64 - d.base64[len]) >> (64 - len - min_sym_len)) as usize;
+            sym += read_u16_le(data, d.lowest_sym + 2 * len)? as usize;
+            if sym >= d.symlen.len() {
+                return Err(String::from("invalid symbol"));
+            }
+
+            if offset < d.symlen[sym] as i64 + 1 {
+                break;
+            }
+
+            offset -= d.symlen[sym] as i64 + 1;
+            len += min_sym_len;
+            buf64 = if len >= 64 { 0 } else { buf64 << len };
+            buf64_size -= len as i32;
+
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (read_u32_be(data, ptr)? as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        // Walk down the pairs until reaching the single value at `offset`.
+        while d.symlen[sym] != 0 {
+            let left = self.btree_left(d, sym)?;
+            if offset < d.symlen[left] as i64 + 1 {
+                sym = left;
+            } else {
+                offset -= d.symlen[left] as i64 + 1;
+                sym = self.btree_right(d, sym)?;
+            }
+        }
+
+        Ok(self.btree_left(d, sym)? as u16)
+    }
+
+    #[inline]
+    fn btree_left(&self, d: &PairsData, sym: usize) -> Result<usize, String> {
+        btree_left(&self.data, d.btree, sym)
+    }
+
+    #[inline]
+    fn btree_right(&self, d: &PairsData, sym: usize) -> Result<usize, String> {
+        btree_right(&self.data, d.btree, sym)
+    }
+}
+
+// Each symbol of the pairing tree is stored in 3 bytes: 12 bits for the
+// left child, then 12 bits for the right child.
+fn btree_left(data: &[u8], btree: usize, sym: usize) -> Result<usize, String> {
+    let p = btree + 3 * sym;
+    Ok(((read_u8(data, p + 1)? as usize & 0xF) << 8) | read_u8(data, p)? as usize)
+}
+
+fn btree_right(data: &[u8], btree: usize, sym: usize) -> Result<usize, String> {
+    let p = btree + 3 * sym;
+    Ok(((read_u8(data, p + 2)? as usize) << 4) | (read_u8(data, p + 1)? as usize >> 4))
+}
+
+// Split the pieces of a table into the groups that are encoded together,
+// and compute the index multiplier of every group.
+fn set_groups(material: &Material, d: &mut PairsData, order: [u8; 2], file: usize) {
+    let ix = indices();
+    let mut n = 0;
+    let mut first_len: i32 = if material.has_pawns {
+        0
+    } else if material.has_unique_pieces {
+        3
+    } else {
+        2
+    };
+    d.group_len[n] = 1;
+
+    for i in 1..material.piece_count {
+        first_len -= 1;
+        if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+            d.group_len[n] += 1;
+        } else {
+            n += 1;
+            d.group_len[n] = 1;
+        }
+    }
+    n += 1;
+    d.group_len[n] = 0;
+
+    let both_pawns = material.has_pawns && material.pawn_count[1] > 0;
+    let mut next = if both_pawns { 2 } else { 1 };
+    let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+    let mut idx = 1u64;
+
+    let mut k = 0;
+    while next < n || k == order[0] as usize || k == order[1] as usize {
+        if k == order[0] as usize {
+            d.group_idx[0] = idx;
+            idx *= if material.has_pawns {
+                ix.lead_pawns_size[d.group_len[0]][file]
+            } else if material.has_unique_pieces {
+                31332
+            } else {
+                462
+            };
+        } else if k == order[1] as usize {
+            d.group_idx[1] = idx;
+            idx *= ix.binomial[d.group_len[1]][48 - d.group_len[0]];
+        } else {
+            d.group_idx[next] = idx;
+            idx *= ix.binomial[d.group_len[next]][free_squares];
+            free_squares -= d.group_len[next];
+            next += 1;
+        }
+        k += 1;
+    }
+    d.group_idx[n] = idx;
+}
+
+// Read the block sizes and the Huffman code of a table, returning the
+// offset just past them.
+fn set_sizes(d: &mut PairsData, data: &[u8], mut p: usize) -> Result<usize, String> {
+    d.flags = read_u8(data, p)?;
+    p += 1;
+
+    if d.flags & FLAG_SINGLE_VALUE != 0 {
+        d.min_sym_len = read_u8(data, p)?;
+        return Ok(p + 1);
+    }
+
+    let groups = d.group_len.iter().position(|len| *len == 0).unwrap_or(MAX_PIECES);
+    let table_size = d.group_idx[groups];
+
+    d.block_size = 1 << read_u8(data, p)?.min(63);
+    d.span = 1 << read_u8(data, p + 1)?.min(63);
+    d.sparse_index_size = table_size.div_ceil(d.span);
+    let padding = read_u8(data, p + 2)? as u64;
+    d.num_blocks = read_u32_le(data, p + 3)? as u64;
+    d.block_length_size = d.num_blocks + padding;
+    let max_sym_len = read_u8(data, p + 7)? as usize;
+    d.min_sym_len = read_u8(data, p + 8)?;
+    p += 9;
+    d.lowest_sym = p;
+
+    let min_sym_len = d.min_sym_len as usize;
+    if max_sym_len < min_sym_len || max_sym_len + 1 > 64 || min_sym_len == 0 {
+        return Err(String::from("invalid symbol lengths"));
+    }
+
+    // Build the canonical Huffman code: longer symbols have lower values.
+    let count = max_sym_len - min_sym_len + 1;
+    let mut base64 = vec![0u64; count];
+    for i in (0..count - 1).rev() {
+        let lowest = read_u16_le(data, p + 2 * i)? as u64;
+        let next_lowest = read_u16_le(data, p + 2 * (i + 1))? as u64;
+        base64[i] = (base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest)) / 2;
+    }
+    for (i, base) in base64.iter_mut().enumerate() {
+        let shift = 64 - i - min_sym_len;
+        *base = if shift >= 64 { 0 } else { *base << shift };
+    }
+    d.base64 = base64;
+    p += 2 * count;
+
+    let symbols = read_u16_le(data, p)? as usize;
+    p += 2;
+    d.btree = p;
+    if data.len() < p + 3 * symbols {
+        return Err(String::from("unexpected end of table"));
+    }
+
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for sym in 0..symbols {
+        if !visited[sym] {
+            d.symlen[sym] = set_symlen(d, data, sym, &mut visited)?;
+        }
+    }
+
+    Ok(p + 3 * symbols + (symbols & 1))
+}
+
+// Get how many values, minus one, a symbol of the pairing tree expands to.
+fn set_symlen(d: &mut PairsData, data: &[u8], sym: usize, visited: &mut [bool]) -> Result<u8, String> {
+    visited[sym] = true;
+    let right = btree_right(data, d.btree, sym)?;
+    if right == 0xFFF {
+        return Ok(0);
+    }
+
+    let left = btree_left(data, d.btree, sym)?;
+    if left >= visited.len() || right >= visited.len() {
+        return Err(String::from("invalid symbol"));
+    }
+    if !visited[left] {
+        d.symlen[left] = set_symlen(d, data, left, visited)?;
+    }
+    if !visited[right] {
+        d.symlen[right] = set_symlen(d, data, right, visited)?;
+    }
+
+    Ok(d.symlen[left]
+        .wrapping_add(d.symlen[right])
+        .wrapping_add(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    // A directory of tables for a test, removed once it is done.
+    struct TestDirectory(PathBuf);
+
+    impl TestDirectory {
+        fn new(name: &str, files: &[(&str, &[u8])]) -> Self {
+            let path = env::temp_dir().join(format!("chess-{}-{}", name, std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            for (file, data) in files {
+                fs::write(path.join(file), data).unwrap();
+            }
+            Self(path)
+        }
+    }
+
+    impl Drop for TestDirectory {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    // The KQvK and KRvK tables, see `tests/fixtures/syzygy/generate.py`.
+    fn open_tables() -> Tablebase {
+        Tablebase::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/syzygy"))
+            .unwrap()
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn materials() {
+        let material = Material::parse("KRPvKR").unwrap();
+        assert_eq!(material.piece_count, 5);
+        assert!(material.has_pawns && material.has_unique_pieces);
+        assert_eq!(material.pawn_count, [1, 0]);
+        assert!(Material::parse("KQvKQ").unwrap().symmetric);
+        assert!(Material::parse("QvK").is_none());
+        assert!(Material::parse("KQRBNvKQR").is_none());
+
+        let board = board("8/8/4k3/3pp3/8/8/3P4/RB2K3 w - - 0 1");
+        assert_eq!(material_name(&board, Color::White), "KRBP");
+        assert_eq!(material_name(&board, Color::Black), "KPP");
+    }
+
+    #[test]
+    fn results() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert!(Wdl::CursedWin.is_win() && Wdl::BlessedLoss.is_loss());
+        assert_eq!(Wdl::CursedWin.get_score(), TABLEBASE_WIN_VALUE);
+        assert_eq!(Wdl::Loss.get_score(), -TABLEBASE_WIN_VALUE);
+    }
+
+    #[test]
+    fn probes_need_readable_tables() {
+        let directory = TestDirectory::new("tablebase", &[("KQvK.rtbw", b"not a table")]);
+        let tablebase = Tablebase::open(&directory.0).unwrap();
+        assert_eq!(tablebase.get_max_pieces(), 3);
+
+        // Bare kings are drawn without any table.
+        assert_eq!(
+            tablebase.probe_wdl(&board("8/8/8/4k3/8/8/8/4K3 w - - 0 1")),
+            Some(Wdl::Draw)
+        );
+        // A table that can't be read gives no answer rather than a wrong one.
+        assert_eq!(tablebase.probe_wdl(&board("k7/8/1K6/8/8/8/8/7Q w - - 0 1")), None);
+        // Positions with too many pieces or with castling rights are never
+        // probed.
+        assert!(!tablebase.can_probe(&board("k7/8/1K6/8/8/8/8/6RQ w - - 0 1")));
+        assert!(!tablebase.can_probe(&board("k7/8/8/8/8/8/8/4K2R w K - 0 1")));
+    }
+
+    #[test]
+    fn max_pieces_follow_the_directories() {
+        let mut tablebase = open_tables();
+        assert_eq!(tablebase.get_max_pieces(), 3);
+
+        let directory = TestDirectory::new("tablebase-pieces", &[("KRBvKN.rtbw", b"")]);
+        assert_eq!(tablebase.add_directory(&directory.0), Ok(1));
+        assert_eq!(tablebase.get_max_pieces(), 5);
+        assert!(Tablebase::default().get_max_pieces() == 0);
+    }
+
+    #[test]
+    fn kqvk() {
+        let tablebase = open_tables();
+
+        // Qh8# and Qb7# mate at once.
+        let mate_in_one = board("k7/8/1K6/8/8/8/8/7Q w - - 0 1");
+        let probe = tablebase.probe(&mate_in_one).unwrap();
+        assert_eq!((probe.get_wdl(), probe.get_dtz()), (Wdl::Win, 1));
+        let (m, wdl) = tablebase.get_best_move(&mate_in_one).unwrap();
+        assert_eq!(wdl, Wdl::Win);
+        assert!(mate_in_one.apply_eval_move(m).is_checkmate());
+
+        let mated = board("k6Q/8/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&mated), Some(Wdl::Loss));
+        assert_eq!(tablebase.probe_dtz(&mated), Some(-1));
+
+        // The queen is lost at once.
+        let hanging = board("kQ6/8/8/8/8/8/8/7K b - - 0 1");
+        let probe = tablebase.probe(&hanging).unwrap();
+        assert_eq!((probe.get_wdl(), probe.get_dtz()), (Wdl::Draw, 0));
+    }
+
+    #[test]
+    fn krvk() {
+        let tablebase = open_tables();
+
+        let won = board("8/8/8/3k4/8/8/8/R3K3 w - - 0 1");
+        let probe = tablebase.probe(&won).unwrap();
+        assert_eq!(probe.get_wdl(), Wdl::Win);
+        assert!(probe.get_dtz() > 1);
+
+        // The best move keeps the win, one ply closer to mate.
+        let (m, _) = tablebase.get_best_move(&won).unwrap();
+        let next = tablebase.probe(&won.apply_eval_move(m)).unwrap();
+        assert_eq!(next.get_wdl(), Wdl::Loss);
+        assert_eq!(next.get_dtz(), -(probe.get_dtz() - 1));
+
+        // Black to move takes the rook.
+        let drawn = board("8/8/8/8/8/8/8/Rk2K3 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&drawn), Some(Wdl::Draw));
+    }
+
+    #[test]
+    fn best_moves_mate_in_the_distance_to_zeroing() {
+        let tablebase = open_tables();
+        for fen in [
+            // Without captures or pawn moves, the distance to zeroing is
+            // the distance to mate.
+            "8/8/8/8/3k4/8/8/RK6 w - - 0 1",
+            "8/8/8/8/3k4/8/8/1KQ5 w - - 0 1",
+            // Black to move only probes the other side of the DTZ tables.
+            "8/8/3k4/8/8/8/8/R3K3 b - - 0 1",
+        ] {
+            let mut board = board(fen);
+            let mut dtz = tablebase.probe_dtz(&board).unwrap();
+            let expected_plies = dtz.abs();
+            let mut plies = 0;
+            while !board.is_checkmate() {
+                let (m, _) = tablebase.get_best_move(&board).unwrap();
+                board = board.apply_eval_move(m);
+                plies += 1;
+
+                // A mated position has a DTZ of -1 rather than 0.
+                let next = tablebase.probe_dtz(&board).unwrap();
+                let expected = if dtz == 1 { -1 } else { -(dtz - dtz.signum()) };
+                assert_eq!(next, expected, "`{}` after {} plies", fen, plies);
+                dtz = next;
+            }
+            assert_eq!(plies, expected_plies, "`{}`", fen);
+        }
+    }
+}
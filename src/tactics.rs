@@ -0,0 +1,500 @@
+use crate::{
+    board::{Board, BoardBuilder},
+    game::{Evaluate, GameResult, Move},
+    piece::{Color, Piece, Position, WHITE},
+};
+use std::fmt;
+
+// The lines a rook moves along, then the ones a bishop moves along.
+const ORTHOGONALS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const DIAGONALS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+// A tactical motif one player can use against the other, with the squares
+// of the pieces involved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Motif {
+    // A piece attacking two or more enemy pieces at once, that are either
+    // the king, worth more than it or undefended.
+    Fork {
+        piece: Position,
+        targets: Vec<Position>,
+    },
+    // An enemy piece that can't move off the line between a rook, bishop or
+    // queen and a more valuable piece behind it without losing that piece.
+    // The pin is absolute when the piece behind is the king, and moving off
+    // the line is illegal.
+    Pin {
+        attacker: Position,
+        pinned: Position,
+        target: Position,
+        absolute: bool,
+    },
+    // A rook, bishop or queen attacking an enemy piece which has a less
+    // valuable one behind it on the same line, won once the front one moves.
+    Skewer {
+        attacker: Position,
+        front: Position,
+        behind: Position,
+    },
+    // A piece standing between a rook, bishop or queen of the same color
+    // and an enemy target, that attacks the target by moving off the line.
+    DiscoveredAttack {
+        piece: Position,
+        attacker: Position,
+        target: Position,
+    },
+    // An enemy piece that is attacked and not defended at all.
+    Hanging {
+        piece: Position,
+        attackers: Vec<Position>,
+    },
+    // An enemy piece that is defended, but not well enough to keep it from
+    // being won, because it has too few defenders or a cheaper piece
+    // attacks it.
+    Underdefended {
+        piece: Position,
+        attackers: Vec<Position>,
+        defenders: Vec<Position>,
+    },
+    // The enemy king is stuck on its back row behind its own pieces, while
+    // a rook or queen could give check along it.
+    BackRankWeakness {
+        king: Position,
+    },
+    // A move that would checkmate the enemy king, if it were the player's
+    // turn to play it.
+    MateThreat {
+        m: Move,
+        from: Option<Position>,
+    },
+}
+
+impl Motif {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Self::Fork { .. } => "fork",
+            Self::Pin { absolute: true, .. } => "absolute pin",
+            Self::Pin { .. } => "relative pin",
+            Self::Skewer { .. } => "skewer",
+            Self::DiscoveredAttack { .. } => "discovered attack",
+            Self::Hanging { .. } => "hanging piece",
+            Self::Underdefended { .. } => "underdefended piece",
+            Self::BackRankWeakness { .. } => "back rank weakness",
+            Self::MateThreat { .. } => "mate threat",
+        }
+    }
+
+    // Get every square involved in the motif, starting with the piece
+    // carrying it out.
+    pub fn get_squares(&self) -> Vec<Position> {
+        match self {
+            Self::Fork { piece, targets } => {
+                let mut squares = vec![*piece];
+                squares.extend(targets);
+                squares
+            }
+            Self::Pin {
+                attacker,
+                pinned,
+                target,
+                ..
+            } => vec![*attacker, *pinned, *target],
+            Self::Skewer {
+                attacker,
+                front,
+                behind,
+            } => vec![*attacker, *front, *behind],
+            Self::DiscoveredAttack {
+                piece,
+                attacker,
+                target,
+            } => vec![*piece, *attacker, *target],
+            Self::Hanging { piece, attackers } => {
+                let mut squares = attackers.clone();
+                squares.push(*piece);
+                squares
+            }
+            Self::Underdefended {
+                piece,
+                attackers,
+                defenders,
+            } => {
+                let mut squares = attackers.clone();
+                squares.push(*piece);
+                squares.extend(defenders);
+                squares
+            }
+            Self::BackRankWeakness { king } => vec![*king],
+            Self::MateThreat { from, .. } => from.iter().copied().collect(),
+        }
+    }
+}
+
+impl fmt::Display for Motif {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let squares = self
+            .get_squares()
+            .iter()
+            .map(|pos| pos.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", self.get_name())?;
+        if !squares.is_empty() {
+            write!(f, " ({})", squares.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+// Find the motifs a player can use against the other in a position,
+// whether or not it is their turn.
+pub fn find_motifs(board: &Board, color: Color) -> Vec<Motif> {
+    let mut motifs = find_forks(board, color);
+    motifs.extend(find_lines(board, color));
+    motifs.extend(find_loose_pieces(board, color));
+    motifs.extend(find_back_rank_weakness(board, color));
+    motifs.extend(find_mate_threats(board, color));
+    motifs
+}
+
+// Find the motifs a move creates for the player making it: the ones in the
+// position after it that weren't there before, along with the attacks it
+// uncovers by moving off a line. Illegal moves create none.
+pub fn find_move_motifs(board: &Board, m: Move) -> Vec<Motif> {
+    let color = board.get_turn_color();
+    let next_board = match board.play_move(m) {
+        GameResult::Continuing(next_board) | GameResult::Victory(next_board, _)
+            if m != Move::Resign =>
+        {
+            next_board
+        }
+        _ => return vec![],
+    };
+
+    let before = find_motifs(board, color);
+    let mut motifs = find_motifs(&next_board, color)
+        .into_iter()
+        .filter(|motif| !before.contains(motif))
+        .collect::<Vec<Motif>>();
+
+    // The attack a move uncovers is a plain one after it, so it is found by
+    // looking for lines the piece moved off of.
+    if let Move::Piece(from, to) | Move::Promotion(from, to, _) = m {
+        for (attacker, direction) in get_sliding_lines(&next_board, color) {
+            let (pieces, ray) = walk(&next_board, attacker, direction);
+            if let Some(&(target_pos, target)) = pieces.first() {
+                let value = piece_value(&next_board, attacker);
+                let was_blocking = ray
+                    .iter()
+                    .take_while(|pos| **pos != target_pos)
+                    .any(|pos| *pos == from);
+                if attacker != to
+                    && target.get_color() != color
+                    && was_blocking
+                    && is_target(&next_board, target, value)
+                {
+                    motifs.push(Motif::DiscoveredAttack {
+                        piece: to,
+                        attacker,
+                        target: target_pos,
+                    });
+                }
+            }
+        }
+    }
+    motifs
+}
+
+// Find the pieces of a player attacking two or more enemy targets.
+fn find_forks(board: &Board, color: Color) -> Vec<Motif> {
+    let mut targets: Vec<(Position, Vec<Position>)> = vec![];
+    for target in get_pieces(board, !color) {
+        for attacker in board.attackers_of(target.get_pos(), color) {
+            if !is_target(board, target, piece_value(board, attacker)) {
+                continue;
+            }
+            match targets.iter_mut().find(|(piece, _)| *piece == attacker) {
+                Some((_, attacked)) => attacked.push(target.get_pos()),
+                None => targets.push((attacker, vec![target.get_pos()])),
+            }
+        }
+    }
+
+    targets
+        .into_iter()
+        .filter(|(_, targets)| targets.len() >= 2)
+        .map(|(piece, targets)| Motif::Fork { piece, targets })
+        .collect()
+}
+
+// Find the pins, skewers and discovered attacks along the lines of the
+// rooks, bishops and queens of a player.
+fn find_lines(board: &Board, color: Color) -> Vec<Motif> {
+    let mut motifs = vec![];
+    for (attacker, direction) in get_sliding_lines(board, color) {
+        let (pieces, _) = walk(board, attacker, direction);
+        let (front, behind) = match pieces[..] {
+            [front, behind] => (front, behind),
+            _ => continue,
+        };
+        if behind.1.get_color() == color {
+            continue;
+        }
+
+        let value = piece_value(board, attacker);
+        if front.1.get_color() == color {
+            if is_target(board, behind.1, value) {
+                motifs.push(Motif::DiscoveredAttack {
+                    piece: front.0,
+                    attacker,
+                    target: behind.0,
+                });
+            }
+        } else if behind.1.get_material_value() > front.1.get_material_value() {
+            motifs.push(Motif::Pin {
+                attacker,
+                pinned: front.0,
+                target: behind.0,
+                absolute: behind.1.is_king(),
+            });
+        } else if front.1.get_material_value() > behind.1.get_material_value()
+            && is_target(board, front.1, value)
+        {
+            motifs.push(Motif::Skewer {
+                attacker,
+                front: front.0,
+                behind: behind.0,
+            });
+        }
+    }
+    motifs
+}
+
+// Find the enemy pieces a player can win because they are attacked and not
+// defended well enough.
+fn find_loose_pieces(board: &Board, color: Color) -> Vec<Motif> {
+    let mut motifs = vec![];
+    for piece in get_pieces(board, !color) {
+        if piece.is_king() {
+            continue;
+        }
+        let pos = piece.get_pos();
+        let attackers = board.attackers_of(pos, color);
+        if attackers.is_empty() {
+            continue;
+        }
+
+        let defenders = board.attackers_of(pos, !color);
+        if defenders.is_empty() {
+            motifs.push(Motif::Hanging {
+                piece: pos,
+                attackers,
+            });
+        } else if attackers
+            .iter()
+            .any(|attacker| board.see(Move::Piece(*attacker, pos)) > 0)
+        {
+            motifs.push(Motif::Underdefended {
+                piece: pos,
+                attackers,
+                defenders,
+            });
+        }
+    }
+    motifs
+}
+
+// Find whether the enemy king is stuck on its back row, where a rook or
+// queen of the player could mate it.
+fn find_back_rank_weakness(board: &Board, color: Color) -> Option<Motif> {
+    let king = board.get_king_pos(!color)?;
+    let (back_row, forward) = if color == WHITE { (7, -1) } else { (0, 1) };
+    if king.get_row() != back_row {
+        return None;
+    }
+    let has_heavy_piece = get_pieces(board, color)
+        .iter()
+        .any(|piece| piece.is_rook() || piece.is_queen());
+    if !has_heavy_piece {
+        return None;
+    }
+
+    let is_stuck = (king.get_col() - 1..=king.get_col() + 1)
+        .map(|col| Position::new(back_row + forward, col))
+        .filter(|pos| pos.is_on_board())
+        .all(|pos| board.has_ally_piece(pos, !color) || board.is_threatened(pos, !color));
+    if is_stuck {
+        Some(Motif::BackRankWeakness { king })
+    } else {
+        None
+    }
+}
+
+// Find the moves that would checkmate the enemy king, if it were the
+// player's turn.
+fn find_mate_threats(board: &Board, color: Color) -> Vec<Motif> {
+    let board = BoardBuilder::from(*board)
+        .set_en_passant(if board.get_turn_color() == color {
+            board.get_en_passant()
+        } else {
+            None
+        })
+        .set_turn(color)
+        .build();
+    if board.is_in_check(!color) {
+        return vec![];
+    }
+
+    board
+        .get_legal_moves()
+        .into_iter()
+        .filter(|m| matches!(board.play_move(*m), GameResult::Victory(_, _)))
+        .map(|m| Motif::MateThreat {
+            m,
+            from: match m {
+                Move::Piece(from, _) | Move::Promotion(from, _, _) => Some(from),
+                _ => board.get_king_pos(color),
+            },
+        })
+        .collect()
+}
+
+// Get the lines along which the rooks, bishops and queens of a player
+// move, from the square of the piece.
+fn get_sliding_lines(board: &Board, color: Color) -> Vec<(Position, (i32, i32))> {
+    let mut lines = vec![];
+    for piece in get_pieces(board, color) {
+        let pos = piece.get_pos();
+        if piece.is_rook() || piece.is_queen() {
+            lines.extend(ORTHOGONALS.iter().map(|direction| (pos, *direction)));
+        }
+        if piece.is_bishop() || piece.is_queen() {
+            lines.extend(DIAGONALS.iter().map(|direction| (pos, *direction)));
+        }
+    }
+    lines
+}
+
+// Walk a line from a square, and get the first two pieces on it along
+// with the squares walked through.
+fn walk(
+    board: &Board,
+    from: Position,
+    (row_step, col_step): (i32, i32),
+) -> (Vec<(Position, Piece)>, Vec<Position>) {
+    let mut pieces = vec![];
+    let mut ray = vec![];
+    let mut pos = from;
+    while pieces.len() < 2 {
+        pos = Position::new(pos.get_row() + row_step, pos.get_col() + col_step);
+        if pos.is_off_board() {
+            break;
+        }
+        ray.push(pos);
+        if let Some(piece) = board.get_piece(pos) {
+            pieces.push((pos, piece));
+        }
+    }
+    (pieces, ray)
+}
+
+fn get_pieces(board: &Board, color: Color) -> Vec<Piece> {
+    board
+        .squares()
+        .iter()
+        .filter_map(|square| square.get_piece())
+        .filter(|piece| piece.get_color() == color)
+        .collect()
+}
+
+fn piece_value(board: &Board, pos: Position) -> i32 {
+    board
+        .get_piece(pos)
+        .map_or(0, |piece| piece.get_material_value())
+}
+
+// Is an enemy piece worth going after with a piece of some value? The king
+// always is, and other pieces when they are worth more or undefended.
+fn is_target(board: &Board, target: Piece, value: i32) -> bool {
+    target.is_king()
+        || target.get_material_value() > value
+        || board
+            .attackers_of(target.get_pos(), target.get_color())
+            .is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{A1, A4, A8, B5, E1, E8};
+    use crate::piece::BLACK;
+
+    // The squares that have no constant in `board`.
+    const C6: Position = Position::new(5, 2);
+    const C7: Position = Position::new(6, 2);
+    const D5: Position = Position::new(4, 3);
+    const D7: Position = Position::new(6, 3);
+    const E2: Position = Position::new(1, 4);
+
+    fn motifs(fen: &str, color: Color) -> Vec<Motif> {
+        find_motifs(&Board::from_fen(fen).unwrap(), color)
+    }
+
+    #[test]
+    fn fork() {
+        let fork = Motif::Fork {
+            piece: C7,
+            targets: vec![A8, E8],
+        };
+        assert!(motifs("r3k3/2N5/8/8/8/8/8/4K3 b - - 0 1", WHITE).contains(&fork));
+
+        // The knight jumps in from d5, which forked nothing.
+        let board = Board::from_fen("r3k3/8/8/3N4/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(!find_motifs(&board, WHITE).contains(&fork));
+        let m = board.parse_san("Nc7+").unwrap();
+        assert_eq!(m, Move::Piece(D5, C7));
+        assert!(find_move_motifs(&board, m).contains(&fork));
+    }
+
+    #[test]
+    fn pins() {
+        let absolute = Motif::Pin {
+            attacker: E1,
+            pinned: E2,
+            target: E8,
+            absolute: true,
+        };
+        let found = motifs("4k3/8/8/8/8/8/4n3/4R1K1 w - - 0 1", WHITE);
+        assert!(found.contains(&absolute), "{:?}", found);
+        assert_eq!(absolute.get_name(), "absolute pin");
+
+        let relative = Motif::Pin {
+            attacker: B5,
+            pinned: C6,
+            target: D7,
+            absolute: false,
+        };
+        let found = motifs("7k/3q4/2n5/1B6/8/8/8/6K1 w - - 0 1", WHITE);
+        assert!(found.contains(&relative), "{:?}", found);
+        // The black pieces pin nothing of white's.
+        assert!(!motifs("7k/3q4/2n5/1B6/8/8/8/6K1 w - - 0 1", BLACK)
+            .iter()
+            .any(|motif| matches!(motif, Motif::Pin { .. })));
+    }
+
+    #[test]
+    fn skewer() {
+        let skewer = Motif::Skewer {
+            attacker: A1,
+            front: A4,
+            behind: A8,
+        };
+        let found = motifs("q7/8/8/8/k7/8/8/R5K1 b - - 0 1", WHITE);
+        assert!(found.contains(&skewer), "{:?}", found);
+        // With the queen in front of the king, it is a pin instead.
+        let found = motifs("k7/8/8/8/q7/8/8/R5K1 b - - 0 1", WHITE);
+        assert!(!found
+            .iter()
+            .any(|motif| matches!(motif, Motif::Skewer { .. })));
+        assert!(found.iter().any(|motif| matches!(motif, Motif::Pin { .. })));
+    }
+}
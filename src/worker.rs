@@ -0,0 +1,862 @@
+// The computer searches for its moves in a Web Worker, so that the page
+// stays responsive while it thinks.
+//
+// The page and the worker each run their own instance of this module, and
+// talk with messages that are plain objects with a `type` field:
+//
+// - `ready`, from the worker once it can take requests.
+// - `search`, to the worker, with an `id`, the `fen` of the position, the
+//   difficulty `level`, the evaluation `params` as JSON and the `stop` flag
+//   shared with the page, if there is one.
+// - `progress`, from the worker, with the `id` of the search, the `depth`
+//   searched, the best `move` so far in UCI notation, its `value` and the
+//   number of `nodes` evaluated.
+// - `bestmove`, from the worker, with the `id` of the search, the `move` to
+//   play and the `value` of the position for the computer, unless the move
+//   comes from the opening book.
+// - `ponder`, to the worker, like `search` but with the position after the
+//   computer's move, to search the reply the player is expected to play
+//   while they think.
+// - `pondering`, from the worker, with the `id` of a ponder search and the
+//   expected reply `move`. The `progress` and `bestmove` that follow are for
+//   the position after it.
+// - `book`, to the worker, with the `bytes` of a Polyglot opening book and
+//   whether its moves are picked at `random`.
+// - `analyze`, to the worker, with an `id`, the `fen` of a position, the
+//   evaluation `params` as JSON and the `stop` flag, like `search`, to
+//   search it at full strength for the evaluation bar.
+// - `analysis`, from the worker, with the `id` of the analysis, the `depth`
+//   searched and the `value` of the position for the player to move.
+// - `analyzed`, from the worker, with the `id` of the analysis once it has
+//   stopped.
+// - `review`, to the worker, with an `id`, the `fen` of the position a game
+//   started from, its `moves` in UCI notation separated by spaces and the
+//   evaluation `params` as JSON, to analyse every position of the game.
+// - `reviewprogress`, from the worker, with the `id` of the review, the
+//   number of positions `done` and the `total` number of them.
+// - `reviewed`, from the worker, with the `id` of the review, the best
+//   `moves` of the positions in UCI notation separated by spaces, `0000`
+//   once the game is over, and their `values` for the player to move.
+//
+// The analysis for the evaluation bar and the review of a finished game run
+// in a second worker, so that they don't hold up the computer's moves.
+//
+// The worker doesn't read messages while it searches, so a search is
+// stopped with a flag in a `SharedArrayBuffer` that the page shares with
+// the worker, and that the search checks as it runs. It then plays the best
+// move it has found so far. The flag holds one more than the id of the last
+// search to stop, which stops the searches before it too. This is how the
+// player makes the computer move at once, how a ponder search is dropped
+// when the player plays another move than the expected one, and how the
+// analysis of a position is dropped once it is played from.
+//
+// Shared memory is only available when the page is cross-origin isolated.
+// Otherwise a search is cancelled by terminating the worker and starting a
+// new one.
+
+use crate::{
+    board::Board,
+    book::Book,
+    difficulty::Difficulty,
+    game::{get_book_move, set_opening_book, Evaluate, Move},
+    params::EvalParams,
+    review::{Analysis, Review},
+    search::{Search, SearchLimits},
+    show_review, show_status, show_thinking, update_rating_bar,
+};
+use futures::channel::oneshot;
+use js_sys::{Atomics, Int32Array, Object, Reflect, SharedArrayBuffer, Uint8Array};
+use std::{cell::RefCell, thread::LocalKey, time::Duration};
+use wasm_bindgen::{closure::Closure, prelude::*, JsCast};
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent, Worker};
+
+// The script of the worker, built next to the page.
+const WORKER_SCRIPT: &str = "./worker.js";
+
+// How long a position is analysed for the evaluation bar, and the size of
+// the transposition table in megabytes.
+const ANALYSIS_TIME: Duration = Duration::from_secs(3);
+const ANALYSIS_HASH: usize = 16;
+
+// The size of the transposition table of the computer's searches, in
+// megabytes.
+const SEARCH_HASH: usize = 16;
+
+// How long the computer thinks at most about the reply it expects the
+// player to play, before pondering on it.
+const PONDER_EXPECT_TIME: Duration = Duration::from_secs(1);
+
+// How deep every position of a game is searched to review it, and for how
+// long at most.
+const REVIEW_DEPTH: i32 = 6;
+const REVIEW_TIME: Duration = Duration::from_millis(500);
+
+type WorkerSlot = LocalKey<RefCell<Option<SearchWorker>>>;
+
+thread_local! {
+    // On the page, the worker searching for the computer's moves.
+    static SEARCH_WORKER: RefCell<Option<SearchWorker>> = const { RefCell::new(None) };
+    // On the page, the worker analysing positions for the evaluation bar.
+    static ANALYSIS_WORKER: RefCell<Option<SearchWorker>> = const { RefCell::new(None) };
+    // In the worker, the flag the page stops searches with, and the id of
+    // the search running.
+    static STOP_FLAG: RefCell<Option<(Int32Array, u32)>> = const { RefCell::new(None) };
+}
+
+// A search waiting for its best move.
+struct PendingSearch {
+    id: u32,
+    board: Board,
+    level: u8,
+    params: String,
+    // For a ponder search, the reply the player is expected to play on
+    // `board`, once the worker has picked it.
+    expected: Option<Move>,
+    pondering: bool,
+    // The best move the worker has reported so far, with its value.
+    best: Option<(Move, f64)>,
+    // The best move of a ponder search that finished before the player
+    // played.
+    result: Option<(Move, Option<f64>)>,
+    // Set once the computer waits for the move.
+    sender: Option<oneshot::Sender<(Move, Option<f64>)>>,
+}
+
+impl PendingSearch {
+    // Is this a ponder search of the position the computer has to move in,
+    // with the same settings?
+    fn is_ponder_hit(&self, board: &Board, level: u8, params: &str) -> bool {
+        self.pondering
+            && self.expected.is_some()
+            && self.board == *board
+            && self.level == level
+            && self.params == params
+    }
+}
+
+struct SearchWorker {
+    worker: Worker,
+    // Where the page keeps the worker, to hand it its messages.
+    slot: &'static WorkerSlot,
+    // Messages are held back until the worker is ready, since it drops the
+    // ones sent while it is still loading.
+    ready: bool,
+    queue: Vec<Object>,
+    next_id: u32,
+    pending: Option<PendingSearch>,
+    // The position being analysed, with the id of its analysis.
+    analyzing: Option<(u32, Board)>,
+    // The game being reviewed, with the id of its review.
+    reviewing: Option<(u32, Board, Vec<Move>)>,
+    // The opening book, to hand again to the next worker when one is
+    // terminated.
+    book: Option<(Vec<u8>, bool)>,
+    // The flag shared with the worker to stop its searches, if the page
+    // can share memory with it.
+    stop_flag: Option<Int32Array>,
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+}
+
+impl SearchWorker {
+    fn new(slot: &'static WorkerSlot, book: Option<(Vec<u8>, bool)>) -> Result<Self, JsValue> {
+        let worker = Worker::new(WORKER_SCRIPT)?;
+        let on_message = Closure::wrap(Box::new(move |event: MessageEvent| {
+            slot.with(|search_worker| {
+                if let Some(search_worker) = search_worker.borrow_mut().as_mut() {
+                    search_worker.receive(&event.data());
+                }
+            });
+        }) as Box<dyn FnMut(_)>);
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+
+        let mut result = Self {
+            worker,
+            slot,
+            ready: false,
+            queue: vec![],
+            next_id: 0,
+            pending: None,
+            analyzing: None,
+            reviewing: None,
+            book: None,
+            stop_flag: is_cross_origin_isolated()
+                .then(|| Int32Array::new(&SharedArrayBuffer::new(4))),
+            _on_message: on_message,
+        };
+        if let Some((bytes, random)) = book {
+            result.send_book(bytes, random);
+        }
+        Ok(result)
+    }
+
+    fn send(&mut self, message: Object) {
+        if self.ready {
+            let _ = self.worker.post_message(&message);
+        } else {
+            self.queue.push(message);
+        }
+    }
+
+    fn send_book(&mut self, bytes: Vec<u8>, random: bool) {
+        let message = new_message("book");
+        set_field(&message, "bytes", &Uint8Array::from(bytes.as_slice()));
+        set_field(&message, "random", &random.into());
+        self.send(message);
+        self.book = Some((bytes, random));
+    }
+
+    // Start a search of `board`, or a ponder search of the reply expected
+    // on it, replacing any search the worker was told about before.
+    fn start_search(
+        &mut self,
+        kind: &str,
+        board: &Board,
+        level: u8,
+        params: String,
+        sender: Option<oneshot::Sender<(Move, Option<f64>)>>,
+    ) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let message = new_message(kind);
+        set_field(&message, "id", &id.into());
+        set_field(&message, "fen", &board.to_fen().into());
+        set_field(&message, "level", &level.into());
+        set_field(&message, "params", &params.as_str().into());
+        if let Some(stop_flag) = &self.stop_flag {
+            set_field(&message, "stop", stop_flag);
+        }
+        self.send(message);
+
+        self.pending = Some(PendingSearch {
+            id,
+            board: *board,
+            level,
+            params,
+            expected: None,
+            pondering: kind == "ponder",
+            best: None,
+            result: None,
+            sender,
+        });
+    }
+
+    // Tell the worker to stop the search with `id` and the ones before it.
+    // Returns whether it could be told, which it can't without shared
+    // memory.
+    fn stop_search(&self, id: u32) -> bool {
+        self.stop_flag
+            .as_ref()
+            .is_some_and(|stop_flag| Atomics::store(stop_flag, 0, id as i32 + 1).is_ok())
+    }
+
+    fn receive(&mut self, data: &JsValue) {
+        let id = get_field(data, "id").as_f64().map(|id| id as u32);
+        match get_field(data, "type").as_string().as_deref() {
+            Some("ready") => {
+                self.ready = true;
+                for message in std::mem::take(&mut self.queue) {
+                    let _ = self.worker.post_message(&message);
+                }
+            }
+            Some("pondering") => {
+                if let Some(pending) = self
+                    .pending
+                    .as_mut()
+                    .filter(|pending| Some(pending.id) == id && pending.expected.is_none())
+                {
+                    let uci = get_field(data, "move").as_string().unwrap_or_default();
+                    if let Ok(m) = pending.board.parse_uci(&uci) {
+                        pending.expected = Some(m);
+                        pending.board = pending.board.apply_eval_move(m);
+                    }
+                }
+            }
+            Some("progress") => {
+                if let Some(pending) = self
+                    .pending
+                    .as_mut()
+                    .filter(|pending| Some(pending.id) == id)
+                {
+                    let uci = get_field(data, "move").as_string().unwrap_or_default();
+                    if let Ok(m) = pending.board.parse_uci(&uci) {
+                        let value = get_field(data, "value").as_f64().unwrap_or_default();
+                        pending.best = Some((m, value));
+                        // Pondering happens quietly while the player thinks.
+                        if pending.sender.is_some() {
+                            show_thinking(Some(&format!(
+                                "depth {}, best {}",
+                                get_field(data, "depth").as_f64().unwrap_or_default() + 1.0,
+                                pending.board.get_san(m)
+                            )));
+                        }
+                    }
+                }
+            }
+            Some("analysis") => {
+                if let Some((_, board)) = self
+                    .analyzing
+                    .filter(|(analysis_id, _)| Some(*analysis_id) == id)
+                {
+                    update_rating_bar(
+                        &board,
+                        get_field(data, "value").as_f64().unwrap_or_default(),
+                    );
+                }
+            }
+            Some("analyzed") => {
+                self.analyzing
+                    .take_if(|(analysis_id, _)| Some(*analysis_id) == id);
+            }
+            Some("reviewprogress")
+                if self
+                    .reviewing
+                    .as_ref()
+                    .is_some_and(|(review_id, _, _)| Some(*review_id) == id) =>
+            {
+                show_status(Some(&format!(
+                    "Reviewing position {} of {}",
+                    get_field(data, "done").as_f64().unwrap_or_default() + 1.0,
+                    get_field(data, "total").as_f64().unwrap_or_default()
+                )));
+            }
+            Some("reviewed") => {
+                if let Some((_, start, moves)) = self
+                    .reviewing
+                    .take_if(|(review_id, _, _)| Some(*review_id) == id)
+                {
+                    let best_moves = get_field(data, "moves").as_string().unwrap_or_default();
+                    let values = js_sys::Array::from(&get_field(data, "values"));
+                    let mut board = start;
+                    let mut analyses = vec![];
+                    for (i, uci) in best_moves.split_whitespace().enumerate() {
+                        analyses.push(Analysis::new(
+                            board.parse_uci(uci).ok(),
+                            values.get(i as u32).as_f64().unwrap_or_default(),
+                        ));
+                        if let Some(m) = moves.get(i) {
+                            board = board.apply_eval_move(*m);
+                        }
+                    }
+                    let review = crate::EVAL_PARAMS
+                        .with(|params| Review::new(start, &moves, &analyses, &params.borrow()));
+                    match review {
+                        Ok(review) => show_review(&review),
+                        Err(e) => {
+                            web_sys::console::error_1(&format!("Invalid review: {}", e).into())
+                        }
+                    }
+                }
+            }
+            Some("bestmove")
+                if self
+                    .pending
+                    .as_ref()
+                    .is_some_and(|pending| Some(pending.id) == id) =>
+            {
+                let pending = self.pending.as_mut().unwrap();
+                let uci = get_field(data, "move").as_string().unwrap_or_default();
+                let m = pending.board.parse_uci(&uci).unwrap_or(Move::Resign);
+                let value = get_field(data, "value").as_f64();
+                match pending.sender.take() {
+                    Some(sender) => {
+                        let _ = sender.send((m, value));
+                        self.pending = None;
+                    }
+                    // Keep the move of a ponder search until the player
+                    // plays the expected reply.
+                    None => pending.result = Some((m, value)),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Can the page share memory with its workers?
+fn is_cross_origin_isolated() -> bool {
+    get_field(&js_sys::global(), "crossOriginIsolated")
+        .as_bool()
+        .unwrap_or_default()
+}
+
+// Has the page told the worker to stop the search running?
+fn is_stop_requested() -> bool {
+    STOP_FLAG.with(|stop_flag| {
+        stop_flag.borrow().as_ref().is_some_and(|(stop_flag, id)| {
+            Atomics::load(stop_flag, 0).is_ok_and(|stopped| stopped > *id as i32)
+        })
+    })
+}
+
+// Keep the flag the page stops the search of a message with, along with
+// the id of the search.
+fn set_stop_flag(data: &JsValue) {
+    let stop_flag = get_field(data, "stop").dyn_into::<Int32Array>().ok();
+    let id = get_field(data, "id").as_f64();
+    STOP_FLAG.with(|current| {
+        *current.borrow_mut() = stop_flag
+            .zip(id)
+            .map(|(stop_flag, id)| (stop_flag, id as u32))
+    });
+}
+
+fn new_message(kind: &str) -> Object {
+    let message = Object::new();
+    set_field(&message, "type", &kind.into());
+    message
+}
+
+#[inline]
+fn set_field(message: &Object, name: &str, value: &JsValue) {
+    let _ = Reflect::set(message, &name.into(), value);
+}
+
+#[inline]
+fn get_field(message: &JsValue, name: &str) -> JsValue {
+    Reflect::get(message, &name.into()).unwrap_or(JsValue::UNDEFINED)
+}
+
+// Start the worker for the page. If workers aren't available, the computer
+// searches on the page instead.
+pub fn start() {
+    match SearchWorker::new(&SEARCH_WORKER, None) {
+        Ok(search_worker) => {
+            SEARCH_WORKER.with(|current| *current.borrow_mut() = Some(search_worker));
+        }
+        Err(e) => web_sys::console::warn_2(&"Searching without a worker:".into(), &e),
+    }
+    // Without a worker, the evaluation bar only moves with the computer's
+    // own searches.
+    if let Ok(analysis_worker) = SearchWorker::new(&ANALYSIS_WORKER, None) {
+        ANALYSIS_WORKER.with(|current| *current.borrow_mut() = Some(analysis_worker));
+    }
+}
+
+// Analyse a position for the evaluation bar, which is updated as the search
+// goes deeper, instead of the position analysed before.
+pub fn analyze(board: &Board, params: &EvalParams) {
+    stop_analysis();
+    ANALYSIS_WORKER.with(|analysis_worker| {
+        if let (Some(analysis_worker), Ok(params)) =
+            (analysis_worker.borrow_mut().as_mut(), params.to_json())
+        {
+            let id = analysis_worker.next_id;
+            analysis_worker.next_id += 1;
+            let message = new_message("analyze");
+            set_field(&message, "id", &id.into());
+            set_field(&message, "fen", &board.to_fen().into());
+            set_field(&message, "params", &params.as_str().into());
+            if let Some(stop_flag) = &analysis_worker.stop_flag {
+                set_field(&message, "stop", stop_flag);
+            }
+            analysis_worker.send(message);
+            analysis_worker.analyzing = Some((id, *board));
+        }
+    });
+}
+
+// Stop analysing for the evaluation bar, when the position is played from
+// or the game is over. Without shared memory, the worker is restarted if
+// its analysis hasn't finished yet.
+pub fn stop_analysis() {
+    ANALYSIS_WORKER.with(|analysis_worker| {
+        let mut analysis_worker = analysis_worker.borrow_mut();
+        let analyzing = analysis_worker
+            .as_mut()
+            .and_then(|worker| worker.analyzing.take());
+        if let Some((id, _)) = analyzing {
+            if !analysis_worker.as_ref().unwrap().stop_search(id) {
+                restart(&mut analysis_worker);
+            }
+        }
+    });
+}
+
+// Review a finished game by analysing every position in it, and show the
+// review once it is done. Without a worker, the game is reviewed on the page.
+pub fn review(start: &Board, moves: &[Move], params: &EvalParams) {
+    stop_analysis();
+    let sent = ANALYSIS_WORKER.with(|analysis_worker| {
+        let mut analysis_worker = analysis_worker.borrow_mut();
+        let (analysis_worker, params) = match (analysis_worker.as_mut(), params.to_json()) {
+            (Some(analysis_worker), Ok(params)) => (analysis_worker, params),
+            _ => return false,
+        };
+        let id = analysis_worker.next_id;
+        analysis_worker.next_id += 1;
+        let mut board = *start;
+        let uci = moves
+            .iter()
+            .map(|m| {
+                let uci = board.get_uci(*m);
+                board = board.apply_eval_move(*m);
+                uci
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        let message = new_message("review");
+        set_field(&message, "id", &id.into());
+        set_field(&message, "fen", &start.to_fen().into());
+        set_field(&message, "moves", &uci.into());
+        set_field(&message, "params", &params.as_str().into());
+        analysis_worker.send(message);
+        analysis_worker.reviewing = Some((id, *start, moves.to_vec()));
+        true
+    });
+
+    if !sent {
+        let search = Search::new(1, ANALYSIS_HASH);
+        let limits = get_review_limits();
+        match Review::run(*start, moves, &search, &limits, params, |_, _| {}) {
+            Ok(review) => show_review(&review),
+            Err(e) => web_sys::console::error_1(&format!("Invalid review: {}", e).into()),
+        }
+    }
+}
+
+#[inline]
+fn get_review_limits() -> SearchLimits {
+    SearchLimits::new()
+        .with_depth(REVIEW_DEPTH)
+        .with_time(REVIEW_TIME)
+}
+
+// Hand an opening book to the worker, if there is one.
+pub fn send_book(bytes: &[u8], random: bool) {
+    SEARCH_WORKER.with(|search_worker| {
+        if let Some(search_worker) = search_worker.borrow_mut().as_mut() {
+            search_worker.send_book(bytes.to_vec(), random);
+        }
+    });
+}
+
+// Terminate the worker in the middle of its search, and start a new one for
+// the next search. Get the search that was pending, if any.
+fn restart(search_worker: &mut Option<SearchWorker>) -> Option<PendingSearch> {
+    let old_worker = search_worker.take()?;
+    old_worker.worker.terminate();
+    match SearchWorker::new(old_worker.slot, old_worker.book) {
+        Ok(new_worker) => *search_worker = Some(new_worker),
+        Err(e) => web_sys::console::warn_2(&"Searching without a worker:".into(), &e),
+    }
+    old_worker.pending
+}
+
+// Get the computer's next move and the value of its position, searched by
+// the worker while the page keeps running, or on the page if there is no
+// worker. If the worker was pondering this position, its search is used
+// instead of starting over. Moves from the opening book have no value.
+pub async fn get_move(
+    board: &Board,
+    difficulty: Difficulty,
+    params: &EvalParams,
+) -> (Move, Option<f64>) {
+    let receiver = SEARCH_WORKER.with(|search_worker| {
+        let mut search_worker = search_worker.borrow_mut();
+        let params = params.to_json().ok()?;
+        let level = difficulty.get_level();
+        let (sender, receiver) = oneshot::channel();
+
+        let current = search_worker.as_mut()?;
+        match current.pending.as_mut() {
+            Some(pending) if pending.is_ponder_hit(board, level, &params) => {
+                pending.pondering = false;
+                if let Some(result) = pending.result {
+                    current.pending = None;
+                    let _ = sender.send(result);
+                } else {
+                    pending.sender = Some(sender);
+                }
+                return Some(receiver);
+            }
+            // The player didn't play the expected reply.
+            Some(pending) => {
+                let id = pending.id;
+                if !current.stop_search(id) {
+                    restart(&mut search_worker);
+                }
+            }
+            None => {}
+        }
+        search_worker
+            .as_mut()?
+            .start_search("search", board, level, params, Some(sender));
+        Some(receiver)
+    });
+
+    match receiver {
+        Some(receiver) => {
+            show_thinking(Some(""));
+            let result = receiver.await;
+            show_thinking(None);
+            match result {
+                Ok(result) => result,
+                Err(_) => get_move_here(board, difficulty, params),
+            }
+        }
+        None => get_move_here(board, difficulty, params),
+    }
+}
+
+// Get the computer's next move and the value of its position like the
+// worker does, but on the page.
+fn get_move_here(
+    board: &Board,
+    difficulty: Difficulty,
+    params: &EvalParams,
+) -> (Move, Option<f64>) {
+    match get_book_move(board) {
+        Some(m) => (m, None),
+        None => {
+            let search = Search::new(1, SEARCH_HASH);
+            let (m, value) = difficulty.get_move_and_value(board, &search, params);
+            (m, Some(value))
+        }
+    }
+}
+
+// Think about the reply the player is expected to play on `board` while
+// they think about their move.
+pub fn ponder(board: &Board, difficulty: Difficulty, params: &EvalParams) {
+    SEARCH_WORKER.with(|search_worker| {
+        if let (Some(search_worker), Ok(params)) =
+            (search_worker.borrow_mut().as_mut(), params.to_json())
+        {
+            search_worker.start_search("ponder", board, difficulty.get_level(), params, None);
+        }
+    });
+}
+
+// Stop the computer's search and make it play the best move it has found
+// so far. Without shared memory, this is the last best move the worker
+// reported, or the best move without any lookahead if it hasn't reported
+// one.
+#[wasm_bindgen]
+pub fn stop_thinking() {
+    SEARCH_WORKER.with(|search_worker| {
+        let mut search_worker = search_worker.borrow_mut();
+        // Pondering goes on, since the computer isn't waiting for it.
+        let waiting = search_worker
+            .as_ref()
+            .and_then(|worker| worker.pending.as_ref())
+            .filter(|pending| pending.sender.is_some())
+            .map(|pending| pending.id);
+        let id = match waiting {
+            Some(id) => id,
+            None => return,
+        };
+        // The worker posts its best move once the search stops.
+        if search_worker.as_ref().unwrap().stop_search(id) {
+            return;
+        }
+
+        let pending = restart(&mut search_worker).unwrap();
+        let (m, value) = pending.best.unwrap_or_else(|| {
+            let result = crate::EVAL_PARAMS
+                .with(|params| Search::new(1, 1).run(&pending.board, 0, &params.borrow()));
+            (result.get_best_move(), result.get_value())
+        });
+        if let Some(sender) = pending.sender {
+            let _ = sender.send((m, Some(value)));
+        }
+    });
+}
+
+// Start answering the messages of the page. This is called by the worker
+// script once the module is loaded.
+#[wasm_bindgen]
+pub fn start_search_worker() -> Result<(), JsValue> {
+    let scope = js_sys::global().dyn_into::<DedicatedWorkerGlobalScope>()?;
+
+    let on_message = Closure::wrap(Box::new(|event: MessageEvent| {
+        let scope = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+        let data = event.data();
+        match get_field(&data, "type").as_string().as_deref() {
+            Some(kind @ ("search" | "ponder")) => search(&scope, &data, kind == "ponder"),
+            Some("analyze") => analyze_here(&scope, &data),
+            Some("review") => review_here(&scope, &data),
+            Some("book") => {
+                let bytes = Uint8Array::new(&get_field(&data, "bytes")).to_vec();
+                let random = get_field(&data, "random").as_bool().unwrap_or_default();
+                match Book::from_bytes(&bytes) {
+                    Ok(book) => set_opening_book(Some(book), random),
+                    Err(e) => {
+                        web_sys::console::error_1(&format!("Invalid opening book: {}", e).into())
+                    }
+                }
+            }
+            _ => {}
+        }
+    }) as Box<dyn FnMut(_)>);
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The worker answers messages for as long as it lives.
+    on_message.forget();
+
+    scope.post_message(&new_message("ready"))
+}
+
+// Search the position of a `search` or `ponder` message, and post the best
+// move found.
+fn search(scope: &DedicatedWorkerGlobalScope, data: &JsValue, ponder: bool) {
+    let id = get_field(data, "id");
+    let board = Board::from_fen(&get_field(data, "fen").as_string().unwrap_or_default());
+    let difficulty = Difficulty::new(get_field(data, "level").as_f64().unwrap_or_default() as u8);
+    let params = EvalParams::from_json(&get_field(data, "params").as_string().unwrap_or_default());
+    let (mut board, difficulty, params) = match (board, difficulty, params) {
+        (Ok(board), Ok(difficulty), Ok(params)) => (board, difficulty, params),
+        (board, difficulty, params) => {
+            let e = board.err().or(difficulty.err()).or(params.err()).unwrap();
+            web_sys::console::error_1(&format!("Invalid search: {}", e).into());
+            return;
+        }
+    };
+
+    set_stop_flag(data);
+
+    let search = Search::new(1, SEARCH_HASH);
+    if ponder {
+        // Expect the player to play about as well as the computer, which
+        // takes a search one move shallower than its own.
+        if board.get_legal_moves().is_empty() {
+            return;
+        }
+        let expected = get_book_move(&board).unwrap_or_else(|| {
+            let limits = SearchLimits::new()
+                .with_depth((difficulty.get_depth() - 1).max(0))
+                .with_time(PONDER_EXPECT_TIME)
+                .with_stop_check(is_stop_requested);
+            search
+                .run_with_limits(&board, &limits, &params)
+                .get_best_move()
+        });
+        let message = new_message("pondering");
+        set_field(&message, "id", &id);
+        set_field(&message, "move", &board.get_uci(expected).into());
+        let _ = scope.post_message(&message);
+        board = board.apply_eval_move(expected);
+    }
+
+    let (m, value) = match get_book_move(&board) {
+        Some(m) => (m, None),
+        None => {
+            let limits = difficulty.get_limits().with_stop_check(is_stop_requested);
+            let (m, value) =
+                difficulty.get_move_with_progress(&board, &search, &limits, &params, |result| {
+                    let message = new_message("progress");
+                    set_field(&message, "id", &id);
+                    set_field(&message, "depth", &result.get_depth().into());
+                    set_field(
+                        &message,
+                        "move",
+                        &board.get_uci(result.get_best_move()).into(),
+                    );
+                    set_field(&message, "value", &result.get_value().into());
+                    set_field(&message, "nodes", &(result.get_nodes() as f64).into());
+                    let _ = scope.post_message(&message);
+                });
+            (m, Some(value))
+        }
+    };
+
+    let message = new_message("bestmove");
+    set_field(&message, "id", &id);
+    set_field(&message, "move", &board.get_uci(m).into());
+    if let Some(value) = value {
+        set_field(&message, "value", &value.into());
+    }
+    let _ = scope.post_message(&message);
+}
+
+// Search the position of an `analyze` message deeper and deeper until the
+// time runs out or the page stops it, posting its value after every depth,
+// and post that the analysis is over.
+fn analyze_here(scope: &DedicatedWorkerGlobalScope, data: &JsValue) {
+    let id = get_field(data, "id");
+    analyze_position(scope, data);
+    let message = new_message("analyzed");
+    set_field(&message, "id", &id);
+    let _ = scope.post_message(&message);
+}
+
+fn analyze_position(scope: &DedicatedWorkerGlobalScope, data: &JsValue) {
+    let id = get_field(data, "id");
+    let board = Board::from_fen(&get_field(data, "fen").as_string().unwrap_or_default());
+    let params = EvalParams::from_json(&get_field(data, "params").as_string().unwrap_or_default());
+    let (board, params) = match (board, params) {
+        (Ok(board), Ok(params)) => (board, params),
+        (board, params) => {
+            let e = board.err().or(params.err()).unwrap();
+            web_sys::console::error_1(&format!("Invalid analysis: {}", e).into());
+            return;
+        }
+    };
+    if board.get_legal_moves().is_empty() {
+        return;
+    }
+
+    set_stop_flag(data);
+
+    let limits = SearchLimits::new()
+        .with_time(ANALYSIS_TIME)
+        .with_stop_check(is_stop_requested);
+    Search::new(1, ANALYSIS_HASH).run_with_progress(&board, &limits, &params, |result| {
+        let message = new_message("analysis");
+        set_field(&message, "id", &id);
+        set_field(&message, "depth", &result.get_depth().into());
+        set_field(&message, "value", &result.get_value().into());
+        let _ = scope.post_message(&message);
+    });
+}
+
+// Analyse every position of the game of a `review` message, posting the
+// progress after each one, and post the best moves and values found.
+fn review_here(scope: &DedicatedWorkerGlobalScope, data: &JsValue) {
+    let id = get_field(data, "id");
+    let board = Board::from_fen(&get_field(data, "fen").as_string().unwrap_or_default());
+    let params = EvalParams::from_json(&get_field(data, "params").as_string().unwrap_or_default());
+    let (mut board, params) = match (board, params) {
+        (Ok(board), Ok(params)) => (board, params),
+        (board, params) => {
+            let e = board.err().or(params.err()).unwrap();
+            web_sys::console::error_1(&format!("Invalid review: {}", e).into());
+            return;
+        }
+    };
+    let moves = get_field(data, "moves").as_string().unwrap_or_default();
+    let moves = moves.split_whitespace().collect::<Vec<&str>>();
+
+    let search = Search::new(1, ANALYSIS_HASH);
+    let limits = get_review_limits();
+    let mut best_moves = vec![];
+    let values = js_sys::Array::new();
+    for i in 0..=moves.len() {
+        let message = new_message("reviewprogress");
+        set_field(&message, "id", &id);
+        set_field(&message, "done", &(i as u32).into());
+        set_field(&message, "total", &(moves.len() as u32 + 1).into());
+        let _ = scope.post_message(&message);
+
+        let analysis = Analysis::run(&board, &search, &limits, &params);
+        best_moves.push(match analysis.get_best_move() {
+            Some(m) => board.get_uci(m),
+            None => String::from("0000"),
+        });
+        values.push(&analysis.get_value().into());
+        if let Some(uci) = moves.get(i) {
+            match board.parse_uci(uci) {
+                Ok(m) => board = board.apply_eval_move(m),
+                Err(e) => {
+                    web_sys::console::error_1(&format!("Invalid review: {}", e).into());
+                    return;
+                }
+            }
+        }
+    }
+
+    let message = new_message("reviewed");
+    set_field(&message, "id", &id);
+    set_field(&message, "moves", &best_moves.join(" ").into());
+    set_field(&message, "values", &values);
+    let _ = scope.post_message(&message);
+}
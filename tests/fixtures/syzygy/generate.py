@@ -0,0 +1,444 @@
+#!/usr/bin/env python3
+# Generate the KQvK and KRvK Syzygy tables used by the tablebase tests.
+#
+# The endgames are solved by retrograde analysis, and written in the Syzygy
+# format: the positions are indexed like the official generator does, and
+# the values are compressed with recursive pairs and a canonical Huffman
+# code. The WDL tables store both players to move, the DTZ tables only white
+# to move, in moves through a value map, like the official ones do.
+#
+# usage: generate.py [<directory>]
+
+import os
+import struct
+import sys
+from collections import Counter
+
+WDL_MAGIC = bytes([0x71, 0xE8, 0x23, 0x5D])
+DTZ_MAGIC = bytes([0xD7, 0x66, 0x0C, 0xA5])
+FLAG_MAPPED = 2
+FLAG_SINGLE_VALUE = 128
+
+BLOCK_SIZE_LOG = 9
+SPAN_LOG = 10
+MAX_PAIRS = 200
+
+WHITE_KING, WHITE_QUEEN, WHITE_ROOK, BLACK_KING = 6, 5, 4, 14
+
+KING_STEPS = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)]
+ROOK_STEPS = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+QUEEN_STEPS = KING_STEPS
+
+
+def rank(sq):
+    return sq >> 3
+
+
+def file(sq):
+    return sq & 7
+
+
+def off_diagonal(sq):
+    return rank(sq) - file(sq)
+
+
+def flip_diagonal(sq):
+    return ((sq >> 3) | (sq << 3)) & 63
+
+
+def distance(a, b):
+    return max(abs(rank(a) - rank(b)), abs(file(a) - file(b)))
+
+
+def king_moves(sq):
+    moves = []
+    for dr, df in KING_STEPS:
+        r, f = rank(sq) + dr, file(sq) + df
+        if 0 <= r < 8 and 0 <= f < 8:
+            moves.append(r * 8 + f)
+    return moves
+
+
+def slider_moves(sq, steps, blockers):
+    moves = []
+    for dr, df in steps:
+        r, f = rank(sq) + dr, file(sq) + df
+        while 0 <= r < 8 and 0 <= f < 8:
+            to = r * 8 + f
+            moves.append(to)
+            if to in blockers:
+                break
+            r, f = r + dr, f + df
+    return moves
+
+
+# ---------------------------------------------------------------------------
+# Indexing, for tables without pawns and with a unique piece.
+
+TRIANGLE = {1: 0, 2: 1, 3: 2, 10: 3, 11: 4, 19: 5, 0: 6, 9: 7, 18: 8, 27: 9}
+LOWER = {sq: i for i, sq in enumerate(s for s in range(64) if off_diagonal(s) < 0)}
+TABLE_SIZE = 6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + 4 * 7 * 6
+
+
+def encode(squares):
+    p = list(squares)
+    if file(p[0]) > 3:
+        p = [sq ^ 7 for sq in p]
+    if rank(p[0]) > 3:
+        p = [sq ^ 56 for sq in p]
+    for i in range(3):
+        if off_diagonal(p[i]) != 0:
+            if off_diagonal(p[i]) > 0:
+                p[i:] = [flip_diagonal(sq) for sq in p[i:]]
+            break
+
+    s1 = int(p[1] > p[0])
+    s2 = int(p[2] > p[0]) + int(p[2] > p[1])
+    if off_diagonal(p[0]) != 0:
+        return (TRIANGLE[p[0]] * 63 + p[1] - s1) * 62 + p[2] - s2
+    if off_diagonal(p[1]) != 0:
+        return (6 * 63 + rank(p[0]) * 28 + LOWER[p[1]]) * 62 + p[2] - s2
+    if off_diagonal(p[2]) != 0:
+        return (
+            6 * 63 * 62
+            + 4 * 28 * 62
+            + rank(p[0]) * 7 * 28
+            + (rank(p[1]) - s1) * 28
+            + LOWER[p[2]]
+        )
+    return (
+        6 * 63 * 62
+        + 4 * 28 * 62
+        + 4 * 7 * 28
+        + rank(p[0]) * 7 * 6
+        + (rank(p[1]) - s1) * 6
+        + rank(p[2])
+        - s2
+    )
+
+
+# ---------------------------------------------------------------------------
+# Retrograde analysis of a king and piece against a king.
+
+
+def solve(steps):
+    def attacks(piece, king, target):
+        return target in slider_moves(piece, steps, {king, target})
+
+    # Positions are (white king, piece, black king, white to move).
+    in_check = {}
+    successors = {}
+    for wk in range(64):
+        for pc in range(64):
+            for bk in range(64):
+                if len({wk, pc, bk}) < 3 or distance(wk, bk) <= 1:
+                    continue
+                checked = attacks(pc, wk, bk)
+                # White to move: black can't be in check.
+                if not checked:
+                    moves = []
+                    for to in king_moves(wk):
+                        if to != pc and distance(to, bk) > 1:
+                            moves.append((to, pc, bk, False))
+                    for to in slider_moves(pc, steps, {wk, bk}):
+                        if to not in (wk, bk):
+                            moves.append((wk, to, bk, False))
+                    successors[(wk, pc, bk, True)] = moves
+
+                moves = []
+                captures = False
+                for to in king_moves(bk):
+                    if distance(to, wk) <= 1:
+                        continue
+                    if to == pc:
+                        captures = True
+                        continue
+                    # The black king does not block the piece's attacks on
+                    # the squares behind it.
+                    if to in slider_moves(pc, steps, {wk}):
+                        continue
+                    moves.append((wk, pc, to, True))
+                in_check[(wk, pc, bk, False)] = checked
+                successors[(wk, pc, bk, False)] = (moves, captures)
+
+    # Plies to mate for the side to move, positive when winning, negative
+    # when losing, and None when drawn.
+    value = {}
+    remaining = {}
+    predecessors = {}
+    frontier = []
+    for position, moves in successors.items():
+        if position[3]:
+            for m in moves:
+                predecessors.setdefault(m, []).append(position)
+        else:
+            moves, captures = moves
+            for m in moves:
+                predecessors.setdefault(m, []).append(position)
+            remaining[position] = len(moves) + captures
+            if not moves and not captures and in_check[position]:
+                value[position] = 0
+                frontier.append(position)
+
+    plies = 0
+    while frontier:
+        plies += 1
+        wins = []
+        for lost in frontier:
+            for position in predecessors.get(lost, []):
+                if position not in value:
+                    value[position] = plies
+                    wins.append(position)
+        plies += 1
+        frontier = []
+        for won in wins:
+            for position in predecessors.get(won, []):
+                remaining[position] -= 1
+                if remaining[position] == 0:
+                    value[position] = -plies
+                    frontier.append(position)
+
+    return successors, value
+
+
+# ---------------------------------------------------------------------------
+# Compression.
+
+
+def pair(values):
+    # Symbols are (left, right) pairs of other symbols, or values.
+    symbols = [("value", v) for v in sorted(set(values))]
+    expansions = [1] * len(symbols)
+    ids = {v: i for i, (_, v) in enumerate(symbols)}
+    sequence = [ids[v] for v in values]
+
+    for _ in range(MAX_PAIRS):
+        counts = Counter(zip(sequence, sequence[1:]))
+        candidates = [
+            (count, p)
+            for p, count in counts.items()
+            if expansions[p[0]] + expansions[p[1]] <= 256
+        ]
+        if not candidates:
+            break
+        count, best = max(candidates)
+        if count < 8:
+            break
+
+        sym = len(symbols)
+        symbols.append(("pair", best))
+        expansions.append(expansions[best[0]] + expansions[best[1]])
+        paired = []
+        i = 0
+        while i < len(sequence):
+            if i + 1 < len(sequence) and (sequence[i], sequence[i + 1]) == best:
+                paired.append(sym)
+                i += 2
+            else:
+                paired.append(sequence[i])
+                i += 1
+        sequence = paired
+
+    return symbols, expansions, sequence
+
+
+def huffman_lengths(frequencies):
+    nodes = [(count, [sym]) for sym, count in frequencies.items()]
+    lengths = {sym: 0 for sym in frequencies}
+    while len(nodes) > 1:
+        nodes.sort(key=lambda node: (node[0], min(node[1])))
+        (a, left), (b, right) = nodes[0], nodes[1]
+        for sym in left + right:
+            lengths[sym] += 1
+        nodes = nodes[2:] + [(a + b, left + right)]
+    return lengths
+
+
+def compress(values, flags=0):
+    # The sizes and code of the table, and its sparse index, block lengths
+    # and blocks.
+    if len(set(values)) == 1:
+        return bytes([flags | FLAG_SINGLE_VALUE, values[0]]), b"", b"", b"", 0
+
+    symbols, expansions, sequence = pair(values)
+    used = set(sequence)
+    for sym in list(used):
+        stack = [sym]
+        while stack:
+            s = stack.pop()
+            if symbols[s][0] == "pair":
+                for child in symbols[s][1]:
+                    if child not in used:
+                        used.add(child)
+                        stack.append(child)
+    frequencies = Counter(sequence)
+    for sym in used:
+        frequencies.setdefault(sym, 0)
+    # Symbols only used inside pairs still need a code.
+    frequencies = {sym: count + 1 for sym, count in frequencies.items()}
+    lengths = huffman_lengths(frequencies)
+
+    # Longer codes get the lower symbol numbers.
+    order = sorted(lengths, key=lambda sym: (-lengths[sym], sym))
+    number = {sym: i for i, sym in enumerate(order)}
+    min_len, max_len = min(lengths.values()), max(lengths.values())
+    count = max_len - min_len + 1
+    per_length = Counter(lengths.values())
+    lowest = [0] * count
+    for i in range(count - 2, -1, -1):
+        lowest[i] = lowest[i + 1] + per_length.get(i + 1 + min_len, 0)
+    base = [0] * count
+    for i in range(count - 2, -1, -1):
+        total = base[i + 1] + per_length.get(i + 1 + min_len, 0)
+        assert total % 2 == 0
+        base[i] = total // 2
+    assert base[0] + per_length.get(min_len, 0) == 1 << min_len
+
+    def code(sym):
+        i = lengths[sym] - min_len
+        return base[i] + number[sym] - lowest[i], lengths[sym]
+
+    btree = bytearray()
+    for sym in order:
+        kind, data = symbols[sym]
+        if kind == "value":
+            left, right = data, 0xFFF
+        else:
+            left, right = number[data[0]], number[data[1]]
+        btree += bytes([left & 0xFF, (left >> 8) | ((right & 0xF) << 4), right >> 4])
+    if len(order) & 1:
+        btree += b"\0"
+
+    # Fill the blocks, leaving room for the decoder to read ahead.
+    block_size = 1 << BLOCK_SIZE_LOG
+    block_bits = block_size * 8 - 64
+    blocks = []
+    starts = []
+    bits, used_bits, block_values = [], 0, 0
+    position = 0
+    for sym in sequence:
+        c, length = code(sym)
+        if used_bits + length > block_bits or block_values + expansions[sym] > 65536:
+            blocks.append((bits, block_values))
+            bits, used_bits, block_values = [], 0, 0
+        if block_values == 0:
+            starts.append(position)
+        bits.append((c, length))
+        used_bits += length
+        block_values += expansions[sym]
+        position += expansions[sym]
+    blocks.append((bits, block_values))
+
+    data = bytearray()
+    lengths_data = bytearray()
+    for bits, block_values in blocks:
+        acc, n = 0, 0
+        for c, length in bits:
+            acc = (acc << length) | c
+            n += length
+        acc <<= block_size * 8 - n
+        data += acc.to_bytes(block_size, "big")
+        lengths_data += struct.pack("<H", block_values - 1)
+
+    span = 1 << SPAN_LOG
+    sparse = bytearray()
+    for k in range((len(values) + span - 1) // span):
+        idx = k * span + span // 2
+        block = max(b for b in range(len(starts)) if starts[b] <= min(idx, len(values) - 1))
+        sparse += struct.pack("<IH", block, idx - starts[block])
+
+    sizes = bytearray([flags, BLOCK_SIZE_LOG, SPAN_LOG, 0])
+    sizes += struct.pack("<I", len(blocks))
+    sizes += bytes([max_len, min_len])
+    for value in lowest:
+        sizes += struct.pack("<H", value)
+    sizes += struct.pack("<H", len(order))
+    sizes += btree
+    return bytes(sizes), bytes(sparse), bytes(lengths_data), bytes(data), len(blocks)
+
+
+def write_table(path, magic, pieces, sides, flags=0, maps=None):
+    header = bytearray(magic)
+    header.append(1)
+    header.append(0x00)
+    for piece in pieces:
+        header.append(piece | piece << 4)
+    header += b"\0" * (len(header) & 1)
+
+    parts = [compress(values, flags) for values in sides]
+    for sizes, _, _, _, _ in parts:
+        header += sizes
+    if maps is not None:
+        for m in maps:
+            header.append(len(m))
+            header += bytes(m)
+        header += b"\0" * (len(header) & 1)
+    for _, sparse, _, _, _ in parts:
+        header += sparse
+    for _, _, lengths, _, _ in parts:
+        header += lengths
+    for _, _, _, data, _ in parts:
+        header += b"\0" * (-len(header) % 64)
+        header += data
+    with open(path, "wb") as out:
+        out.write(header)
+
+
+def generate(directory, name, piece, steps):
+    positions, value = solve(steps)
+    # The table order: the piece, then the kings.
+    pieces = [piece, WHITE_KING, BLACK_KING]
+
+    wdl = [[None] * TABLE_SIZE, [None] * TABLE_SIZE]
+    dtz = [None] * TABLE_SIZE
+    for position in positions:
+        wk, pc, bk, white = position
+        v = value.get(position)
+        result = 0 if v is None else (2 if v > 0 else -2)
+        side = 0 if white else 1
+        idx = encode([pc, wk, bk])
+        if wdl[side][idx] is not None:
+            assert wdl[side][idx] == result + 2, (name, position)
+        wdl[side][idx] = result + 2
+        if white:
+            # White never captures or moves a pawn, so the distance to
+            # zeroing is the distance to mate.
+            stored = 0 if v is None else (v - 1) // 2
+            if dtz[idx] is not None:
+                assert dtz[idx] == stored, (name, position)
+            dtz[idx] = stored
+
+    def fill(values):
+        # Unreachable indices take the previous value, which compresses best.
+        previous = values[0] if values[0] is not None else 2
+        out = []
+        for v in values:
+            previous = previous if v is None else v
+            out.append(previous)
+        return out
+
+    wdl = [fill(values) for values in wdl]
+    dtz = fill(dtz)
+
+    # DTZ values are stored as indices into the map of the wins.
+    moves = sorted(set(dtz))
+    symbol = {m: i for i, m in enumerate(moves)}
+    write_table(os.path.join(directory, name + ".rtbw"), WDL_MAGIC, pieces, wdl)
+    write_table(
+        os.path.join(directory, name + ".rtbz"),
+        DTZ_MAGIC,
+        pieces,
+        [[symbol[m] for m in dtz]],
+        flags=FLAG_MAPPED,
+        maps=[moves, [], [], []],
+    )
+
+
+def main():
+    directory = sys.argv[1] if len(sys.argv) > 1 else os.path.dirname(os.path.abspath(__file__))
+    generate(directory, "KQvK", WHITE_QUEEN, QUEEN_STEPS)
+    generate(directory, "KRvK", WHITE_ROOK, ROOK_STEPS)
+
+
+if __name__ == "__main__":
+    main()
@@ -1 +1,78 @@
 const wasm = import("chess");
+
+wasm.then(chess => {
+  // Fill the difficulty picker with every level and its approximate Elo,
+  // and remember the chosen level between visits.
+  const difficulty = document.getElementById("difficulty");
+  for (let level = 1; level <= 20; level++) {
+    const option = document.createElement("option");
+    option.value = level;
+    option.textContent = `Level ${level} (~${chess.get_difficulty_elo(level)} Elo)`;
+    difficulty.appendChild(option);
+  }
+
+  const saved = Number(localStorage.getItem("difficulty"));
+  if (saved >= 1 && saved <= 20) {
+    chess.set_difficulty(saved);
+  }
+  difficulty.value = chess.get_difficulty();
+
+  difficulty.addEventListener("change", () => {
+    chess.set_difficulty(Number(difficulty.value));
+    localStorage.setItem("difficulty", difficulty.value);
+  });
+
+  // Let the computer think while the player does, if it was allowed to on
+  // an earlier visit.
+  const ponder = document.getElementById("ponder");
+  chess.set_ponder(localStorage.getItem("ponder") === "true");
+  ponder.checked = chess.get_ponder();
+  ponder.addEventListener("change", () => {
+    chess.set_ponder(ponder.checked);
+    localStorage.setItem("ponder", ponder.checked);
+  });
+
+  // Play the best move found so far instead of waiting for the search.
+  document.getElementById("move-now").addEventListener("click", () => chess.stop_thinking());
+
+  // Suggest a move to the player.
+  document.getElementById("hint").addEventListener("click", () => chess.show_hint());
+
+  // Draws are agreed on with buttons rather than moves on the board.
+  document.getElementById("offer-draw").addEventListener("click", () => chess.offer_draw());
+  document.getElementById("accept-draw").addEventListener("click", () => chess.accept_draw());
+
+  // Once the game is over, the engine reviews every move of it.
+  document.getElementById("review-game").addEventListener("click", () => chess.review_game());
+
+  // Puzzles are loaded from a CSV file in the format of the Lichess puzzle
+  // database, and the puzzle rating is kept between visits.
+  const puzzleRating = document.getElementById("puzzle-rating");
+  const showPuzzleRating = () => {
+    puzzleRating.textContent = `Puzzle rating: ${Math.round(chess.get_puzzle_rating())}`;
+  };
+  const savedRating = Number(localStorage.getItem("puzzleRating"));
+  if (savedRating > 0) {
+    chess.set_puzzle_rating(savedRating);
+  }
+  showPuzzleRating();
+
+  const nextPuzzle = document.getElementById("next-puzzle");
+  document.getElementById("puzzle-file").addEventListener("change", async event => {
+    const file = event.target.files[0];
+    if (!file) {
+      return;
+    }
+    try {
+      chess.load_puzzles(await file.text());
+      nextPuzzle.classList.remove("hidden");
+    } catch (e) {
+      console.error("Invalid puzzle file:", e);
+    }
+  });
+  nextPuzzle.addEventListener("click", async () => {
+    const rating = await chess.play_puzzle();
+    localStorage.setItem("puzzleRating", rating);
+    showPuzzleRating();
+  });
+}).catch(e => console.error("Error loading `chess`:", e));
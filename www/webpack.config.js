@@ -1,14 +1,34 @@
 const CopyWebpackPlugin = require("copy-webpack-plugin");
 const path = require('path');
 
-module.exports = {
-  entry: "./bootstrap.js",
-  output: {
-    path: path.resolve(__dirname, "dist"),
-    filename: "bootstrap.js",
+module.exports = [
+  {
+    entry: "./bootstrap.js",
+    output: {
+      path: path.resolve(__dirname, "dist"),
+      filename: "bootstrap.js",
+    },
+    mode: "development",
+    plugins: [
+      new CopyWebpackPlugin(['index.html'])
+    ],
+    // Cross-origin isolation lets the page share memory with the search
+    // worker, which is how it stops the computer's search.
+    devServer: {
+      headers: {
+        "Cross-Origin-Opener-Policy": "same-origin",
+        "Cross-Origin-Embedder-Policy": "require-corp",
+      },
+    },
   },
-  mode: "development",
-  plugins: [
-    new CopyWebpackPlugin(['index.html'])
-  ],
-};
+  // The search runs in a Web Worker, which loads its own copy of the module.
+  {
+    entry: "./worker.js",
+    target: "webworker",
+    output: {
+      path: path.resolve(__dirname, "dist"),
+      filename: "worker.js",
+    },
+    mode: "development",
+  },
+];
@@ -0,0 +1,6 @@
+// The computer searches for its moves in this worker, so that the page
+// stays responsive while it thinks. The module answers the messages of the
+// page by itself once it is started.
+import("chess")
+  .then(chess => chess.start_search_worker())
+  .catch(e => console.error("Error starting the search worker:", e));